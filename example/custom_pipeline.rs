@@ -24,6 +24,7 @@ use bevy::{
 use visual_du::{
     app_scaffold::{AppScaffoldPlugin, WindowSize},
     render::poly_rect::{PolyRectMeshHandle, PolyRectMeshPlugin},
+    theme::ThemeSource,
 };
 
 fn main() {
@@ -32,6 +33,7 @@ fn main() {
         .add_plugin(AppScaffoldPlugin {
             title: "Custom Pipeline",
             bin_module_path: module_path!(),
+            theme: ThemeSource::default(),
         })
         .add_plugin(PolyRectMeshPlugin)
         .add_plugin(RenderPolyRectPlugin)
@@ -0,0 +1,84 @@
+//! Compares ancestor size propagation strategies over a real directory tree.
+//!
+//! The tree is walked once up front so that only ingestion is timed:
+//!
+//! * `string keys` is the original approach, formatting every ancestor's relative path
+//!   into a `String` and looking it up in a `HashMap`, once per file per ancestor.
+//! * `fs tree` inserts into an [`FsTree`] in frame-sized batches, merging size deltas on
+//!   their way up so that each ancestor is touched once per batch.
+use std::{collections::HashMap, env, fs, time::Instant};
+use visual_du::fs::{walk_dir, FsEntity, FsTree};
+
+const BATCH_SIZE: usize = 1024;
+const ITERATIONS: u32 = 5;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let root_path_buf = fs::canonicalize(args.first().map_or(".", |p| p))?;
+
+    let walk_start = Instant::now();
+    let entities: Vec<FsEntity> =
+        walk_dir(&root_path_buf)?.filter_map(Result::ok).collect();
+    println!(
+        "walked {} entities in {:?}",
+        entities.len(),
+        walk_start.elapsed()
+    );
+
+    let mut string_key_total = 0;
+    let mut fs_tree_total = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        string_key_total += ingest_with_string_keys(&entities, &root_path_buf);
+        let string_key_elapsed = start.elapsed();
+
+        let start = Instant::now();
+        fs_tree_total += ingest_with_fs_tree(&entities);
+        let fs_tree_elapsed = start.elapsed();
+
+        println!(
+            "string keys: {string_key_elapsed:>12?}    fs tree: {fs_tree_elapsed:>12?}    \
+             ({speedup:.1}x)",
+            speedup = string_key_elapsed.as_secs_f64() / fs_tree_elapsed.as_secs_f64()
+        );
+    }
+
+    // Both strategies must agree on the size of the root
+    assert_eq!(string_key_total, fs_tree_total);
+    println!("root size: {}b", string_key_total / ITERATIONS as u64);
+
+    Ok(())
+}
+
+/// Returns the aggregate size of the root
+fn ingest_with_string_keys(entities: &[FsEntity], root_path: &std::path::Path) -> u64 {
+    let mut sizes_by_key: HashMap<String, u64> = HashMap::new();
+    for entity in entities {
+        let rel_path = entity.path.strip_prefix(root_path).unwrap();
+        let size_in_bytes = entity.size_in_bytes();
+        sizes_by_key.insert(rel_path.to_string_lossy().into(), size_in_bytes);
+
+        if size_in_bytes == 0 {
+            continue;
+        }
+        for ancestor_path in rel_path.ancestors().skip(1) {
+            let key: String = ancestor_path.to_string_lossy().into();
+            *sizes_by_key.get_mut(&key).unwrap() += size_in_bytes;
+        }
+    }
+
+    sizes_by_key[""]
+}
+
+/// Returns the aggregate size of the root
+fn ingest_with_fs_tree(entities: &[FsEntity]) -> u64 {
+    let mut fs_tree = FsTree::new();
+    for batch in entities.chunks(BATCH_SIZE) {
+        for entity in batch {
+            fs_tree.insert(entity);
+        }
+        fs_tree.propagate_sizes();
+    }
+
+    fs_tree.aggregate_size(fs_tree.root().unwrap())
+}
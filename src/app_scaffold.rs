@@ -1,4 +1,7 @@
-use crate::debug::DebugPlugin;
+use crate::{
+    debug::DebugPlugin,
+    theme::{ThemePlugin, ThemeSource},
+};
 use bevy::{
     log::{LogPlugin, LogSettings},
     math::const_vec2,
@@ -11,7 +14,6 @@ use tracing::Level;
 #[cfg(not(debug_assertions))]
 include!(concat!(env!("OUT_DIR"), "/add_embasset_assets.rs"));
 
-const WINDOW_COLOR: Color = Color::rgb(0.161, 0.173, 0.2);
 const INITIAL_WINDOW_SIZE: Vec2 = const_vec2!([1280.0, 800.0]);
 
 #[derive(Deref, DerefMut)]
@@ -20,6 +22,7 @@ pub struct WindowSize(pub Vec2);
 pub struct AppScaffoldPlugin {
     pub title: &'static str,
     pub bin_module_path: &'static str,
+    pub theme: ThemeSource,
 }
 impl Plugin for AppScaffoldPlugin {
     fn build(&self, app: &mut App) {
@@ -31,7 +34,6 @@ impl Plugin for AppScaffoldPlugin {
                 ..default()
             })
             .insert_resource(WindowSize(INITIAL_WINDOW_SIZE))
-            .insert_resource(ClearColor(WINDOW_COLOR))
             .insert_resource(LogSettings {
                 filter: "warn".into(),
                 level: Level::WARN,
@@ -47,31 +49,34 @@ impl Plugin for AppScaffoldPlugin {
             });
         }
 
-        app.add_plugin(DebugPlugin)
-            .add_plugins_with(DefaultPlugins, |group| {
-                // If we're in release mode, ensure we hijack the asset plugin so that
-                // we load assets embedded in the executable (using Embasset, check out
-                // build.dart), instead of the file system (it's not there!).
-                #[cfg(not(debug_assertions))]
-                let group = {
-                    use bevy::asset::AssetPlugin;
-                    use bevy_embasset::EmbassetPlugin;
+        app.add_plugin(ThemePlugin {
+            source: self.theme.clone(),
+        })
+        .add_plugin(DebugPlugin)
+        .add_plugins_with(DefaultPlugins, |group| {
+            // If we're in release mode, ensure we hijack the asset plugin so that
+            // we load assets embedded in the executable (using Embasset, check out
+            // build.dart), instead of the file system (it's not there!).
+            #[cfg(not(debug_assertions))]
+            let group = {
+                use bevy::asset::AssetPlugin;
+                use bevy_embasset::EmbassetPlugin;
 
-                    group.add_before::<AssetPlugin, _>(EmbassetPlugin::new(
-                        add_embasset_assets,
-                    ))
-                };
+                group.add_before::<AssetPlugin, _>(EmbassetPlugin::new(
+                    add_embasset_assets,
+                ))
+            };
 
-                // We disable the log plugin so we can setup the tracing subscriber the
-                // way we like it...
-                group
-                    .disable::<LogPlugin>()
-                    // ...and we also disable PBR because it's expensive even if it's
-                    // doing nothing (big data movement to the GPU, involving lights???).
-                    .disable::<PbrPlugin>()
-            })
-            .add_startup_system(create_ui_camera)
-            .add_system(update_window_size);
+            // We disable the log plugin so we can setup the tracing subscriber the
+            // way we like it...
+            group
+                .disable::<LogPlugin>()
+                // ...and we also disable PBR because it's expensive even if it's
+                // doing nothing (big data movement to the GPU, involving lights???).
+                .disable::<PbrPlugin>()
+        })
+        .add_startup_system(create_ui_camera)
+        .add_system(update_window_size);
     }
 }
 
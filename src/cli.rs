@@ -0,0 +1,245 @@
+use crate::{
+    export::ExportOptions,
+    fs::FsIngestBudget,
+    theme::ThemeSource,
+    ui::{ColorScheme, DiskUsageTreeOptions, TreeViewAnimationOptions, MAX_DEPTH_LIMIT},
+};
+use std::{fmt, ops::RangeInclusive, str::FromStr, time::Duration};
+
+pub const USAGE: &str = "\
+usage: main [OPTIONS] [PATH]
+
+Visualizes the disk usage of PATH (defaults to ./example).
+
+options:
+    --report             print a du-style report to stdout instead of opening a window
+    --tui                browse in the terminal instead of opening a window
+    --top <N>            (report) the number of largest directories to list [default: 10]
+    --max-depth <N>      the number of levels drawn, or the depth of the printed tree,
+                         from 1 to 64 [default: 6, or 3 for --report]
+    --adaptive-depth     draw as many levels as fit in the window
+    --layout <NAME>      how the tree is drawn: icicle, sunburst or treemap
+                         [default: icicle]
+    --orientation <NAME> which way the icicle grows: icicle (root at the top), flame
+                         (root at the bottom) or horizontal (left to right)
+                         [default: flame]
+    --colors <MODE[:PALETTE]>
+                         what blocks are colored by: position, hsl, file-type, age, owner,
+                         relative-size, or stable, which keeps colors fixed while a scan
+                         runs [default: position]. A palette can follow the mode, as in
+                         age:cividis; the first of each mode's is the default:
+                           position:       rainbow, sinebow, viridis*
+                           hsl:            hsl, cividis*
+                           file-type:      tableau10, okabe-ito*, dark2, set2
+                           age:            turbo, cividis*, viridis*
+                           owner:          category10, okabe-ito*, dark2
+                           relative-size:  inferno*, cividis*
+                           stable:         hsl, sinebow, okabe-ito*
+                         (* colorblind safe)
+    --theme <NAME|PATH>  a theme preset (dark, light or high-contrast), or a RON theme
+                         file, which is reloaded when it changes [default: theme.ron in
+                         the user config directory, then in assets, then dark]
+    --export-dir <DIR>   where Ctrl+E writes the selection's path list, cleanup script
+                         and manifest [default: .]
+    --export-separator <NAME>
+                         how the exported path list is separated: newline or nul
+                         [default: newline]
+    --all                (report) include files in the printed tree, not just directories
+    --bytes              (report) print exact byte counts instead of human-readable sizes
+    --animation-ms <N>   the length of layout animations, 0 to disable [default: 250]
+    --easing <NAME>      the easing of layout animations: linear, quad-in-out, cubic-out,
+                         or cubic-in-out [default: cubic-in-out]
+    --ingest-ms <N>      how long each frame may spend adding scanned entries to the
+                         tree, 0 for no limit; lower keeps the window responsive during
+                         huge scans, higher finishes them sooner [default: 4]
+    --ingest-entries <N> how many scanned entries each frame may add to the tree, 0 for
+                         no limit [default: 0]
+    -h, --help           print this message";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CliMode {
+    Gui,
+    Report,
+    Tui,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReportOptions {
+    pub top_n: usize,
+    pub max_depth: u16,
+    pub include_files: bool,
+    pub raw_bytes: bool,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            top_n: 10,
+            max_depth: 3,
+            include_files: false,
+            raw_bytes: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GuiOptions {
+    pub tree: DiskUsageTreeOptions,
+    pub animation: TreeViewAnimationOptions,
+    pub colors: ColorScheme,
+    pub theme: ThemeSource,
+    pub export: ExportOptions,
+    pub ingest_budget: FsIngestBudget,
+}
+
+#[derive(Clone, Debug)]
+pub struct CliArgs {
+    pub root_path: String,
+    pub mode: CliMode,
+    pub report: ReportOptions,
+    pub gui: GuiOptions,
+}
+
+impl Default for CliArgs {
+    fn default() -> Self {
+        Self {
+            root_path: "./example".into(),
+            mode: CliMode::Gui,
+            report: ReportOptions::default(),
+            gui: GuiOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    HelpRequested,
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+    },
+    OutOfRange {
+        option: String,
+        value: String,
+        range: String,
+    },
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::HelpRequested => write!(f, "{}", USAGE),
+            CliError::UnknownOption(option) => write!(f, "unknown option {option}"),
+            CliError::MissingValue(option) => write!(f, "{option} requires a value"),
+            CliError::InvalidValue { option, value } => {
+                write!(f, "invalid value {value:?} for {option}")
+            }
+            CliError::OutOfRange {
+                option,
+                value,
+                range,
+            } => write!(f, "{option} must be {range}, not {value}"),
+            CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument {arg:?}"),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl CliArgs {
+    /// Parses the process's arguments (skipping the binary name)
+    pub fn from_env() -> Result<Self, CliError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut cli_args = CliArgs::default();
+        let mut root_path: Option<String> = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Err(CliError::HelpRequested),
+                "--report" => cli_args.mode = CliMode::Report,
+                "--tui" => cli_args.mode = CliMode::Tui,
+                "--top" => cli_args.report.top_n = parse_value(&arg, args.next())?,
+                "--max-depth" => {
+                    let max_depth =
+                        parse_value_in(&arg, args.next(), 1..=MAX_DEPTH_LIMIT)?;
+                    cli_args.report.max_depth = max_depth;
+                    cli_args.gui.tree.max_depth = max_depth;
+                    cli_args.gui.tree.is_depth_adaptive = false;
+                }
+                "--adaptive-depth" => cli_args.gui.tree.is_depth_adaptive = true,
+                "--layout" => cli_args.gui.tree.layout = parse_value(&arg, args.next())?,
+                "--orientation" => {
+                    cli_args.gui.tree.orientation = parse_value(&arg, args.next())?
+                }
+                "--colors" => cli_args.gui.colors = parse_value(&arg, args.next())?,
+                "--theme" => cli_args.gui.theme = parse_value(&arg, args.next())?,
+                "--export-dir" => {
+                    cli_args.gui.export.dir = parse_value(&arg, args.next())?
+                }
+                "--export-separator" => {
+                    cli_args.gui.export.separator = parse_value(&arg, args.next())?
+                }
+                "--all" => cli_args.report.include_files = true,
+                "--bytes" => cli_args.report.raw_bytes = true,
+                "--animation-ms" => {
+                    cli_args.gui.animation.duration =
+                        Duration::from_millis(parse_value(&arg, args.next())?)
+                }
+                "--easing" => {
+                    cli_args.gui.animation.easing = parse_value(&arg, args.next())?
+                }
+                "--ingest-ms" => {
+                    let millis: u64 = parse_value(&arg, args.next())?;
+                    cli_args.gui.ingest_budget.max_time_per_frame =
+                        (millis > 0).then_some(Duration::from_millis(millis));
+                }
+                "--ingest-entries" => {
+                    let max_entities: usize = parse_value(&arg, args.next())?;
+                    cli_args.gui.ingest_budget.max_entities_per_frame =
+                        (max_entities > 0).then_some(max_entities);
+                }
+                _ if arg.starts_with('-') => return Err(CliError::UnknownOption(arg)),
+                _ if root_path.is_none() => root_path = Some(arg),
+                _ => return Err(CliError::UnexpectedArgument(arg)),
+            }
+        }
+
+        if let Some(root_path) = root_path {
+            cli_args.root_path = root_path;
+        }
+        Ok(cli_args)
+    }
+}
+
+fn parse_value<T: FromStr>(option: &str, value: Option<String>) -> Result<T, CliError> {
+    let value = value.ok_or_else(|| CliError::MissingValue(option.into()))?;
+    value.parse().map_err(|_| CliError::InvalidValue {
+        option: option.into(),
+        value,
+    })
+}
+
+/// Parses an option's value that has to be within `range`
+fn parse_value_in<T: FromStr + PartialOrd + fmt::Display>(
+    option: &str,
+    value: Option<String>,
+    range: RangeInclusive<T>,
+) -> Result<T, CliError> {
+    let parsed = parse_value(option, value)?;
+    if range.contains(&parsed) {
+        Ok(parsed)
+    } else {
+        Err(CliError::OutOfRange {
+            option: option.into(),
+            value: parsed.to_string(),
+            range: format!("between {} and {}", range.start(), range.end()),
+        })
+    }
+}
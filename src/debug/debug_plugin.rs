@@ -1,4 +1,5 @@
 use super::{PrettierFormatter, SpriteCountDiagnosticsPlugin, SPRITE_COUNT};
+use crate::theme::Theme;
 use bevy::{
     diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin},
     log::LogSettings,
@@ -30,7 +31,8 @@ impl Plugin for DebugPlugin {
             .add_plugin(FrameTimeDiagnosticsPlugin)
             // Diagnostics display
             .add_startup_system(setup_diagnostics_ui)
-            .add_system(display_diagnostics);
+            .add_system(display_diagnostics)
+            .add_system(apply_theme_to_diagnostics);
     }
 }
 
@@ -64,7 +66,11 @@ fn setup_tracing(maybe_settings: Option<Res<LogSettings>>) {
 #[derive(Component)]
 struct DiagnosticsText;
 
-fn setup_diagnostics_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_diagnostics_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
     // NOTE: This might not successfully load...but it's not entirely clear how to
     // properly handle the failure.
     let font = asset_server.load("fonts/Hack-Regular.ttf");
@@ -86,7 +92,7 @@ fn setup_diagnostics_ui(mut commands: Commands, asset_server: Res<AssetServer>)
                 TextStyle {
                     font: font.clone(),
                     font_size: 24.0,
-                    color: Color::WHITE,
+                    color: theme.diagnostics_text,
                 },
                 TextAlignment::default(),
             ),
@@ -128,3 +134,15 @@ fn display_diagnostics(
     let mut text = text.single_mut();
     text.sections[0].value = display_text;
 }
+
+fn apply_theme_to_diagnostics(
+    theme: Res<Theme>,
+    mut text: Query<&mut Text, With<DiagnosticsText>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].style.color = theme.diagnostics_text;
+    }
+}
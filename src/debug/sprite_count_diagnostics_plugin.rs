@@ -17,7 +17,11 @@ impl Plugin for SpriteCountDiagnosticsPlugin {
 }
 
 fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
-    diagnostics.add(Diagnostic::new(SPRITE_COUNT, "sprite_count", /* max_history_length` */ 20));
+    diagnostics.add(Diagnostic::new(
+        SPRITE_COUNT,
+        "sprite_count",
+        /* max_history_length` */ 20,
+    ));
 }
 
 fn diagnostic_system(counter: SpriteCounter, mut diagnostics: ResMut<Diagnostics>) {
@@ -0,0 +1,349 @@
+use crate::{
+    fs::{format_timestamp, human_readable_size, FsNodeId, FsNodeKind, FsTree},
+    shell::{os_str_bytes, shell_quote},
+};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+/// How the paths of an exported path list are separated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathListSeparator {
+    Newline,
+    /// For `xargs -0`, and paths with newlines in them
+    Nul,
+}
+
+impl PathListSeparator {
+    const ALL: [PathListSeparator; 2] =
+        [PathListSeparator::Newline, PathListSeparator::Nul];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PathListSeparator::Newline => "newline",
+            PathListSeparator::Nul => "nul",
+        }
+    }
+
+    fn byte(&self) -> u8 {
+        match self {
+            PathListSeparator::Newline => b'\n',
+            PathListSeparator::Nul => b'\0',
+        }
+    }
+}
+
+impl fmt::Display for PathListSeparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for PathListSeparator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|separator| separator.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Where exports are written, and how
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub dir: PathBuf,
+    pub separator: PathListSeparator,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            dir: ".".into(),
+            separator: PathListSeparator::Newline,
+        }
+    }
+}
+
+/// A node being exported, with everything the exports say about it
+pub struct ExportedNode {
+    /// Absolute
+    pub path: PathBuf,
+    pub kind: FsNodeKind,
+    pub size_in_bytes: u64,
+    pub file_count: u32,
+    pub dir_count: u32,
+}
+
+impl ExportedNode {
+    /// Describes `node_id`, with its path made absolute by `root_path`, which stands in
+    /// for the tree's root
+    pub fn new(fs_tree: &FsTree, root_path: &Path, node_id: FsNodeId) -> Self {
+        Self {
+            path: root_path.join(fs_tree.relative_path(node_id)),
+            kind: fs_tree.kind(node_id),
+            size_in_bytes: fs_tree.aggregate_size(node_id),
+            file_count: fs_tree.descendent_file_count(node_id),
+            dir_count: fs_tree.descendent_dir_count(node_id),
+        }
+    }
+}
+
+/// The files written by [`export_nodes`]
+pub struct ExportedFiles {
+    pub path_list: PathBuf,
+    pub script: PathBuf,
+    pub manifest: PathBuf,
+}
+
+/// Writes `nodes` to `options.dir` as a path list, a dry-run cleanup script and a JSON
+/// manifest. The files are named after the time of the export, so that earlier exports
+/// aren't overwritten.
+pub fn export_nodes(
+    fs_tree: &FsTree,
+    nodes: &[FsNodeId],
+    options: &ExportOptions,
+) -> io::Result<ExportedFiles> {
+    let root_path = fs::canonicalize(fs_tree.root_path())?;
+    let exported: Vec<ExportedNode> = nodes
+        .iter()
+        .map(|node_id| ExportedNode::new(fs_tree, &root_path, *node_id))
+        .collect();
+
+    let created_at = format_timestamp(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs() as i64),
+    );
+    let base_name = format!("visual_du-selection-{}", created_at.replace(':', ""));
+    fs::create_dir_all(&options.dir)?;
+    let files = ExportedFiles {
+        path_list: options.dir.join(format!("{base_name}.txt")),
+        script: options.dir.join(format!("{base_name}.sh")),
+        manifest: options.dir.join(format!("{base_name}.json")),
+    };
+
+    write_file(&files.path_list, |out| {
+        write_path_list(out, &exported, options.separator)
+    })?;
+    write_file(&files.script, |out| write_cleanup_script(out, &exported))?;
+    #[cfg(unix)]
+    fs::set_permissions(&files.script, fs::Permissions::from_mode(0o755))?;
+    write_file(&files.manifest, |out| {
+        write_manifest(out, &root_path, &created_at, &exported)
+    })?;
+    Ok(files)
+}
+
+fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out)?;
+    out.flush()
+}
+
+/// Writes each path followed by `separator`. Paths are written as they are on disk, even
+/// when they aren't UTF-8.
+pub fn write_path_list<W: Write>(
+    out: &mut W,
+    nodes: &[ExportedNode],
+    separator: PathListSeparator,
+) -> io::Result<()> {
+    for node in nodes {
+        out.write_all(&os_str_bytes(node.path.as_os_str()))?;
+        out.write_all(&[separator.byte()])?;
+    }
+    Ok(())
+}
+
+/// Writes a bash script with an `rm -rf` for each node, all commented out so that
+/// nothing happens until a reviewer uncomments them. Each command is preceded by the size
+/// it would free. Paths are quoted so that none of their bytes can end the comment (see
+/// [`shell_quote`]).
+pub fn write_cleanup_script<W: Write>(
+    out: &mut W,
+    nodes: &[ExportedNode],
+) -> io::Result<()> {
+    let total_size: u64 = nodes.iter().map(|node| node.size_in_bytes).sum();
+    writeln!(out, "#!/usr/bin/env bash")?;
+    writeln!(
+        out,
+        "# Removes {} paths, {} in total.",
+        nodes.len(),
+        human_readable_size(total_size)
+    )?;
+    writeln!(
+        out,
+        "# This is a dry run: uncomment the commands that should run once reviewed."
+    )?;
+    writeln!(out, "set -eu")?;
+    for node in nodes {
+        writeln!(out)?;
+        match node.kind {
+            FsNodeKind::Dir => writeln!(
+                out,
+                "# {} ({} bytes), {} files and {} directories",
+                human_readable_size(node.size_in_bytes),
+                node.size_in_bytes,
+                node.file_count,
+                node.dir_count
+            )?,
+            _ => writeln!(
+                out,
+                "# {} ({} bytes)",
+                human_readable_size(node.size_in_bytes),
+                node.size_in_bytes
+            )?,
+        }
+        out.write_all(b"# rm -rf -- ")?;
+        out.write_all(&shell_quote(&os_str_bytes(node.path.as_os_str())))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes a JSON object describing the root, when it was written, and each node. Paths
+/// that aren't UTF-8 can't be JSON strings as they are, so their strings replace what
+/// isn't UTF-8, and their exact bytes follow in a `root_bytes` or `path_bytes` array.
+pub fn write_manifest<W: Write>(
+    out: &mut W,
+    root_path: &Path,
+    created_at: &str,
+    nodes: &[ExportedNode],
+) -> io::Result<()> {
+    let total_size: u64 = nodes.iter().map(|node| node.size_in_bytes).sum();
+    writeln!(out, "{{")?;
+    writeln!(out, "  {},", json_path_fields("root", root_path))?;
+    writeln!(out, "  \"created_at\": \"{created_at}Z\",")?;
+    writeln!(out, "  \"total_size_in_bytes\": {total_size},")?;
+    writeln!(out, "  \"entries\": [")?;
+    for (i, node) in nodes.iter().enumerate() {
+        let kind = match node.kind {
+            FsNodeKind::File => "file",
+            FsNodeKind::Dir => "dir",
+            FsNodeKind::Symlink => "symlink",
+            FsNodeKind::Other => "other",
+        };
+        writeln!(
+            out,
+            "    {{{}, \"kind\": \"{kind}\", \"size_in_bytes\": {}, \
+             \"file_count\": {}, \"dir_count\": {}}}{}",
+            json_path_fields("path", &node.path),
+            node.size_in_bytes,
+            node.file_count,
+            node.dir_count,
+            if i + 1 < nodes.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(out, "  ]")?;
+    writeln!(out, "}}")
+}
+
+/// The `key` field of `path`, followed by a `{key}_bytes` field if the path isn't UTF-8
+fn json_path_fields(key: &str, path: &Path) -> String {
+    let mut fields = format!("\"{key}\": {}", json_string(&path.to_string_lossy()));
+    if path.to_str().is_none() {
+        let bytes: Vec<String> = os_str_bytes(path.as_os_str())
+            .iter()
+            .map(u8::to_string)
+            .collect();
+        fields.push_str(&format!(", \"{key}_bytes\": [{}]", bytes.join(", ")));
+    }
+    fields
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> ExportedNode {
+        ExportedNode {
+            path: PathBuf::from(path),
+            kind: FsNodeKind::File,
+            size_in_bytes: 1,
+            file_count: 1,
+            dir_count: 0,
+        }
+    }
+
+    #[test]
+    fn cleanup_script_paths_cant_escape_their_comments() {
+        let nodes = [
+            file("/tmp/plain's"),
+            file("/tmp/a\nrm -rf ~\n"),
+            file("/tmp/b\r\x1b[2K\\"),
+        ];
+        let mut script = vec![];
+        write_cleanup_script(&mut script, &nodes).unwrap();
+        let script = String::from_utf8(script).unwrap();
+
+        // Apart from `set -eu`, nothing but comments
+        for line in script.lines() {
+            assert!(
+                line.is_empty() || line == "set -eu" || line.starts_with('#'),
+                "{line:?} would run"
+            );
+        }
+        assert!(script.contains(r"# rm -rf -- '/tmp/plain'\''s'"));
+        assert!(script.contains(r"# rm -rf -- $'/tmp/a\nrm -rf ~\n'"));
+        assert!(script.contains(r"# rm -rf -- $'/tmp/b\r\x1b[2K\\'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn manifest_keeps_the_bytes_of_paths_that_arent_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let nodes = [
+            file("/tmp/plain"),
+            ExportedNode {
+                path: OsStr::from_bytes(b"/tmp/\xff").into(),
+                ..file("")
+            },
+        ];
+        let mut manifest = vec![];
+        write_manifest(
+            &mut manifest,
+            Path::new("/tmp"),
+            "2026-01-01T00:00:00",
+            &nodes,
+        )
+        .unwrap();
+        let manifest = String::from_utf8(manifest).unwrap();
+
+        assert!(manifest.contains(r#"{"path": "/tmp/plain", "kind""#));
+        assert!(manifest.contains(
+            r#"{"path": "/tmp/�", "path_bytes": [47, 116, 109, 112, 47, 255], "kind""#
+        ));
+        assert!(manifest.contains(r#""root": "/tmp","#));
+    }
+}
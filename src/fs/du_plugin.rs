@@ -1,7 +1,11 @@
-use super::{walk_dir, FsEntity};
-use bevy::prelude::*;
-use crossbeam_channel::bounded;
-use std::{fs, thread};
+use super::{spawn_batched_walk, FsEntity, FsNodeId, FsNodeKind, FsTree};
+use bevy::{prelude::*, utils::HashMap};
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::{
+    collections::VecDeque,
+    fs,
+    time::{Duration, Instant},
+};
 use tracing::debug;
 use valuable::{Valuable, Value};
 
@@ -32,8 +36,29 @@ impl Valuable for FsEntityKey {
     }
 }
 
-#[derive(Component, Debug, Deref)]
-pub struct FsEntityComponent(FsEntity);
+/// Marks an entity that has been materialized from a node in the [`FsTree`]. Only the
+/// small slice of the tree that can be displayed is ever materialized.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct FsEntityComponent {
+    pub node_id: FsNodeId,
+    pub depth: u16,
+    pub kind: FsNodeKind,
+}
+
+#[allow(dead_code)]
+impl FsEntityComponent {
+    pub fn is_dir(&self) -> bool {
+        self.kind == FsNodeKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == FsNodeKind::File
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FsNodeKind::Symlink
+    }
+}
 
 #[derive(Component, Debug, Default)]
 pub struct FsAggregateSize {
@@ -41,19 +66,129 @@ pub struct FsAggregateSize {
 }
 
 #[derive(Deref)]
-struct FsStreamReceiver(crossbeam_channel::Receiver<FsEntity>);
+struct FsStreamReceiver(Receiver<Vec<FsEntity>>);
+
+/// Entities received from the walker that didn't fit into a previous frame's budget
+#[derive(Default, Deref, DerefMut)]
+struct FsIngestBacklog(VecDeque<FsEntity>);
+
+#[derive(Default)]
+pub struct FsWalkState {
+    /// Set once everything the walker found is in the tree
+    pub is_complete: bool,
+}
+
+/// Requests that a directory be walked again, replacing everything the tree has below it.
+/// Files are rescanned along with their directory.
+pub struct FsRescanRequest(pub FsNodeId);
+
+/// Reports that a node was deleted from disk, which removes it from the tree and takes its
+/// size out of its ancestors'
+pub struct FsNodeDeleted(pub FsNodeId);
+
+/// A walk of a directory that's already in the tree
+struct FsRescan {
+    target: FsNodeId,
+    receiver: Receiver<Vec<FsEntity>>,
+    backlog: VecDeque<FsEntity>,
+    /// Maps the IDs assigned by the rescan's walk to the tree's
+    node_ids: HashMap<FsNodeId, FsNodeId>,
+}
+
+#[derive(Default, Deref, DerefMut)]
+struct FsRescans(Vec<FsRescan>);
 
+/// Nodes inserted into the tree this frame, waiting to be considered for materialization
+#[derive(Default, Deref, DerefMut)]
+struct FsNewNodes(Vec<FsNodeId>);
+
+/// Maps the materialized nodes of the [`FsTree`] to their entities
 #[derive(Deref, DerefMut)]
-struct FsEntityMap(bevy::utils::HashMap<String, Entity>);
+pub struct FsEntityMap(bevy::utils::HashMap<FsNodeId, Entity>);
+
+/// Controls which nodes of the [`FsTree`] are materialized as entities. Everything else
+/// lives only in the tree.
+pub struct FsMaterializationOptions {
+    /// Nodes at this depth or shallower are materialized
+    pub max_depth: u16,
+    /// If set, this node's descendents are also materialized, down to `max_depth` levels
+    /// below it. This lets a view that is zoomed into a deep directory show its contents.
+    pub expanded_root: Option<FsNodeId>,
+    /// Nodes smaller than this fraction of the root they're drawn under (the expanded
+    /// root, if they're below it) are too small to be seen, and aren't materialized until
+    /// they grow. This keeps directories with huge numbers of tiny entries cheap.
+    pub min_size_fraction: f64,
+}
+
+impl Default for FsMaterializationOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: u16::MAX,
+            expanded_root: None,
+            min_size_fraction: 0.0,
+        }
+    }
+}
+
+impl FsMaterializationOptions {
+    fn should_materialize(&self, fs_tree: &FsTree, node_id: FsNodeId) -> bool {
+        if Some(node_id) == self.expanded_root {
+            return true;
+        }
+
+        let depth = fs_tree.depth(node_id);
+        let drawn_under = match self.expanded_root {
+            Some(expanded_root)
+                if depth
+                    <= fs_tree.depth(expanded_root).saturating_add(self.max_depth)
+                    && fs_tree.ancestors(node_id).any(|a| a == expanded_root) =>
+            {
+                expanded_root
+            }
+            _ if depth <= self.max_depth => FsNodeId::ROOT,
+            _ => return false,
+        };
+
+        fs_tree.aggregate_size(node_id) as f64
+            >= self.min_size_fraction * fs_tree.aggregate_size(drawn_under) as f64
+    }
+}
+
+/// Limits the amount of ingestion work done each frame, so that huge scans stream in
+/// without dragging down the frame rate. Ingestion stops for the frame as soon as either
+/// limit is reached.
+#[derive(Clone, Copy, Debug)]
+pub struct FsIngestBudget {
+    pub max_entities_per_frame: Option<usize>,
+    pub max_time_per_frame: Option<Duration>,
+}
+
+impl Default for FsIngestBudget {
+    fn default() -> Self {
+        Self {
+            max_entities_per_frame: None,
+            max_time_per_frame: Some(Duration::from_millis(4)),
+        }
+    }
+}
+
+impl FsIngestBudget {
+    fn is_exhausted(&self, ingested_count: usize, started_at: Instant) -> bool {
+        matches!(self.max_entities_per_frame, Some(max) if ingested_count >= max)
+            || matches!(self.max_time_per_frame, Some(max) if started_at.elapsed() >= max)
+    }
+}
 
 pub struct DiskUsageWalkConfig {
     pub root_path: std::path::PathBuf,
+    pub ingest_budget: FsIngestBudget,
 }
 
 impl DiskUsageWalkConfig {
     pub fn new(path: String) -> Self {
         Self {
             root_path: fs::canonicalize(path).unwrap(),
+            ingest_budget: default(),
         }
     }
 }
@@ -62,6 +197,7 @@ impl Default for DiskUsageWalkConfig {
     fn default() -> Self {
         Self {
             root_path: fs::canonicalize(".").unwrap(),
+            ingest_budget: default(),
         }
     }
 }
@@ -70,10 +206,24 @@ pub struct DiskUsagePlugin;
 impl Plugin for DiskUsagePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<DiskUsageWalkConfig>()
+            .init_resource::<FsMaterializationOptions>()
+            .init_resource::<FsNewNodes>()
+            .init_resource::<FsIngestBacklog>()
+            .init_resource::<FsWalkState>()
+            .init_resource::<FsRescans>()
+            .add_event::<FsRescanRequest>()
+            .add_event::<FsNodeDeleted>()
             .add_startup_system(start_dir_walk)
-            .add_system(spawn_fs_entities)
-            .add_system(establish_parentage)
-            .add_system(increment_ancestor_sizes_on_add);
+            .add_system(remove_deleted_nodes)
+            .add_system(start_rescans.after(remove_deleted_nodes))
+            .add_system(ingest_fs_entities.after(start_rescans))
+            .add_system(materialize_fs_entities.after(ingest_fs_entities))
+            .add_system(
+                materialize_fs_entities_on_options_change.after(materialize_fs_entities),
+            )
+            .add_system(
+                update_aggregate_sizes.after(materialize_fs_entities_on_options_change),
+            );
     }
 }
 
@@ -81,127 +231,347 @@ fn start_dir_walk(mut commands: Commands, config: Res<DiskUsageWalkConfig>) {
     let root_path = &config.root_path;
     info!(root_path = root_path.as_value(), "starting directory walk");
 
-    let (send_channel, receive_channel) = bounded::<FsEntity>(64);
-    let root_path_for_move = root_path.clone(); // We need a scope-appropriate lifetime
-    thread::spawn(move || {
-        for entity_res in walk_dir(root_path_for_move).unwrap() {
-            let entity = entity_res.unwrap();
-            match send_channel.send(entity) {
-                Ok(_) => {}
-                Err(e) => error!(error = %e, "Error encountered while sending"),
-            }
+    commands.insert_resource(FsStreamReceiver(spawn_batched_walk(root_path.clone())));
+    commands.insert_resource(FsTree::new());
+    commands.insert_resource(FsEntityMap(bevy::utils::HashMap::new()));
+}
+
+/// Removes everything below the requested directories, and starts walking them again.
+/// Rescans wait for the first walk to complete, so that nothing is found twice.
+fn start_rescans(
+    mut commands: Commands,
+    mut requests: EventReader<FsRescanRequest>,
+    mut pending_requests: Local<Vec<FsNodeId>>,
+    mut rescans: ResMut<FsRescans>,
+    mut fs_tree: ResMut<FsTree>,
+    mut fs_entity_map: ResMut<FsEntityMap>,
+    walk_state: Res<FsWalkState>,
+) {
+    pending_requests.extend(requests.iter().map(|FsRescanRequest(node_id)| *node_id));
+    if !walk_state.is_complete || pending_requests.is_empty() {
+        return;
+    }
+
+    for node_id in std::mem::take(&mut *pending_requests) {
+        // Requests that waited for the scan may be for nodes deleted in the meantime
+        if !fs_tree.contains(node_id) {
+            continue;
         }
-    });
+        let target = match fs_tree.kind(node_id) {
+            FsNodeKind::Dir => node_id,
+            _ => match fs_tree.parent(node_id) {
+                Some(parent) => parent,
+                None => continue,
+            },
+        };
 
-    commands.insert_resource(FsStreamReceiver(receive_channel));
-    commands.insert_resource(FsEntityMap(bevy::utils::HashMap::new()));
+        // A rescan of the target or one of its ancestors already covers it, and rescans
+        // of its descendents are covered by this one
+        if rescans.iter().any(|rescan| {
+            rescan.target == target
+                || fs_tree.ancestors(target).any(|a| a == rescan.target)
+        }) {
+            continue;
+        }
+        rescans.retain(|rescan| !fs_tree.ancestors(rescan.target).any(|a| a == target));
+
+        let removed = fs_tree.remove_children(target);
+        despawn_removed_nodes(&mut commands, &mut fs_entity_map, &fs_tree, &removed);
+
+        let path = fs_tree.path(target);
+        info!(
+            path = path.as_value(),
+            removed_count = removed.len(),
+            "rescanning"
+        );
+        rescans.push(FsRescan {
+            target,
+            receiver: spawn_batched_walk(path),
+            backlog: default(),
+            // The root of the rescan's walk is the target itself
+            node_ids: HashMap::from_iter([(FsNodeId::ROOT, target)]),
+        });
+    }
 }
 
-fn spawn_fs_entities(
+fn remove_deleted_nodes(
     mut commands: Commands,
+    mut deleted_events: EventReader<FsNodeDeleted>,
+    mut rescans: ResMut<FsRescans>,
+    mut fs_tree: ResMut<FsTree>,
     mut fs_entity_map: ResMut<FsEntityMap>,
-    fs_entity_stream: ResMut<FsStreamReceiver>,
+) {
+    for FsNodeDeleted(node_id) in deleted_events.iter() {
+        if !fs_tree.contains(*node_id) || fs_tree.parent(*node_id).is_none() {
+            continue;
+        }
+        // Rescans within the deleted node have nothing left to find
+        rescans.retain(|rescan| {
+            rescan.target != *node_id
+                && !fs_tree.ancestors(rescan.target).any(|a| a == *node_id)
+        });
+
+        let removed = fs_tree.remove(*node_id);
+        despawn_removed_nodes(&mut commands, &mut fs_entity_map, &fs_tree, &removed);
+        debug!(node = %node_id, removed_count = removed.len(), "deleted node removed");
+    }
+}
+
+/// Despawns the entities of nodes removed from the tree. `removed` has to hold whole
+/// subtrees, parents before children.
+pub(crate) fn despawn_removed_nodes(
+    commands: &mut Commands,
+    fs_entity_map: &mut FsEntityMap,
+    fs_tree: &FsTree,
+    removed: &[FsNodeId],
+) {
+    for node_id in removed {
+        let entity = match fs_entity_map.remove(node_id) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        // Despawning the top of each subtree takes its descendents with it
+        let is_subtree_root = !matches!(
+            fs_tree.parent(*node_id),
+            Some(parent) if fs_tree.is_removed(parent)
+        );
+        if is_subtree_root {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// The next entity from a walk, taken from its backlog before its channel
+fn next_walked_entity(
+    backlog: &mut VecDeque<FsEntity>,
+    receiver: &Receiver<Vec<FsEntity>>,
+) -> Result<FsEntity, TryRecvError> {
+    loop {
+        if let Some(fs_entity) = backlog.pop_front() {
+            return Ok(fs_entity);
+        }
+        backlog.extend(receiver.try_recv()?);
+    }
+}
+
+/// Moves walked entities off the channel and into the tree, until the frame's ingest
+/// budget runs out. Rescans share the budget, once the first walk is complete.
+#[allow(clippy::too_many_arguments)]
+fn ingest_fs_entities(
+    mut fs_tree: ResMut<FsTree>,
+    mut new_nodes: ResMut<FsNewNodes>,
+    mut backlog: ResMut<FsIngestBacklog>,
+    mut walk_state: ResMut<FsWalkState>,
+    mut rescans: ResMut<FsRescans>,
+    fs_entity_stream: Res<FsStreamReceiver>,
     config: Res<DiskUsageWalkConfig>,
 ) {
-    for fs_entity in fs_entity_stream.try_iter() {
+    let started_at = Instant::now();
+    let budget = config.ingest_budget;
+    let mut ingested_count = 0;
+
+    while !budget.is_exhausted(ingested_count, started_at) {
+        let fs_entity = match next_walked_entity(&mut backlog, &fs_entity_stream) {
+            Ok(fs_entity) => fs_entity,
+            Err(TryRecvError::Disconnected) => {
+                if !walk_state.is_complete {
+                    info!("directory walk complete");
+                    walk_state.is_complete = true;
+                }
+                break;
+            }
+            Err(TryRecvError::Empty) => break,
+        };
+
         let rel_path = relative_to!(fs_entity.path, config.root_path);
-        let key: String = rel_path.to_string_lossy().into();
-        debug!(path = rel_path.as_value(), "spawning entity");
-
-        fs_entity_map.insert(
-            key.clone(),
-            commands
-                .spawn()
-                .insert(FsAggregateSize {
-                    size_in_bytes: fs_entity.size_in_bytes(),
-                })
-                .insert(FsEntityKey(key))
-                .insert(FsEntityComponent(fs_entity))
-                .id(),
+        debug!(path = rel_path.as_value(), "ingesting entity");
+        new_nodes.push(fs_tree.insert(&fs_entity));
+        ingested_count += 1;
+    }
+
+    let mut completed_targets = vec![];
+    for rescan in rescans.iter_mut() {
+        while !budget.is_exhausted(ingested_count, started_at) {
+            let fs_entity =
+                match next_walked_entity(&mut rescan.backlog, &rescan.receiver) {
+                    Ok(fs_entity) => fs_entity,
+                    Err(TryRecvError::Disconnected) => {
+                        completed_targets.push(rescan.target);
+                        break;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                };
+
+            let parent = match fs_entity.parent_id {
+                Some(walk_parent) => rescan.node_ids[&walk_parent],
+                // The target is already in the tree
+                None => continue,
+            };
+            let node_id = fs_tree.insert_child(parent, &fs_entity);
+            rescan.node_ids.insert(fs_entity.id, node_id);
+            new_nodes.push(node_id);
+            ingested_count += 1;
+        }
+    }
+    if !completed_targets.is_empty() {
+        rescans.retain(|rescan| !completed_targets.contains(&rescan.target));
+        info!(count = completed_targets.len(), "rescans complete");
+    }
+
+    if ingested_count > 0 {
+        debug!(
+            ingested_count,
+            backlog_len = backlog.len(),
+            elapsed = ?started_at.elapsed(),
+            "ingested entities"
         );
+        fs_tree.propagate_sizes();
     }
 }
 
-/// Establishes the parentage of fs entities in the data layer
-fn establish_parentage(
+/// Spawns entities for newly ingested nodes that the materialization options call for,
+/// parenting each to its parent node's entity. Nodes whose parent isn't materialized wait
+/// for it to be.
+fn materialize_fs_entities(
     mut commands: Commands,
-    added_fs_entities: Query<
-        (Entity, &FsEntityKey, &FsEntityComponent),
-        Added<FsEntityComponent>,
-    >,
-    fs_entity_map: Res<FsEntityMap>,
-    config: Res<DiskUsageWalkConfig>,
+    mut fs_entity_map: ResMut<FsEntityMap>,
+    mut new_nodes: ResMut<FsNewNodes>,
+    fs_tree: Res<FsTree>,
+    options: Res<FsMaterializationOptions>,
 ) {
-    for (child_entity, fs_key, fs_entity) in added_fs_entities.iter() {
-        let path = &fs_entity.path;
-        let rel_path = relative_to!(path, config.root_path);
-        debug!(path = fs_key.as_value(), "establishing parentage");
-        if let Some(parent_path) = rel_path.parent() {
-            debug!(
-                path = fs_key.as_value(),
-                parent_path = parent_path.as_value(),
-                "linking to parent",
-            );
-            let parent_key: String = parent_path.to_string_lossy().into();
-            let parent_entity = fs_entity_map.get(&parent_key).unwrap();
-            commands.entity(*parent_entity).add_child(child_entity);
-        } else {
-            debug!(
-                path = fs_key.as_value(),
-                "adding FsRootEntityComponent marker to root fs entity"
-            );
-            commands.entity(child_entity).insert(FsRootComponent {});
+    for node_id in new_nodes.drain(..) {
+        let is_parent_materialized = !matches!(
+            fs_tree.parent(node_id),
+            Some(parent) if !fs_entity_map.contains_key(&parent)
+        );
+        if !fs_tree.is_removed(node_id)
+            && is_parent_materialized
+            && options.should_materialize(&fs_tree, node_id)
+        {
+            materialize_node(&mut commands, &mut fs_entity_map, &fs_tree, node_id);
         }
     }
 }
 
-fn increment_ancestor_sizes_on_add(
-    added_fs_entities: Query<
-        (&FsEntityKey, &FsEntityComponent),
-        Added<FsEntityComponent>,
-    >,
-    mut all_sizes: Query<&mut FsAggregateSize>,
-    fs_entity_map: Res<FsEntityMap>,
-    config: Res<DiskUsageWalkConfig>,
+/// When the materialization options widen (a deeper max depth, or a newly expanded
+/// subtree), spawns entities for nodes that were previously left in the tree
+fn materialize_fs_entities_on_options_change(
+    mut commands: Commands,
+    mut fs_entity_map: ResMut<FsEntityMap>,
+    fs_tree: Res<FsTree>,
+    options: Res<FsMaterializationOptions>,
 ) {
-    for (fs_key, fs_entity) in added_fs_entities.iter() {
-        let rel_path = relative_to!(fs_entity.path, config.root_path);
-        let key: String = rel_path.to_string_lossy().into();
-        let size_in_bytes = all_sizes
-            .get(*fs_entity_map.get(&key).unwrap())
-            .unwrap()
-            .size_in_bytes;
+    if !options.is_changed() || fs_tree.is_empty() {
+        return;
+    }
 
-        if size_in_bytes == 0 {
-            debug!(
-                path = fs_entity.path.as_value(),
-                "increasing ancestor sizes...skip (0 size)",
-            );
-            continue;
-        } else {
-            debug!(
-                path = fs_key.as_value(),
-                "increasing ancestor sizes +{size_in_bytes}b",
-            );
+    let span = debug_span!("materializing nodes for new options");
+    let _enter_guard = span.enter();
+
+    for root in std::iter::once(FsNodeId::ROOT).chain(options.expanded_root) {
+        materialize_subtree(&mut commands, &mut fs_entity_map, &fs_tree, &options, root);
+    }
+}
+
+/// Materializes `node_id`, if it isn't already, and whichever of its descendents the
+/// options call for. Its parent has to be materialized.
+fn materialize_subtree(
+    commands: &mut Commands,
+    fs_entity_map: &mut FsEntityMap,
+    fs_tree: &FsTree,
+    options: &FsMaterializationOptions,
+    node_id: FsNodeId,
+) {
+    // Top down, so that parents are always materialized before their children
+    let mut stack: Vec<FsNodeId> = vec![node_id];
+    while let Some(node_id) = stack.pop() {
+        if !fs_entity_map.contains_key(&node_id) {
+            materialize_node(commands, fs_entity_map, fs_tree, node_id);
         }
 
-        // Loop over the path's ancestors, increasing the size of each by the size of the entity
-        // we're processing.
-        //
-        // NOTE: The skip(1) is to skip the entity itself
-        let ancestor_paths = rel_path.ancestors().skip(1);
-        for ancestor_path in ancestor_paths {
-            let entity_key: String = ancestor_path.to_string_lossy().into();
-            let ancestor_entity: &Entity = fs_entity_map.get(&entity_key).unwrap();
-
-            if let Ok(mut ancestor_agg_size) = all_sizes.get_mut(*ancestor_entity) {
-                ancestor_agg_size.size_in_bytes += size_in_bytes;
-                debug!(
-                    path = ancestor_path.as_value(),
-                    new_size = ancestor_agg_size.size_in_bytes,
+        stack.extend(
+            fs_tree
+                .children(node_id)
+                .filter(|child| options.should_materialize(fs_tree, *child)),
+        );
+    }
+}
+
+fn materialize_node(
+    commands: &mut Commands,
+    fs_entity_map: &mut FsEntityMap,
+    fs_tree: &FsTree,
+    node_id: FsNodeId,
+) {
+    let key: String = fs_tree.relative_path(node_id).to_string_lossy().into();
+    debug!(path = key.as_value(), "spawning entity");
+
+    let entity = commands
+        .spawn()
+        .insert(FsAggregateSize {
+            size_in_bytes: fs_tree.aggregate_size(node_id),
+        })
+        .insert(FsEntityKey(key))
+        .insert(FsEntityComponent {
+            node_id,
+            depth: fs_tree.depth(node_id),
+            kind: fs_tree.kind(node_id),
+        })
+        .id();
+    fs_entity_map.insert(node_id, entity);
+
+    match fs_tree.parent(node_id) {
+        Some(parent_id) => {
+            let parent_entity = fs_entity_map.get(&parent_id).unwrap();
+            commands.entity(*parent_entity).add_child(entity);
+        }
+        None => {
+            debug!("adding FsRootEntityComponent marker to root fs entity");
+            commands.entity(entity).insert(FsRootComponent {});
+        }
+    }
+}
+
+/// Copies aggregate sizes that changed in the tree onto their materialized entities, and
+/// materializes nodes that have grown big enough to be seen
+fn update_aggregate_sizes(
+    mut commands: Commands,
+    mut fs_tree: ResMut<FsTree>,
+    mut all_sizes: Query<&mut FsAggregateSize>,
+    mut fs_entity_map: ResMut<FsEntityMap>,
+    options: Res<FsMaterializationOptions>,
+) {
+    for node_id in fs_tree.drain_changed() {
+        let entity = match fs_entity_map.get(&node_id) {
+            Some(entity) => *entity,
+            None => {
+                let is_parent_materialized = matches!(
+                    fs_tree.parent(node_id),
+                    Some(parent) if fs_entity_map.contains_key(&parent)
                 );
-            } else {
-                error!("(error!!!)");
+                if is_parent_materialized
+                    && !fs_tree.is_removed(node_id)
+                    && options.should_materialize(&fs_tree, node_id)
+                {
+                    materialize_subtree(
+                        &mut commands,
+                        &mut fs_entity_map,
+                        &fs_tree,
+                        &options,
+                        node_id,
+                    );
+                }
+                continue;
+            }
+        };
+
+        // Entities spawned this frame don't exist yet, but were created with the
+        // up-to-date size
+        if let Ok(mut agg_size) = all_sizes.get_mut(entity) {
+            let size_in_bytes = fs_tree.aggregate_size(node_id);
+            if agg_size.size_in_bytes != size_in_bytes {
+                agg_size.size_in_bytes = size_in_bytes;
+                debug!(node = %node_id, new_size = size_in_bytes);
             }
         }
     }
@@ -0,0 +1,654 @@
+use super::FsEntity;
+use bevy::utils::HashMap;
+use std::{
+    collections::{BTreeSet, BinaryHeap},
+    ffi::OsStr,
+    fmt, path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+/// Sentinel used in place of `Option<u32>` for node links, to keep nodes small
+const NO_NODE: u32 = u32::MAX;
+
+/// Identifies a node in an [`FsTree`]. IDs are assigned by the walker in the order
+/// entities are yielded, so the root is always `0`, and a parent's ID is always lower than
+/// its children's.
+///
+/// The places of removed nodes are reused by nodes inserted by rescans. Each reuse bumps
+/// the place's generation, so the ID of a removed node never refers to its successor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FsNodeId {
+    index: u32,
+    generation: u32,
+}
+
+impl FsNodeId {
+    pub const ROOT: FsNodeId = FsNodeId {
+        index: 0,
+        generation: 0,
+    };
+
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    /// The ID of a walked entity, which is always the first generation of its place
+    pub(crate) fn from_index(index: usize) -> Self {
+        FsNodeId {
+            index: index as u32,
+            generation: 0,
+        }
+    }
+}
+
+impl fmt::Display for FsNodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.generation {
+            0 => write!(f, "#{}", self.index),
+            generation => write!(f, "#{}.{}", self.index, generation),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct NameId(u32);
+
+/// Stores every distinct path component once. Trees with millions of entries repeat the
+/// same handful of names (`.git`, `node_modules`, `mod.rs`, ...) over and over.
+#[derive(Default)]
+struct NameInterner {
+    names: Vec<Arc<OsStr>>,
+    lookup: HashMap<Arc<OsStr>, NameId>,
+}
+
+impl NameInterner {
+    fn intern(&mut self, name: &OsStr) -> NameId {
+        if let Some(id) = self.lookup.get(name) {
+            return *id;
+        }
+
+        let id = NameId(self.names.len() as u32);
+        let name: Arc<OsStr> = Arc::from(name);
+        self.names.push(name.clone());
+        self.lookup.insert(name, id);
+        id
+    }
+
+    fn resolve(&self, id: NameId) -> &OsStr {
+        &self.names[id.0 as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsNodeKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// The subset of [`std::fs::Metadata`] that we care about, packed down to a few words
+#[derive(Clone, Copy, Debug)]
+pub struct FsNodeMetadata {
+    pub kind: FsNodeKind,
+    /// Unix permission bits (including the file type bits)
+    pub mode: u32,
+    pub uid: u32,
+    /// Seconds since the Unix epoch
+    pub mtime_secs: i64,
+}
+
+impl FsNodeMetadata {
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            FsNodeKind::Symlink
+        } else if file_type.is_dir() {
+            FsNodeKind::Dir
+        } else if file_type.is_file() {
+            FsNodeKind::File
+        } else {
+            FsNodeKind::Other
+        };
+
+        #[cfg(unix)]
+        let (mode, uid, mtime_secs) = (metadata.mode(), metadata.uid(), metadata.mtime());
+        #[cfg(not(unix))]
+        let (mode, uid, mtime_secs) = (
+            0,
+            0,
+            metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs() as i64),
+        );
+
+        Self {
+            kind,
+            mode,
+            uid,
+            mtime_secs,
+        }
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        if self.mtime_secs >= 0 {
+            UNIX_EPOCH + Duration::from_secs(self.mtime_secs as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(self.mtime_secs.unsigned_abs())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct FsNode {
+    name: NameId,
+    parent: u32,
+    first_child: u32,
+    last_child: u32,
+    next_sibling: u32,
+    depth: u16,
+    /// Set when the aggregate size has changed since the last call to
+    /// [`FsTree::drain_changed`]
+    is_changed: bool,
+    /// Set when the node has been removed from the tree. Removed nodes are unreachable from
+    /// the root, and their places are free to be reused.
+    is_removed: bool,
+    /// How many times the node's place has been reused
+    generation: u32,
+    size_in_bytes: u64,
+    aggregate_size: u64,
+    descendent_files: u32,
+    descendent_dirs: u32,
+    metadata: FsNodeMetadata,
+}
+
+/// Changes to a node's aggregates that are waiting to be propagated
+#[derive(Clone, Copy, Debug, Default)]
+struct AggregateDelta {
+    size_in_bytes: u64,
+    files: u32,
+    dirs: u32,
+}
+
+impl AggregateDelta {
+    fn add(&mut self, other: &AggregateDelta) {
+        self.size_in_bytes += other.size_in_bytes;
+        self.files += other.files;
+        self.dirs += other.dirs;
+    }
+}
+
+/// An arena holding the entire scanned file system tree.
+///
+/// Each node costs a few dozen bytes: names are interned, links to parents and siblings
+/// are indices into the arena, and only the metadata we display is retained. Paths are
+/// rebuilt on demand by walking parent links.
+#[derive(Default)]
+pub struct FsTree {
+    root_path: path::PathBuf,
+    nodes: Vec<FsNode>,
+    names: NameInterner,
+    changed: Vec<FsNodeId>,
+    /// Deltas that have not yet been propagated to the keyed node (and its ancestors)
+    pending_deltas: HashMap<FsNodeId, AggregateDelta>,
+    /// The places of removed nodes, which [`FsTree::insert_child`] reuses
+    free: BTreeSet<u32>,
+}
+
+impl FsTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts an entity produced by the walker. Its parent must already have been
+    /// inserted.
+    ///
+    /// The entity's size (and its contribution to file and directory counts) is not added
+    /// to its ancestors until the next call to [`FsTree::propagate_sizes`].
+    ///
+    /// # Panics
+    ///
+    /// If the entity isn't the next one in walk order, or its parent hasn't been inserted.
+    /// Propagating sizes relies on parents having lower IDs than their children.
+    pub fn insert(&mut self, fs_entity: &FsEntity) -> FsNodeId {
+        assert_eq!(
+            FsNodeId::from_index(self.nodes.len()),
+            fs_entity.id,
+            "entities must be inserted in walk order"
+        );
+        if let Some(parent_id) = fs_entity.parent_id {
+            assert!(
+                parent_id < fs_entity.id,
+                "{} must be inserted before its child {}",
+                parent_id,
+                fs_entity.id
+            );
+        }
+        self.insert_node(fs_entity.parent_id, fs_entity.depth, fs_entity, None)
+    }
+
+    /// Inserts an entity from a walk rooted at one of the tree's directories (a rescan)
+    /// as a child of `parent`. The IDs that walk assigned are ignored.
+    ///
+    /// The node takes the first free place after its parent's, if there is one, so that
+    /// rescanning and deleting don't grow the tree without bound.
+    pub fn insert_child(&mut self, parent: FsNodeId, fs_entity: &FsEntity) -> FsNodeId {
+        let depth = self.depth(parent) + 1;
+        let free_index = self.free.range(parent.index + 1..).next().copied();
+        self.insert_node(Some(parent), depth, fs_entity, free_index)
+    }
+
+    fn insert_node(
+        &mut self,
+        parent: Option<FsNodeId>,
+        depth: u16,
+        fs_entity: &FsEntity,
+        free_index: Option<u32>,
+    ) -> FsNodeId {
+        let id = match free_index {
+            Some(index) => {
+                self.free.remove(&index);
+                FsNodeId {
+                    index,
+                    generation: self.nodes[index as usize].generation.wrapping_add(1),
+                }
+            }
+            None => FsNodeId::from_index(self.nodes.len()),
+        };
+        let name = match parent {
+            Some(_) => self.names.intern(
+                fs_entity
+                    .path
+                    .file_name()
+                    .unwrap_or(fs_entity.path.as_os_str()),
+            ),
+            None => {
+                self.root_path = fs_entity.path.clone();
+                self.names.intern(OsStr::new(""))
+            }
+        };
+
+        let size_in_bytes = fs_entity.size_in_bytes();
+        let node = FsNode {
+            name,
+            parent: parent.map_or(NO_NODE, |p| p.index),
+            first_child: NO_NODE,
+            last_child: NO_NODE,
+            next_sibling: NO_NODE,
+            depth,
+            is_changed: false,
+            // Anything found below a removed directory is removed along with it
+            is_removed: matches!(parent, Some(parent) if self.is_removed(parent)),
+            size_in_bytes,
+            aggregate_size: size_in_bytes,
+            descendent_files: 0,
+            descendent_dirs: 0,
+            generation: id.generation,
+            metadata: FsNodeMetadata::from_metadata(&fs_entity.metadata),
+        };
+        let is_removed = node.is_removed;
+        match free_index {
+            Some(index) => self.nodes[index as usize] = node,
+            None => self.nodes.push(node),
+        }
+        // The removed parent's place may already have been reused, so the node is left
+        // out of its children, and its own place is free right away
+        if is_removed {
+            self.free.insert(id.index);
+            return id;
+        }
+
+        if let Some(parent) = parent {
+            let parent_node = &mut self.nodes[parent.index()];
+            match parent_node.last_child {
+                NO_NODE => {
+                    parent_node.first_child = id.index;
+                    parent_node.last_child = id.index;
+                }
+                last_child => {
+                    parent_node.last_child = id.index;
+                    self.nodes[last_child as usize].next_sibling = id.index;
+                }
+            }
+
+            let is_dir = self.nodes[id.index()].metadata.kind == FsNodeKind::Dir;
+            self.pending_deltas
+                .entry(parent)
+                .or_default()
+                .add(&AggregateDelta {
+                    size_in_bytes,
+                    files: !is_dir as u32,
+                    dirs: is_dir as u32,
+                });
+        }
+
+        self.mark_changed(id);
+        id
+    }
+
+    /// Adds the sizes of everything inserted since the last call to the aggregates of
+    /// their ancestors, and counts them among their ancestors' descendents.
+    ///
+    /// Deltas are merged on their way up the tree, so each ancestor is updated once per
+    /// call no matter how many of its descendents were inserted.
+    pub fn propagate_sizes(&mut self) {
+        // Children always have higher IDs than their parents, so visiting nodes from the
+        // highest ID down guarantees that a node has received all of its deltas before it
+        // passes them on.
+        let mut queue: BinaryHeap<FsNodeId> =
+            self.pending_deltas.keys().copied().collect();
+        while let Some(id) = queue.pop() {
+            let delta = self.pending_deltas.remove(&id).unwrap();
+            let node = &mut self.nodes[id.index()];
+            node.aggregate_size += delta.size_in_bytes;
+            node.descendent_files += delta.files;
+            node.descendent_dirs += delta.dirs;
+            self.mark_changed(id);
+
+            if let Some(parent) = self.parent(id) {
+                if !self.pending_deltas.contains_key(&parent) {
+                    queue.push(parent);
+                }
+                self.pending_deltas.entry(parent).or_default().add(&delta);
+            }
+        }
+    }
+
+    /// Removes everything below `id`, and takes its size out of the aggregates of `id` and
+    /// its ancestors. Returns the removed nodes, parents before their children.
+    pub fn remove_children(&mut self, id: FsNodeId) -> Vec<FsNodeId> {
+        self.propagate_sizes();
+
+        let mut removed: Vec<FsNodeId> = self.children(id).collect();
+        let mut next = 0;
+        while next < removed.len() {
+            let children: Vec<FsNodeId> = self.children(removed[next]).collect();
+            removed.extend(children);
+            next += 1;
+        }
+        for removed_id in removed.iter() {
+            self.nodes[removed_id.index()].is_removed = true;
+            self.free.insert(removed_id.index);
+        }
+
+        let node = &mut self.nodes[id.index()];
+        node.first_child = NO_NODE;
+        node.last_child = NO_NODE;
+        let delta = AggregateDelta {
+            size_in_bytes: node.aggregate_size - node.size_in_bytes,
+            files: node.descendent_files,
+            dirs: node.descendent_dirs,
+        };
+        self.subtract_from_aggregates(id, &delta);
+        removed
+    }
+
+    /// Removes `id` and everything below it, and takes its size out of the aggregates of
+    /// its ancestors. Returns the removed nodes, parents before their children.
+    pub fn remove(&mut self, id: FsNodeId) -> Vec<FsNodeId> {
+        let parent = self.parent(id).expect("the root can't be removed");
+        let mut removed = self.remove_children(id);
+        removed.insert(0, id);
+
+        let node = &mut self.nodes[id.index()];
+        node.is_removed = true;
+        self.free.insert(id.index);
+        let is_dir = node.metadata.kind == FsNodeKind::Dir;
+        let delta = AggregateDelta {
+            size_in_bytes: node.size_in_bytes,
+            files: !is_dir as u32,
+            dirs: is_dir as u32,
+        };
+        self.unlink(parent, id);
+        self.subtract_from_aggregates(parent, &delta);
+        removed
+    }
+
+    /// Takes `id` out of `parent`'s list of children
+    fn unlink(&mut self, parent: FsNodeId, id: FsNodeId) {
+        let previous = self
+            .children(parent)
+            .take_while(|child| *child != id)
+            .last();
+        let next_sibling = self.nodes[id.index()].next_sibling;
+        match previous {
+            Some(previous) => self.nodes[previous.index()].next_sibling = next_sibling,
+            None => self.nodes[parent.index()].first_child = next_sibling,
+        }
+        let parent_node = &mut self.nodes[parent.index()];
+        if parent_node.last_child == id.index {
+            parent_node.last_child = previous.map_or(NO_NODE, |previous| previous.index);
+        }
+        self.nodes[id.index()].next_sibling = NO_NODE;
+    }
+
+    /// Takes `delta` out of the aggregates of `id` and all of its ancestors
+    fn subtract_from_aggregates(&mut self, id: FsNodeId, delta: &AggregateDelta) {
+        let ids: Vec<FsNodeId> = std::iter::once(id).chain(self.ancestors(id)).collect();
+        for id in ids {
+            let node = &mut self.nodes[id.index()];
+            node.aggregate_size -= delta.size_in_bytes;
+            node.descendent_files -= delta.files;
+            node.descendent_dirs -= delta.dirs;
+            self.mark_changed(id);
+        }
+    }
+
+    fn mark_changed(&mut self, id: FsNodeId) {
+        let node = &mut self.nodes[id.index()];
+        if !node.is_changed {
+            node.is_changed = true;
+            self.changed.push(id);
+        }
+    }
+
+    /// Returns the nodes that were added or whose aggregate size changed since the last
+    /// call, in no particular order. Nodes whose places have since been reused are left
+    /// out.
+    pub fn drain_changed(&mut self) -> Vec<FsNodeId> {
+        for id in self.changed.iter() {
+            self.nodes[id.index()].is_changed = false;
+        }
+        let mut changed = std::mem::take(&mut self.changed);
+        changed.retain(|id| self.nodes[id.index()].generation == id.generation);
+        changed
+    }
+
+    /// The number of places in the arena, including those of removed nodes that haven't
+    /// been reused yet
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn root(&self) -> Option<FsNodeId> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(FsNodeId::ROOT)
+        }
+    }
+
+    pub fn root_path(&self) -> &path::Path {
+        &self.root_path
+    }
+
+    pub fn contains(&self, id: FsNodeId) -> bool {
+        id.index() < self.nodes.len() && !self.is_removed(id)
+    }
+
+    pub fn is_removed(&self, id: FsNodeId) -> bool {
+        let node = &self.nodes[id.index()];
+        node.is_removed || node.generation != id.generation
+    }
+
+    /// Iterates over every node in the tree, in the order of their places. That's walk
+    /// order, apart from nodes inserted by rescans.
+    pub fn iter(&self) -> impl Iterator<Item = FsNodeId> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.is_removed)
+            .map(|(index, node)| FsNodeId {
+                index: index as u32,
+                generation: node.generation,
+            })
+    }
+
+    pub fn name(&self, id: FsNodeId) -> &OsStr {
+        self.names.resolve(self.nodes[id.index()].name)
+    }
+
+    pub fn parent(&self, id: FsNodeId) -> Option<FsNodeId> {
+        self.node_id(self.nodes[id.index()].parent)
+    }
+
+    pub fn children(&self, id: FsNodeId) -> FsChildren<'_> {
+        FsChildren {
+            tree: self,
+            next: self.nodes[id.index()].first_child,
+        }
+    }
+
+    /// Iterates over `id`'s ancestors, nearest first, not including `id` itself
+    pub fn ancestors(&self, id: FsNodeId) -> impl Iterator<Item = FsNodeId> + '_ {
+        std::iter::successors(self.parent(id), move |a| self.parent(*a))
+    }
+
+    pub fn depth(&self, id: FsNodeId) -> u16 {
+        self.nodes[id.index()].depth
+    }
+
+    /// The node's own apparent size. Always 0 for anything but a file.
+    pub fn size_in_bytes(&self, id: FsNodeId) -> u64 {
+        self.nodes[id.index()].size_in_bytes
+    }
+
+    /// The node's size plus the sizes of all of its descendents
+    pub fn aggregate_size(&self, id: FsNodeId) -> u64 {
+        self.nodes[id.index()].aggregate_size
+    }
+
+    /// The number of non-directories below the node, at any depth
+    pub fn descendent_file_count(&self, id: FsNodeId) -> u32 {
+        self.nodes[id.index()].descendent_files
+    }
+
+    /// The number of directories below the node, at any depth
+    pub fn descendent_dir_count(&self, id: FsNodeId) -> u32 {
+        self.nodes[id.index()].descendent_dirs
+    }
+
+    pub fn metadata(&self, id: FsNodeId) -> &FsNodeMetadata {
+        &self.nodes[id.index()].metadata
+    }
+
+    pub fn kind(&self, id: FsNodeId) -> FsNodeKind {
+        self.nodes[id.index()].metadata.kind
+    }
+
+    /// The node's path, relative to the root of the walk. The root's relative path is
+    /// empty.
+    pub fn relative_path(&self, id: FsNodeId) -> path::PathBuf {
+        let mut components: Vec<&OsStr> = std::iter::once(id)
+            .chain(self.ancestors(id))
+            .filter(|a| *a != FsNodeId::ROOT)
+            .map(|a| self.name(a))
+            .collect();
+        components.reverse();
+        components.iter().collect()
+    }
+
+    /// The node's absolute path
+    pub fn path(&self, id: FsNodeId) -> path::PathBuf {
+        self.root_path.join(self.relative_path(id))
+    }
+
+    /// The ID of the node a link points to
+    fn node_id(&self, link: u32) -> Option<FsNodeId> {
+        match link {
+            NO_NODE => None,
+            index => Some(FsNodeId {
+                index,
+                generation: self.nodes[index as usize].generation,
+            }),
+        }
+    }
+}
+
+pub struct FsChildren<'a> {
+    tree: &'a FsTree,
+    next: u32,
+}
+
+impl<'a> Iterator for FsChildren<'a> {
+    type Item = FsNodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.tree.node_id(self.next)?;
+        self.next = self.tree.nodes[current.index()].next_sibling;
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn dir(path: &str, id: usize, parent_id: Option<usize>) -> FsEntity {
+        FsEntity {
+            path: PathBuf::from(path),
+            metadata: std::fs::metadata("/").unwrap(),
+            id: FsNodeId::from_index(id),
+            parent_id: parent_id.map(FsNodeId::from_index),
+            depth: parent_id.map_or(0, |_| 1),
+        }
+    }
+
+    #[test]
+    fn rescanned_nodes_reuse_the_places_of_removed_ones() {
+        let mut fs_tree = FsTree::new();
+        let root = fs_tree.insert(&dir("/scan", 0, None));
+        let a = fs_tree.insert(&dir("/scan/a", 1, Some(0)));
+        let b = fs_tree.insert(&dir("/scan/b", 2, Some(0)));
+        fs_tree.propagate_sizes();
+
+        fs_tree.remove(a);
+        let c = fs_tree.insert_child(root, &dir("/scan/c", 1, Some(0)));
+        assert_eq!(fs_tree.len(), 3);
+        assert_eq!(c.index(), a.index());
+        assert!(!fs_tree.contains(a));
+        assert!(fs_tree.contains(c));
+        assert_eq!(fs_tree.children(root).collect::<Vec<_>>(), vec![b, c]);
+        assert_eq!(fs_tree.iter().collect::<Vec<_>>(), vec![root, c, b]);
+
+        // Places are only reused after the parent's, so parents keep the lower IDs
+        let d = fs_tree.insert_child(b, &dir("/scan/b/d", 1, Some(0)));
+        assert!(d > b);
+        assert_eq!(fs_tree.len(), 4);
+    }
+
+    #[test]
+    fn nodes_inserted_below_removed_nodes_are_removed() {
+        let mut fs_tree = FsTree::new();
+        let root = fs_tree.insert(&dir("/scan", 0, None));
+        let a = fs_tree.insert(&dir("/scan/a", 1, Some(0)));
+        fs_tree.remove(a);
+
+        let b = fs_tree.insert_child(a, &dir("/scan/a/b", 1, Some(0)));
+        assert!(!fs_tree.contains(b));
+        let c = fs_tree.insert_child(root, &dir("/scan/c", 1, Some(0)));
+        assert_eq!(fs_tree.children(root).collect::<Vec<_>>(), vec![c]);
+        assert!(fs_tree.children(c).next().is_none());
+    }
+}
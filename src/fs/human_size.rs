@@ -0,0 +1,42 @@
+const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Formats a byte count using binary units, like `ls -h` and `du -h` do (`12.3 MiB`)
+pub fn human_readable_size(size_in_bytes: u64) -> String {
+    if size_in_bytes < 1024 {
+        return format!("{} {}", size_in_bytes, UNITS[0]);
+    }
+
+    let (size, unit) = scale_to_unit(size_in_bytes);
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Formats a byte count the way `du -h` prints it, with one-letter units and no space
+/// (`12.3M`), which `sort -h` can order
+pub fn compact_human_readable_size(size_in_bytes: u64) -> String {
+    if size_in_bytes < 1024 {
+        return size_in_bytes.to_string();
+    }
+
+    let (size, unit) = scale_to_unit(size_in_bytes);
+    format!("{:.1}{}", size, &UNITS[unit][..1])
+}
+
+/// The size in the largest unit that keeps it at 1 or more, and the index of that unit
+fn scale_to_unit(size_in_bytes: u64) -> (f64, usize) {
+    let mut size = size_in_bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    (size, unit)
+}
+
+/// Formats `part` as a percentage of `whole`
+pub fn percentage(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        "-".into()
+    } else {
+        format!("{:.1}%", part as f64 / whole as f64 * 100.0)
+    }
+}
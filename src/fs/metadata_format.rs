@@ -0,0 +1,110 @@
+use super::{FsNodeKind, FsNodeMetadata};
+use std::{collections::HashMap, fs, sync::OnceLock};
+
+/// Formats the node's type and permission bits the way `ls -l` does (`drwxr-xr-x`)
+pub fn format_permissions(metadata: &FsNodeMetadata) -> String {
+    let type_char = match metadata.kind {
+        FsNodeKind::Dir => 'd',
+        FsNodeKind::Symlink => 'l',
+        FsNodeKind::File => '-',
+        FsNodeKind::Other => '?',
+    };
+
+    let mut formatted = String::with_capacity(10);
+    formatted.push(type_char);
+    for (shift, special_bit, special_char) in
+        [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')]
+    {
+        let bits = (metadata.mode >> shift) & 0o7;
+        let is_special = metadata.mode & special_bit != 0;
+        formatted.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        formatted.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        formatted.push(match (bits & 0o1 != 0, is_special) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    formatted
+}
+
+/// Formats the modification time as `YYYY-MM-DD HH:MM:SS UTC`
+pub fn format_mtime(metadata: &FsNodeMetadata) -> String {
+    let secs = metadata.mtime_secs;
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DDThh:mm:ss`, in UTC unless the
+/// seconds are offset to another time zone
+pub fn format_timestamp(secs: i64) -> String {
+    let secs_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Converts days since the Unix epoch to a proleptic Gregorian date. This is Howard
+/// Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Looks up the name of the user with `uid` in `/etc/passwd`, falling back to the number
+/// itself for users that aren't listed there (LDAP users, for instance). The file is read
+/// once, the first time a name is looked up.
+pub fn owner_name(uid: u32) -> String {
+    static OWNER_NAMES: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    OWNER_NAMES
+        .get_or_init(read_owner_names)
+        .get(&uid)
+        .cloned()
+        .unwrap_or_else(|| uid.to_string())
+}
+
+fn read_owner_names() -> HashMap<u32, String> {
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    let mut owner_names = HashMap::new();
+    for line in passwd.lines() {
+        let mut fields = line.split(':');
+        let (name, uid) = match (fields.next(), fields.nth(1)) {
+            (Some(name), Some(uid)) => (name, uid),
+            _ => continue,
+        };
+        // The first entry for a uid wins, as it does for `getpwuid`
+        if let Ok(uid) = uid.parse() {
+            owner_names.entry(uid).or_insert_with(|| name.into());
+        }
+    }
+    owner_names
+}
@@ -1,5 +1,15 @@
 pub mod du_plugin;
+pub mod fs_tree;
+pub mod human_size;
+pub mod metadata_format;
+pub mod trash;
 pub mod walk_dir_level_order;
+pub mod walk_stream;
 
 pub use du_plugin::*;
+pub use fs_tree::*;
+pub use human_size::*;
+pub use metadata_format::*;
+pub use trash::*;
 pub use walk_dir_level_order::*;
+pub use walk_stream::*;
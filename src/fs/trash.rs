@@ -0,0 +1,348 @@
+use super::format_timestamp;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{DirBuilderExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// How many names are tried in the trash before giving up on finding a free one
+const MAX_TRASH_NAME_ATTEMPTS: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Moved into the user's trash, from where it can be restored
+    Trash,
+    /// Removed from disk for good
+    Permanent,
+}
+
+/// Deletes the file or directory at `path`. Symlinks are deleted, not what they point to.
+pub fn delete_from_disk(path: &Path, mode: DeleteMode) -> io::Result<()> {
+    match mode {
+        DeleteMode::Trash => move_to_trash(path).map(|_| ()),
+        DeleteMode::Permanent => delete_permanently(path),
+    }
+}
+
+/// `$XDG_DATA_HOME/Trash`, defaulting to `~/.local/share/Trash`
+pub fn trash_dir() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share"))
+        })?;
+    Some(data_dir.join("Trash"))
+}
+
+/// Moves `path` into the trash, as laid out by the freedesktop.org trash spec, and returns
+/// where it ended up. That's the home trash, unless `path` is on another file system,
+/// which has a trash of its own at its top directory.
+pub fn move_to_trash(path: &Path) -> io::Result<PathBuf> {
+    let home_trash_dir = trash_dir().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "no home directory for the trash")
+    })?;
+    let path = absolute(path)?;
+    match move_into_trash_dir(&home_trash_dir, &path, &path) {
+        // Trashing is a rename, which can't cross file systems
+        Err(err) if err.raw_os_error() == Some(libc::EXDEV) => {
+            let topdir = top_directory(&path)?;
+            let relative_path = path.strip_prefix(&topdir).unwrap_or(&path);
+            move_into_trash_dir(&topdir_trash_dir(&topdir), &path, relative_path)
+        }
+        result => result,
+    }
+}
+
+/// Moves `path` into `trash_dir`, recording `info_path` as where it came from. The
+/// `.trashinfo` file is written first, so that a trashed item is never left without one.
+fn move_into_trash_dir(
+    trash_dir: &Path,
+    path: &Path,
+    info_path: &Path,
+) -> io::Result<PathBuf> {
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    // Other users mustn't see what's in the trash
+    let mut dir_builder = fs::DirBuilder::new();
+    dir_builder.recursive(true).mode(0o700);
+    dir_builder.create(&files_dir)?;
+    dir_builder.create(&info_dir)?;
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "nothing to trash"))?;
+    let info = trash_info(info_path, SystemTime::now());
+
+    for attempt in 1..=MAX_TRASH_NAME_ATTEMPTS {
+        let mut trashed_name = name.to_owned();
+        if attempt > 1 {
+            trashed_name.push(format!(".{attempt}"));
+        }
+        let trashed_path = files_dir.join(&trashed_name);
+        let info_path = info_dir.join(info_file_name(trashed_name));
+
+        // Creating the info file claims the name
+        let mut info_file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+        // A stray file without an info file still takes up the name
+        if fs::symlink_metadata(&trashed_path).is_ok() {
+            drop(info_file);
+            fs::remove_file(&info_path)?;
+            continue;
+        }
+
+        let result = info_file
+            .write_all(info.as_bytes())
+            .and_then(|()| fs::rename(path, &trashed_path));
+        return match result {
+            Ok(()) => Ok(trashed_path),
+            Err(err) => {
+                let _ = fs::remove_file(&info_path);
+                Err(err)
+            }
+        };
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no free name left in the trash",
+    ))
+}
+
+/// The top directory of the file system that `path` is on, where it's mounted
+fn top_directory(path: &Path) -> io::Result<PathBuf> {
+    let device = fs::symlink_metadata(path)?.dev();
+    let mut topdir = path;
+    for ancestor in path.ancestors().skip(1) {
+        if fs::metadata(ancestor)?.dev() != device {
+            break;
+        }
+        topdir = ancestor;
+    }
+    Ok(topdir.to_owned())
+}
+
+/// `$topdir/.Trash/$uid` when an administrator has set up `$topdir/.Trash` for everyone
+/// (as a sticky directory, and not a symlink), and otherwise `$topdir/.Trash-$uid`
+fn topdir_trash_dir(topdir: &Path) -> PathBuf {
+    // SAFETY: getuid can't fail, and has no preconditions
+    let uid = unsafe { libc::getuid() };
+    let shared_trash_dir = topdir.join(".Trash");
+    match fs::symlink_metadata(&shared_trash_dir) {
+        Ok(metadata) if metadata.is_dir() && metadata.mode() & libc::S_ISVTX != 0 => {
+            shared_trash_dir.join(uid.to_string())
+        }
+        _ => topdir.join(format!(".Trash-{uid}")),
+    }
+}
+
+pub fn delete_permanently(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_owned());
+    }
+    // Collecting the components drops any `.`s along the way
+    Ok(env::current_dir()?.join(path).components().collect())
+}
+
+fn info_file_name(mut trashed_name: OsString) -> OsString {
+    trashed_name.push(".trashinfo");
+    trashed_name
+}
+
+/// The contents of the `.trashinfo` file of an item that was at `path`
+fn trash_info(path: &Path, deleted_at: SystemTime) -> String {
+    format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(path.as_os_str().as_bytes()),
+        deletion_date(deleted_at)
+    )
+}
+
+/// Escapes everything but unreserved characters and slashes, as URLs do
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for byte in bytes {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~'
+            | b'/' => encoded.push(*byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// The spec asks for local time, with no time zone
+fn deletion_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs() as i64);
+    format_timestamp(secs + utc_offset_secs(secs))
+}
+
+/// How far local time is ahead of UTC at `secs` since the Unix epoch, going by the C
+/// library's time zone
+fn utc_offset_secs(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: `tm` is plain data, and localtime_r only writes to it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    if result.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{os::unix::fs::PermissionsExt, time::Duration};
+
+    /// A directory of its own for each test, removed when the test is done
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir()
+                .join(format!("visual_du-trash-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn paths_are_percent_encoded_byte_for_byte() {
+        assert_eq!(
+            percent_encode(b"/tmp/a b%#?\xff\xc3\xa9/~_.-"),
+            "/tmp/a%20b%25%23%3F%FF%C3%A9/~_.-"
+        );
+    }
+
+    #[test]
+    fn trash_info_has_the_path_and_a_local_deletion_date() {
+        let deleted_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let info = trash_info(Path::new("/home/me/a file"), deleted_at);
+
+        let date = deletion_date(deleted_at);
+        assert_eq!(
+            info,
+            format!("[Trash Info]\nPath=/home/me/a%20file\nDeletionDate={date}\n")
+        );
+        // YYYY-MM-DDThh:mm:ss, with no time zone
+        assert_eq!(date.len(), 19, "{date}");
+        assert_eq!(&date[10..11], "T");
+        assert!(!date.ends_with('Z'));
+        assert_eq!(
+            date,
+            format_timestamp(1_700_000_000 + utc_offset_secs(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn trashed_names_are_suffixed_until_theyre_free() {
+        let temp_dir = TempDir::new("names");
+        let trash_dir = temp_dir.0.join("Trash");
+        let mut trashed_paths = vec![];
+        for parent in ["a", "b", "c"] {
+            let path = temp_dir.0.join(parent).join("notes");
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, parent).unwrap();
+            // The second name is taken by a stray file, which has no info file
+            if parent == "b" {
+                fs::write(trash_dir.join("files").join("notes.2"), "stray").unwrap();
+            }
+            trashed_paths.push(move_into_trash_dir(&trash_dir, &path, &path).unwrap());
+        }
+
+        let files_dir = trash_dir.join("files");
+        assert_eq!(
+            trashed_paths,
+            [
+                files_dir.join("notes"),
+                files_dir.join("notes.3"),
+                files_dir.join("notes.4")
+            ]
+        );
+        assert_eq!(fs::read_to_string(&trashed_paths[1]).unwrap(), "b");
+        let info = fs::read_to_string(trash_dir.join("info/notes.3.trashinfo")).unwrap();
+        assert!(info.contains(&format!(
+            "\nPath={}\n",
+            percent_encode(temp_dir.0.join("b/notes").as_os_str().as_bytes())
+        )));
+        assert!(!trash_dir.join("info/notes.2.trashinfo").exists());
+    }
+
+    #[test]
+    fn topdir_trash_is_shared_only_when_its_sticky_and_not_a_symlink() {
+        let temp_dir = TempDir::new("topdir");
+        let topdir = temp_dir.0.as_path();
+        // SAFETY: getuid can't fail, and has no preconditions
+        let uid = unsafe { libc::getuid() };
+        let own_trash_dir = topdir.join(format!(".Trash-{uid}"));
+        let shared_trash_dir = topdir.join(".Trash");
+
+        assert_eq!(topdir_trash_dir(topdir), own_trash_dir);
+
+        fs::create_dir(&shared_trash_dir).unwrap();
+        assert_eq!(topdir_trash_dir(topdir), own_trash_dir);
+
+        fs::set_permissions(&shared_trash_dir, fs::Permissions::from_mode(0o1777))
+            .unwrap();
+        assert_eq!(
+            topdir_trash_dir(topdir),
+            shared_trash_dir.join(uid.to_string())
+        );
+
+        fs::rename(&shared_trash_dir, topdir.join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(topdir.join("elsewhere"), &shared_trash_dir).unwrap();
+        assert_eq!(topdir_trash_dir(topdir), own_trash_dir);
+    }
+
+    #[test]
+    fn top_directory_is_the_highest_ancestor_on_the_same_file_system() {
+        let temp_dir = TempDir::new("top");
+        let topdir = top_directory(&temp_dir.0).unwrap();
+        let device = |path: &Path| fs::metadata(path).unwrap().dev();
+
+        assert!(temp_dir.0.starts_with(&topdir));
+        assert_eq!(device(&topdir), device(&temp_dir.0));
+        if let Some(parent) = topdir.parent() {
+            assert_ne!(device(parent), device(&topdir));
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::FsNodeId;
 use std::{cmp::Ordering, collections::VecDeque, fs, path, result::Result};
 use tracing::{error, info, trace};
 use valuable::Valuable;
@@ -5,6 +6,8 @@ use valuable::Valuable;
 pub struct LevelOrderDirTraversal {
     pub root_path: path::PathBuf,
     queue: VecDeque<Result<FsEntity, Error>>,
+    /// The ID that will be assigned to the next entity yielded
+    next_id: usize,
 }
 
 impl LevelOrderDirTraversal {
@@ -19,7 +22,7 @@ impl LevelOrderDirTraversal {
         match fs::read_dir(&path) {
             Ok(rd) => {
                 let mut child_entities: Vec<_> = rd
-                    .map(|e| dir_entry_to_fs_entity(e, parent.depth + 1))
+                    .map(|e| dir_entry_to_fs_entity(e, parent.id, parent.depth + 1))
                     .collect();
                 child_entities.sort_by(|a, b| match (a, b) {
                     (Ok(a), Ok(b)) => a.path.to_str().cmp(&b.path.to_str()),
@@ -38,6 +41,7 @@ impl LevelOrderDirTraversal {
 
 fn dir_entry_to_fs_entity(
     dir_entry_res: Result<fs::DirEntry, std::io::Error>,
+    parent_id: FsNodeId,
     depth: u16,
 ) -> Result<FsEntity, Error> {
     let (maybe_dir_entry, metadata_res) = match dir_entry_res {
@@ -50,6 +54,9 @@ fn dir_entry_to_fs_entity(
         .map(|md| FsEntity {
             path: maybe_dir_entry.unwrap().path(),
             metadata: md,
+            // Assigned when the entity is yielded
+            id: FsNodeId::default(),
+            parent_id: Some(parent_id),
             depth,
         })
         .map_err(|_e| Error {})
@@ -59,11 +66,13 @@ impl Iterator for LevelOrderDirTraversal {
     type Item = Result<FsEntity, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let maybe_current_res = self.queue.pop_front();
-        if let Some(ref current_res) = maybe_current_res {
-            if let Ok(ref current) = current_res {
-                self.enque_children_of(current);
-            }
+        let mut maybe_current_res = self.queue.pop_front();
+        if let Some(Ok(ref mut current)) = maybe_current_res {
+            // IDs are handed out in the order entities are yielded, so a parent's ID is
+            // always lower than those of its children
+            current.id = FsNodeId::from_index(self.next_id);
+            self.next_id += 1;
+            self.enque_children_of(current);
         }
         maybe_current_res
     }
@@ -79,6 +88,8 @@ pub fn walk_dir<P: AsRef<path::Path>>(
         .map(|md| FsEntity {
             path: root_path.clone(),
             metadata: md,
+            id: FsNodeId::ROOT,
+            parent_id: None,
             depth: 0,
         })
         .map_err(|_| Error {});
@@ -86,6 +97,7 @@ pub fn walk_dir<P: AsRef<path::Path>>(
     Ok(LevelOrderDirTraversal {
         root_path,
         queue: VecDeque::from([root]),
+        next_id: 0,
     })
 }
 
@@ -93,6 +105,9 @@ pub fn walk_dir<P: AsRef<path::Path>>(
 pub struct FsEntity {
     pub path: path::PathBuf,
     pub metadata: fs::Metadata,
+    /// The ID of this entity's node in an [`super::FsTree`] built from this walk
+    pub id: FsNodeId,
+    pub parent_id: Option<FsNodeId>,
     pub depth: u16,
 }
 
@@ -0,0 +1,61 @@
+use super::{walk_dir, FsEntity};
+use crossbeam_channel::{bounded, Receiver};
+use std::{
+    path, thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error};
+
+/// The number of entities the walker collects before sending them on
+const WALK_BATCH_SIZE: usize = 512;
+/// The number of batches that can be in flight before the walker blocks
+const WALK_CHANNEL_CAPACITY: usize = 64;
+/// A partial batch is sent if it has been held this long, so that slow walks (network
+/// mounts, cold caches) still stream in
+const WALK_BATCH_MAX_LATENCY: Duration = Duration::from_millis(50);
+
+/// Walks `root_path` on a background thread, streaming the entities it finds back in
+/// batches. The channel disconnects once the walk is complete, and the walk stops early if
+/// the receiver is dropped.
+pub fn spawn_batched_walk(root_path: path::PathBuf) -> Receiver<Vec<FsEntity>> {
+    let (send_channel, receive_channel) = bounded::<Vec<FsEntity>>(WALK_CHANNEL_CAPACITY);
+    thread::spawn(move || {
+        let send_batch = |batch: Vec<FsEntity>| match send_channel.send(batch) {
+            Ok(_) => true,
+            Err(_) => {
+                debug!("walk abandoned by its receiver");
+                false
+            }
+        };
+
+        let mut batch = Vec::with_capacity(WALK_BATCH_SIZE);
+        let mut batch_started_at = Instant::now();
+        for entity_res in walk_dir(root_path).unwrap() {
+            if batch.is_empty() {
+                batch_started_at = Instant::now();
+            }
+            match entity_res {
+                Ok(entity) => batch.push(entity),
+                Err(e) => error!(error = %e, "Skipping unreadable entity"),
+            }
+
+            if batch.len() >= WALK_BATCH_SIZE
+                || batch_started_at.elapsed() >= WALK_BATCH_MAX_LATENCY
+            {
+                let is_sent = send_batch(std::mem::replace(
+                    &mut batch,
+                    Vec::with_capacity(WALK_BATCH_SIZE),
+                ));
+                if !is_sent {
+                    return;
+                }
+            }
+        }
+
+        if !batch.is_empty() {
+            send_batch(batch);
+        }
+    });
+
+    receive_channel
+}
@@ -1,5 +1,11 @@
 pub mod app_scaffold;
+pub mod cli;
 pub mod debug;
+pub mod export;
 pub mod fs;
 pub mod render;
+pub mod report;
+pub mod shell;
+pub mod theme;
+pub mod tui;
 pub mod ui;
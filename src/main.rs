@@ -1,24 +1,88 @@
 use bevy::prelude::*;
-use std::env;
+use std::{fs, io, process};
 use visual_du::{
     app_scaffold::AppScaffoldPlugin,
+    cli::{CliArgs, CliError, CliMode},
     fs::{DiskUsagePlugin, DiskUsageWalkConfig},
-    ui::{DiskUsageTreeViewPlugin, InputCamera, MouseInteractionsPlugin},
+    report::run_report,
+    tui::run_tui,
+    ui::{
+        BlockLabelsPlugin, ContextMenuPlugin, DeleteDialogPlugin, DetailsPanelPlugin,
+        DiskUsageTreeViewPlugin, InputCamera, MouseInteractionsPlugin, PanelPlugin,
+        TreeViewAnimationPlugin, TreeViewFocusPlugin, TreeViewSelectionPlugin,
+        TreeViewTablePlugin, TreeViewViewportPlugin, TreeViewZoomPlugin,
+    },
 };
 
 fn main() {
-    let mut args = env::args().skip(1);
-    let root_path: String = args.next().unwrap_or("./example".into());
+    let args = match CliArgs::from_env() {
+        Ok(args) => args,
+        Err(CliError::HelpRequested) => {
+            println!("{}", CliError::HelpRequested);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{}", visual_du::cli::USAGE);
+            process::exit(2);
+        }
+    };
 
+    let result = match args.mode {
+        CliMode::Gui => {
+            run_gui(args);
+            Ok(())
+        }
+        CliMode::Report => fs::canonicalize(&args.root_path)
+            .and_then(|root_path| run_report(&root_path, &args.report)),
+        CliMode::Tui => match args.gui.theme.load() {
+            Ok(theme) => fs::canonicalize(&args.root_path)
+                .and_then(|root_path| run_tui(root_path, args.gui.colors, theme)),
+            Err(e) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        },
+    };
+
+    match result {
+        // Being piped into `head` isn't an error
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        _ => {}
+    }
+}
+
+fn run_gui(args: CliArgs) {
     let mut app = App::new();
     app.add_plugin(AppScaffoldPlugin {
         title: "Visual Disk Usage",
         bin_module_path: module_path!(),
+        theme: args.gui.theme,
+    })
+    .insert_resource(DiskUsageWalkConfig {
+        ingest_budget: args.gui.ingest_budget,
+        ..DiskUsageWalkConfig::new(args.root_path)
     })
-    .insert_resource(DiskUsageWalkConfig::new(root_path))
+    .insert_resource(args.gui.tree)
+    .insert_resource(args.gui.animation)
+    .insert_resource(args.gui.colors)
+    .insert_resource(args.gui.export)
     .add_plugin(MouseInteractionsPlugin)
+    .add_plugin(PanelPlugin)
     .add_plugin(DiskUsagePlugin)
     .add_plugin(DiskUsageTreeViewPlugin)
+    .add_plugin(TreeViewZoomPlugin)
+    .add_plugin(TreeViewViewportPlugin)
+    .add_plugin(TreeViewFocusPlugin)
+    .add_plugin(TreeViewSelectionPlugin)
+    .add_plugin(DetailsPanelPlugin)
+    .add_plugin(TreeViewTablePlugin)
+    .add_plugin(ContextMenuPlugin)
+    .add_plugin(DeleteDialogPlugin)
+    .add_plugin(BlockLabelsPlugin)
+    .add_plugin(TreeViewAnimationPlugin)
     .add_startup_system(setup_cameras)
     .run();
 }
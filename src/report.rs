@@ -0,0 +1,165 @@
+use crate::{
+    cli::ReportOptions,
+    fs::{
+        compact_human_readable_size, percentage, walk_dir, FsNodeId, FsNodeKind, FsTree,
+    },
+};
+use std::{
+    io::{self, BufWriter, Write},
+    path,
+};
+
+/// Walks `root_path` and prints a du-style report to stdout, without starting Bevy.
+///
+/// Every section starts with a `#` header line, and each data line is tab separated, so
+/// the output can be picked apart with the usual tools (`grep -v '^#' | sort -h`). Sizes
+/// are written like `du -h` writes them for that.
+pub fn run_report(root_path: &path::Path, options: &ReportOptions) -> io::Result<()> {
+    let mut fs_tree = FsTree::new();
+    let mut error_count = 0;
+    for entity_res in walk_dir(root_path)? {
+        match entity_res {
+            Ok(fs_entity) => {
+                fs_tree.insert(&fs_entity);
+            }
+            Err(_) => error_count += 1,
+        }
+    }
+    fs_tree.propagate_sizes();
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write_report(&mut out, &fs_tree, error_count, options)?;
+    out.flush()
+}
+
+pub fn write_report<W: Write>(
+    out: &mut W,
+    fs_tree: &FsTree,
+    error_count: usize,
+    options: &ReportOptions,
+) -> io::Result<()> {
+    let root = match fs_tree.root() {
+        Some(root) => root,
+        None => return Ok(()),
+    };
+    let total_size = fs_tree.aggregate_size(root);
+    let format_size = |size_in_bytes: u64| {
+        if options.raw_bytes {
+            size_in_bytes.to_string()
+        } else {
+            compact_human_readable_size(size_in_bytes)
+        }
+    };
+    let write_line = |out: &mut W, node_id: FsNodeId| {
+        let size_in_bytes = fs_tree.aggregate_size(node_id);
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            format_size(size_in_bytes),
+            percentage(size_in_bytes, total_size),
+            display_path(fs_tree, node_id).display(),
+        )
+    };
+
+    // Largest directories, anywhere in the tree
+    writeln!(out, "# top {} directories", options.top_n)?;
+    let mut dirs: Vec<FsNodeId> = fs_tree
+        .iter()
+        .filter(|id| *id != root && fs_tree.kind(*id) == FsNodeKind::Dir)
+        .collect();
+    dirs.sort_unstable_by_key(|id| std::cmp::Reverse(fs_tree.aggregate_size(*id)));
+    for dir in dirs.iter().take(options.top_n) {
+        write_line(out, *dir)?;
+    }
+
+    // The tree, largest children first
+    writeln!(out)?;
+    writeln!(out, "# tree, to a depth of {}", options.max_depth)?;
+    let mut stack = vec![root];
+    while let Some(node_id) = stack.pop() {
+        write_line(out, node_id)?;
+        if fs_tree.depth(node_id) >= options.max_depth {
+            continue;
+        }
+
+        let mut children: Vec<FsNodeId> = fs_tree
+            .children(node_id)
+            .filter(|id| options.include_files || fs_tree.kind(*id) == FsNodeKind::Dir)
+            .collect();
+        // Smallest first, so that the largest is popped first
+        children.sort_by_key(|id| fs_tree.aggregate_size(*id));
+        stack.extend(children);
+    }
+
+    writeln!(out)?;
+    writeln!(out, "# totals")?;
+    writeln!(out, "size\t{}", format_size(total_size))?;
+    // Counted like the details panel counts them, below the root
+    writeln!(out, "files\t{}", fs_tree.descendent_file_count(root))?;
+    writeln!(out, "directories\t{}", fs_tree.descendent_dir_count(root))?;
+    writeln!(out, "unreadable\t{}", error_count)?;
+
+    Ok(())
+}
+
+/// The node's path relative to the root, in the `./a/b` form that du uses
+fn display_path(fs_tree: &FsTree, node_id: FsNodeId) -> path::PathBuf {
+    let rel_path = fs_tree.relative_path(node_id);
+    if rel_path.as_os_str().is_empty() {
+        ".".into()
+    } else {
+        path::Path::new(".").join(rel_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn report_lists_the_largest_directories_the_tree_and_totals() {
+        let root_path =
+            env::temp_dir().join(format!("visual_du-report-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root_path);
+        fs::create_dir_all(root_path.join("big/nested")).unwrap();
+        fs::create_dir_all(root_path.join("small")).unwrap();
+        fs::write(root_path.join("big/nested/a"), vec![0; 3000]).unwrap();
+        fs::write(root_path.join("big/c"), vec![0; 100]).unwrap();
+        fs::write(root_path.join("small/b"), vec![0; 500]).unwrap();
+        std::os::unix::fs::symlink("big", root_path.join("link")).unwrap();
+
+        let mut fs_tree = FsTree::new();
+        for fs_entity in walk_dir(&root_path).unwrap() {
+            fs_tree.insert(&fs_entity.unwrap());
+        }
+        fs_tree.propagate_sizes();
+        fs::remove_dir_all(&root_path).unwrap();
+
+        let options = ReportOptions {
+            top_n: 2,
+            max_depth: 1,
+            ..ReportOptions::default()
+        };
+        let mut report = vec![];
+        write_report(&mut report, &fs_tree, 0, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "# top 2 directories\n\
+             3.0K\t86.1%\t./big\n\
+             2.9K\t83.3%\t./big/nested\n\
+             \n\
+             # tree, to a depth of 1\n\
+             3.5K\t100.0%\t.\n\
+             3.0K\t86.1%\t./big\n\
+             500\t13.9%\t./small\n\
+             \n\
+             # totals\n\
+             size\t3.5K\n\
+             files\t4\n\
+             directories\t3\n\
+             unreadable\t0\n"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+};
+
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// The bytes of `value` as the OS has them. Elsewhere than on Unix, values that aren't
+/// UTF-8 lose the characters that aren't.
+pub fn os_str_bytes(value: &OsStr) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    let bytes = Cow::Borrowed(value.as_bytes());
+    #[cfg(not(unix))]
+    let bytes = match value.to_string_lossy() {
+        Cow::Borrowed(value) => Cow::Borrowed(value.as_bytes()),
+        Cow::Owned(value) => Cow::Owned(value.into_bytes()),
+    };
+    bytes
+}
+
+/// The inverse of [`os_str_bytes`]
+pub fn os_string_from_bytes(bytes: Vec<u8>) -> OsString {
+    #[cfg(unix)]
+    let value = OsString::from_vec(bytes);
+    #[cfg(not(unix))]
+    let value = String::from_utf8_lossy(&bytes).into_owned().into();
+    value
+}
+
+/// Single quotes `bytes`, closing the quotes around any single quotes inside. Bytes that
+/// can't be written as they are, like the newlines that would end a commented out command
+/// and let the rest of a path run, are escaped inside bash's `$'...'` quotes instead.
+pub fn shell_quote(bytes: &[u8]) -> Vec<u8> {
+    if bytes.iter().any(u8::is_ascii_control) {
+        return ansi_c_quote(bytes);
+    }
+
+    let mut quoted = Vec::with_capacity(bytes.len() + 2);
+    quoted.push(b'\'');
+    for byte in bytes {
+        if *byte == b'\'' {
+            quoted.extend_from_slice(br"'\''");
+        } else {
+            quoted.push(*byte);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+fn ansi_c_quote(bytes: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(bytes.len() + 3);
+    quoted.extend_from_slice(b"$'");
+    for byte in bytes {
+        match byte {
+            b'\\' | b'\'' => quoted.extend_from_slice(&[b'\\', *byte]),
+            b'\n' => quoted.extend_from_slice(br"\n"),
+            b'\r' => quoted.extend_from_slice(br"\r"),
+            b'\t' => quoted.extend_from_slice(br"\t"),
+            byte if byte.is_ascii_control() => {
+                quoted.extend_from_slice(format!("\\x{byte:02x}").as_bytes())
+            }
+            byte => quoted.push(*byte),
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_that_arent_utf8_are_quoted_as_they_are() {
+        assert_eq!(shell_quote(b"a\xff'b"), b"'a\xff'\\''b'".to_vec());
+        assert_eq!(shell_quote(b"\xff\n"), b"$'\xff\\n'".to_vec());
+    }
+}
@@ -0,0 +1,449 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// How often the theme file is checked for changes
+const THEME_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Where a theme file is looked for when none is given, after the user config directory.
+/// Relative to the same root as bevy's asset server, see [`assets_theme_path`].
+const ASSETS_THEME_PATH: &str = "assets/theme.ron";
+
+/// The colors that aren't picked by a color mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    /// The window background
+    pub window: Color,
+    /// The root block, when it isn't a zoomed-in directory
+    pub root: Color,
+    /// Files, in the modes that don't color them
+    pub file: Color,
+    /// Groups of children too small to draw on their own
+    pub small_items: Color,
+    /// Drawn over the hovered block
+    pub hover_overlay: Color,
+    /// Drawn over the block with keyboard focus
+    pub focus_overlay: Color,
+    /// Drawn over selected blocks
+    pub selection_overlay: Color,
+    /// The fps and entity counts in the top left corner
+    pub diagnostics_text: Color,
+    /// The background of panels, menus and dialogs
+    pub panel: Color,
+    /// Buttons in panels, and the hovered items of menus
+    pub panel_button: Color,
+    /// Hovered buttons, and pressed menu items
+    pub panel_button_hovered: Color,
+    pub panel_button_pressed: Color,
+    pub panel_text: Color,
+    /// Disabled buttons' text
+    pub panel_text_dim: Color,
+    /// Text about what can't be undone, like deleting for good, and about failures
+    pub danger_text: Color,
+    /// The button that deletes for good
+    pub danger_button: Color,
+    pub danger_button_hovered: Color,
+    pub danger_button_pressed: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemePreset::Dark.theme()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThemePreset {
+    Dark,
+    Light,
+    HighContrast,
+}
+
+impl ThemePreset {
+    const ALL: [ThemePreset; 3] = [
+        ThemePreset::Dark,
+        ThemePreset::Light,
+        ThemePreset::HighContrast,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ThemePreset::Dark => "dark",
+            ThemePreset::Light => "light",
+            ThemePreset::HighContrast => "high-contrast",
+        }
+    }
+
+    pub fn theme(&self) -> Theme {
+        match self {
+            ThemePreset::Dark => Theme {
+                window: Color::rgb(0.161, 0.173, 0.2),
+                root: Color::rgba(0.097, 0.104, 0.120, 0.0),
+                file: Color::rgb(0.502, 0.502, 0.502),
+                small_items: Color::rgb(0.231, 0.240, 0.263),
+                hover_overlay: Color::rgba(0.0, 0.0, 0.0, 0.36),
+                focus_overlay: Color::rgba(1.0, 1.0, 1.0, 0.28),
+                selection_overlay: Color::rgba(0.275, 0.588, 1.0, 0.4),
+                diagnostics_text: Color::WHITE,
+                panel: Color::rgba(0.097, 0.104, 0.120, 0.9),
+                panel_button: Color::rgba(0.231, 0.240, 0.263, 0.9),
+                panel_button_hovered: Color::rgba(0.353, 0.365, 0.396, 0.9),
+                panel_button_pressed: Color::rgba(0.451, 0.463, 0.494, 0.9),
+                panel_text: Color::WHITE,
+                panel_text_dim: Color::rgba(1.0, 1.0, 1.0, 0.4),
+                danger_text: Color::rgb(1.0, 0.45, 0.4),
+                danger_button: Color::rgb(0.6, 0.13, 0.13),
+                danger_button_hovered: Color::rgb(0.72, 0.18, 0.18),
+                danger_button_pressed: Color::rgb(0.82, 0.25, 0.25),
+            },
+            ThemePreset::Light => Theme {
+                window: Color::rgb(0.949, 0.953, 0.961),
+                root: Color::rgba(0.898, 0.906, 0.922, 0.0),
+                file: Color::rgb(0.667, 0.667, 0.667),
+                small_items: Color::rgb(0.808, 0.816, 0.835),
+                hover_overlay: Color::rgba(0.0, 0.0, 0.0, 0.2),
+                focus_overlay: Color::rgba(0.0, 0.333, 0.867, 0.36),
+                selection_overlay: Color::rgba(1.0, 0.6, 0.0, 0.4),
+                diagnostics_text: Color::rgb(0.118, 0.125, 0.141),
+                panel: Color::rgba(0.980, 0.980, 0.988, 0.92),
+                panel_button: Color::rgba(0.878, 0.886, 0.902, 0.95),
+                panel_button_hovered: Color::rgba(0.800, 0.808, 0.827, 0.95),
+                panel_button_pressed: Color::rgba(0.710, 0.718, 0.741, 0.95),
+                panel_text: Color::rgb(0.118, 0.125, 0.141),
+                panel_text_dim: Color::rgba(0.118, 0.125, 0.141, 0.45),
+                danger_text: Color::rgb(0.776, 0.157, 0.157),
+                danger_button: Color::rgb(0.918, 0.6, 0.6),
+                danger_button_hovered: Color::rgb(0.878, 0.486, 0.486),
+                danger_button_pressed: Color::rgb(0.831, 0.369, 0.369),
+            },
+            ThemePreset::HighContrast => Theme {
+                window: Color::BLACK,
+                root: Color::rgba(0.0, 0.0, 0.0, 0.0),
+                file: Color::rgb(0.8, 0.8, 0.8),
+                small_items: Color::rgb(0.4, 0.4, 0.4),
+                hover_overlay: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                focus_overlay: Color::rgba(1.0, 0.867, 0.0, 0.6),
+                selection_overlay: Color::rgba(0.0, 1.0, 1.0, 0.45),
+                diagnostics_text: Color::rgb(1.0, 0.867, 0.0),
+                panel: Color::rgba(0.0, 0.0, 0.0, 0.95),
+                panel_button: Color::rgb(0.2, 0.2, 0.2),
+                panel_button_hovered: Color::rgb(0.35, 0.35, 0.35),
+                panel_button_pressed: Color::rgb(0.5, 0.5, 0.5),
+                panel_text: Color::WHITE,
+                panel_text_dim: Color::rgba(1.0, 1.0, 1.0, 0.6),
+                danger_text: Color::rgb(1.0, 0.4, 0.4),
+                danger_button: Color::rgb(0.7, 0.0, 0.0),
+                danger_button_hovered: Color::rgb(0.85, 0.0, 0.0),
+                danger_button_pressed: Color::rgb(1.0, 0.2, 0.2),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ThemePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ThemePreset {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Where the theme comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThemeSource {
+    /// `theme.ron` in the user config directory, then under assets, then the dark preset
+    Search,
+    Preset(ThemePreset),
+    File(PathBuf),
+}
+
+impl Default for ThemeSource {
+    fn default() -> Self {
+        ThemeSource::Search
+    }
+}
+
+impl FromStr for ThemeSource {
+    type Err = ();
+
+    /// A preset name, or else the path of an existing theme file, so that a misspelled
+    /// preset isn't taken for a file
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(preset) => Ok(ThemeSource::Preset(preset)),
+            Err(_) if Path::new(s).is_file() => Ok(ThemeSource::File(s.into())),
+            Err(_) => Err(()),
+        }
+    }
+}
+
+impl ThemeSource {
+    /// Loads the theme once, for front ends that don't watch the file. A search that
+    /// finds no file settles on the dark preset.
+    pub fn load(&self) -> Result<Theme, ThemeError> {
+        match (self, self.path()) {
+            (ThemeSource::Preset(preset), _) => Ok(preset.theme()),
+            (ThemeSource::Search, Some(path)) if !path.is_file() => Ok(Theme::default()),
+            (_, Some(path)) => Theme::load(&path),
+            (_, None) => Ok(Theme::default()),
+        }
+    }
+
+    /// The file to load and watch, if any. Searches settle on the user config file when
+    /// nothing exists yet, so that creating it takes effect.
+    fn path(&self) -> Option<PathBuf> {
+        match self {
+            ThemeSource::Preset(_) => None,
+            ThemeSource::File(path) => Some(path.clone()),
+            ThemeSource::Search => match user_config_path("theme.ron") {
+                Some(user_path) if user_path.is_file() => Some(user_path),
+                user_path => assets_theme_path()
+                    .filter(|assets_path| assets_path.is_file())
+                    .or(user_path),
+            },
+        }
+    }
+}
+
+/// The theme under the assets directory. Like bevy's asset server, this looks in
+/// `$BEVY_ASSET_ROOT`, then `$CARGO_MANIFEST_DIR`, then next to the executable, rather
+/// than in the working directory.
+fn assets_theme_path() -> Option<PathBuf> {
+    let root = match env::var_os("BEVY_ASSET_ROOT")
+        .or_else(|| env::var_os("CARGO_MANIFEST_DIR"))
+    {
+        Some(root) => PathBuf::from(root),
+        None => env::current_exe().ok()?.parent()?.to_path_buf(),
+    };
+    Some(root.join(ASSETS_THEME_PATH))
+}
+
+/// `$XDG_CONFIG_HOME/visual_du/{file_name}`, defaulting to `~/.config`
+pub(crate) fn user_config_path(file_name: &str) -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("visual_du").join(file_name))
+}
+
+/// A theme file. Colors are hex strings (`"#rrggbb"` or `"#rrggbbaa"`), and any that are
+/// left out come from `preset`, or from the dark preset.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    preset: Option<String>,
+    window: Option<String>,
+    root: Option<String>,
+    file: Option<String>,
+    small_items: Option<String>,
+    hover_overlay: Option<String>,
+    focus_overlay: Option<String>,
+    selection_overlay: Option<String>,
+    diagnostics_text: Option<String>,
+    panel: Option<String>,
+    panel_button: Option<String>,
+    panel_button_hovered: Option<String>,
+    panel_button_pressed: Option<String>,
+    panel_text: Option<String>,
+    panel_text_dim: Option<String>,
+    danger_text: Option<String>,
+    danger_button: Option<String>,
+    danger_button_hovered: Option<String>,
+    danger_button_pressed: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(io::Error),
+    Parse(ron::Error),
+    UnknownPreset(String),
+    InvalidColor { field: &'static str, value: String },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Io(err) => write!(f, "couldn't read the theme: {err}"),
+            ThemeError::Parse(err) => write!(f, "couldn't parse the theme: {err}"),
+            ThemeError::UnknownPreset(name) => write!(f, "unknown theme preset {name:?}"),
+            ThemeError::InvalidColor { field, value } => {
+                write!(f, "invalid color {value:?} for {field}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl Theme {
+    pub fn from_ron(ron_str: &str) -> Result<Self, ThemeError> {
+        // Colors can be written without `Some(..)`
+        let file: ThemeFile = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(ron_str)
+            .map_err(ThemeError::Parse)?;
+        let mut theme = match file.preset {
+            Some(name) => name
+                .parse::<ThemePreset>()
+                .map_err(|_| ThemeError::UnknownPreset(name))?
+                .theme(),
+            None => Theme::default(),
+        };
+
+        let fields = [
+            ("window", file.window, &mut theme.window),
+            ("root", file.root, &mut theme.root),
+            ("file", file.file, &mut theme.file),
+            ("small_items", file.small_items, &mut theme.small_items),
+            (
+                "hover_overlay",
+                file.hover_overlay,
+                &mut theme.hover_overlay,
+            ),
+            (
+                "focus_overlay",
+                file.focus_overlay,
+                &mut theme.focus_overlay,
+            ),
+            (
+                "selection_overlay",
+                file.selection_overlay,
+                &mut theme.selection_overlay,
+            ),
+            (
+                "diagnostics_text",
+                file.diagnostics_text,
+                &mut theme.diagnostics_text,
+            ),
+            ("panel", file.panel, &mut theme.panel),
+            ("panel_button", file.panel_button, &mut theme.panel_button),
+            (
+                "panel_button_hovered",
+                file.panel_button_hovered,
+                &mut theme.panel_button_hovered,
+            ),
+            (
+                "panel_button_pressed",
+                file.panel_button_pressed,
+                &mut theme.panel_button_pressed,
+            ),
+            ("panel_text", file.panel_text, &mut theme.panel_text),
+            (
+                "panel_text_dim",
+                file.panel_text_dim,
+                &mut theme.panel_text_dim,
+            ),
+            ("danger_text", file.danger_text, &mut theme.danger_text),
+            (
+                "danger_button",
+                file.danger_button,
+                &mut theme.danger_button,
+            ),
+            (
+                "danger_button_hovered",
+                file.danger_button_hovered,
+                &mut theme.danger_button_hovered,
+            ),
+            (
+                "danger_button_pressed",
+                file.danger_button_pressed,
+                &mut theme.danger_button_pressed,
+            ),
+        ];
+        for (field, maybe_value, color) in fields {
+            if let Some(value) = maybe_value {
+                *color = Color::hex(value.trim_start_matches('#'))
+                    .map_err(|_| ThemeError::InvalidColor { field, value })?;
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ThemeError> {
+        Self::from_ron(&fs::read_to_string(path).map_err(ThemeError::Io)?)
+    }
+}
+
+/// The theme file, and when it was last loaded
+struct ThemeWatch {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    poll_timer: Timer,
+}
+
+/// Inserts the [`Theme`] from `source`, and reloads it whenever its file changes
+pub struct ThemePlugin {
+    pub source: ThemeSource,
+}
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        let theme = match &self.source {
+            ThemeSource::Preset(preset) => preset.theme(),
+            _ => Theme::default(),
+        };
+        app.insert_resource(theme)
+            .insert_resource(ClearColor(theme.window))
+            .insert_resource(ThemeWatch {
+                path: self.source.path(),
+                modified: None,
+                poll_timer: Timer::new(THEME_POLL_INTERVAL, true),
+            })
+            .add_system(reload_theme_on_file_change)
+            .add_system(update_clear_color.after(reload_theme_on_file_change));
+    }
+}
+
+fn reload_theme_on_file_change(
+    time: Res<Time>,
+    mut watch: ResMut<ThemeWatch>,
+    mut theme: ResMut<Theme>,
+    mut has_polled: Local<bool>,
+) {
+    let path = match &watch.path {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    // The first check is immediate, so the file's colors are there from the start
+    if !watch.poll_timer.tick(time.delta()).just_finished() && *has_polled {
+        return;
+    }
+    *has_polled = true;
+
+    // A file that's gone keeps the theme it last had
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    if modified.is_none() || modified == watch.modified {
+        return;
+    }
+    watch.modified = modified;
+
+    match Theme::load(&path) {
+        Ok(new_theme) => {
+            if *theme != new_theme {
+                info!(path = %path.display(), "theme loaded");
+                *theme = new_theme;
+            }
+        }
+        Err(err) => warn!(path = %path.display(), %err, "theme not loaded"),
+    }
+}
+
+fn update_clear_color(theme: Res<Theme>, mut clear_color: ResMut<ClearColor>) {
+    if theme.is_changed() {
+        clear_color.0 = theme.window;
+    }
+}
@@ -0,0 +1,5 @@
+pub mod terminal_app;
+pub mod text_icicle;
+
+pub use terminal_app::*;
+pub use text_icicle::*;
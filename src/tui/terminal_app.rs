@@ -0,0 +1,372 @@
+use super::{layout_text_icicle, TextBlock};
+use crate::{
+    fs::{
+        human_readable_size, percentage, spawn_batched_walk, FsEntity, FsNodeId,
+        FsNodeKind, FsTree,
+    },
+    theme::Theme,
+    ui::{contrasting_text_color, ColorScheme, NodeColorer},
+};
+use bevy::prelude::{default, Color};
+use crossbeam_channel::{Receiver, TryRecvError};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{self, Print, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::{
+    io::{self, Write},
+    path,
+    time::{Duration, Instant},
+};
+
+/// The number of rows reserved below the icicle for the details pane
+const DETAILS_PANE_HEIGHT: u16 = 5;
+/// How long we wait for input before checking the walker for more entities
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The amount of time spent ingesting between redraws
+const INGEST_BUDGET: Duration = Duration::from_millis(30);
+/// Starts each block, so that neighbors of the same color stay apart
+const BLOCK_EDGE: char = '▏';
+/// Fills blocks past their labels. The focused block is shaded instead.
+const BLOCK_FILL: char = '█';
+const FOCUSED_BLOCK_FILL: char = '▒';
+
+/// Runs the terminal front end until the user quits. Blocks are colored as they are in
+/// the GUI.
+pub fn run_tui(
+    root_path: path::PathBuf,
+    color_scheme: ColorScheme,
+    theme: Theme,
+) -> io::Result<()> {
+    let fs_stream = spawn_batched_walk(root_path);
+    let mut app = TerminalApp {
+        color_scheme,
+        theme,
+        ..default()
+    };
+
+    let _terminal_guard = TerminalGuard::enter()?;
+    let mut stdout = io::stdout();
+    let mut needs_redraw = true;
+    loop {
+        needs_redraw |= app.ingest(&fs_stream);
+
+        if event::poll(INPUT_POLL_INTERVAL)? {
+            match event::read()? {
+                Event::Key(key_event) => match app.handle_key(key_event) {
+                    KeyOutcome::Quit => break,
+                    KeyOutcome::Handled => needs_redraw = true,
+                    KeyOutcome::Ignored => {}
+                },
+                Event::Resize(_, _) => needs_redraw = true,
+                Event::Mouse(_) => {}
+            }
+        }
+
+        if needs_redraw {
+            app.draw(&mut stdout)?;
+            needs_redraw = false;
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts the terminal into raw mode on an alternate screen, and restores it when dropped
+/// (including when unwinding from a panic)
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+enum KeyOutcome {
+    Quit,
+    Handled,
+    Ignored,
+}
+
+#[derive(Default)]
+struct TerminalApp {
+    fs_tree: FsTree,
+    color_scheme: ColorScheme,
+    theme: Theme,
+    is_scan_complete: bool,
+    /// The node drawn on the bottom row, filling the width of the terminal
+    layout_root: Option<FsNodeId>,
+    focused: Option<FsNodeId>,
+    /// The blocks from the last draw, used for navigation
+    blocks: Vec<TextBlock>,
+}
+
+impl TerminalApp {
+    /// Moves entities from the walker into the tree. Returns true if anything changed.
+    fn ingest(&mut self, fs_stream: &Receiver<Vec<FsEntity>>) -> bool {
+        if self.is_scan_complete {
+            return false;
+        }
+
+        let started_at = Instant::now();
+        let mut has_changes = false;
+        while started_at.elapsed() < INGEST_BUDGET {
+            match fs_stream.try_recv() {
+                Ok(batch) => {
+                    for fs_entity in batch.iter() {
+                        self.fs_tree.insert(fs_entity);
+                    }
+                    has_changes = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.is_scan_complete = true;
+                    has_changes = true;
+                    break;
+                }
+            }
+        }
+
+        if has_changes {
+            self.fs_tree.propagate_sizes();
+            if self.layout_root.is_none() {
+                self.layout_root = self.fs_tree.root();
+                self.focused = self.layout_root;
+            }
+        }
+        has_changes
+    }
+
+    fn handle_key(&mut self, key_event: KeyEvent) -> KeyOutcome {
+        let focused = match self.focused {
+            Some(focused) => focused,
+            None => {
+                return match key_event.code {
+                    KeyCode::Char('q') | KeyCode::Esc => KeyOutcome::Quit,
+                    _ => KeyOutcome::Ignored,
+                };
+            }
+        };
+
+        match key_event.code {
+            KeyCode::Char('q') | KeyCode::Esc => return KeyOutcome::Quit,
+            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                return KeyOutcome::Quit
+            }
+            KeyCode::Left => self.focused = self.sibling_of(focused, -1).or(self.focused),
+            KeyCode::Right => self.focused = self.sibling_of(focused, 1).or(self.focused),
+            KeyCode::Up => self.focused = self.largest_child_of(focused).or(self.focused),
+            KeyCode::Down if Some(focused) != self.layout_root => {
+                self.focused = self.fs_tree.parent(focused)
+            }
+            KeyCode::Enter if self.fs_tree.kind(focused) == FsNodeKind::Dir => {
+                self.layout_root = Some(focused)
+            }
+            KeyCode::Backspace => {
+                let layout_root = self.layout_root.unwrap();
+                if let Some(parent) = self.fs_tree.parent(layout_root) {
+                    self.layout_root = Some(parent);
+                    self.focused = Some(layout_root);
+                }
+            }
+            _ => return KeyOutcome::Ignored,
+        }
+        KeyOutcome::Handled
+    }
+
+    /// Finds the displayed sibling `offset` positions away from `node_id`
+    fn sibling_of(&self, node_id: FsNodeId, offset: isize) -> Option<FsNodeId> {
+        let parent_id = self.fs_tree.parent(node_id)?;
+        let siblings: Vec<FsNodeId> = self
+            .blocks
+            .iter()
+            .filter(|b| b.parent_id == Some(parent_id))
+            .map(|b| b.node_id)
+            .collect();
+        let index = siblings.iter().position(|s| *s == node_id)? as isize + offset;
+        if index < 0 {
+            None
+        } else {
+            siblings.get(index as usize).copied()
+        }
+    }
+
+    fn largest_child_of(&self, node_id: FsNodeId) -> Option<FsNodeId> {
+        self.blocks
+            .iter()
+            .filter(|b| b.parent_id == Some(node_id))
+            .max_by_key(|b| self.fs_tree.aggregate_size(b.node_id))
+            .map(|b| b.node_id)
+    }
+
+    fn draw<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let chart_height = height.saturating_sub(DETAILS_PANE_HEIGHT);
+
+        queue!(
+            out,
+            style::ResetColor,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+
+        self.blocks = match self.layout_root {
+            Some(layout_root) => {
+                let colorer = NodeColorer {
+                    scheme: &self.color_scheme,
+                    theme: &self.theme,
+                    fs_tree: &self.fs_tree,
+                };
+                layout_text_icicle(&colorer, layout_root, width, chart_height)
+            }
+            None => vec![],
+        };
+
+        // The focused node can disappear if it becomes too small to display
+        if !self.blocks.iter().any(|b| Some(b.node_id) == self.focused) {
+            self.focused = self.layout_root;
+        }
+
+        for block in self.blocks.iter() {
+            let is_focused = Some(block.node_id) == self.focused;
+            let text_color = to_terminal_color(contrasting_text_color(block.color));
+            let block_color = to_terminal_color(block.color);
+            // The label takes what's left after the edge, and is drawn on the block's
+            // color, with the block's glyphs filling the rest of it
+            let label_width = block.width.saturating_sub(1) as usize;
+            let label = fit_to_width(
+                &format!(
+                    "{} {}",
+                    self.display_name(block.node_id),
+                    human_readable_size(self.fs_tree.aggregate_size(block.node_id))
+                ),
+                label_width,
+            );
+            let label = label.trim_end();
+            let fill = if is_focused {
+                FOCUSED_BLOCK_FILL
+            } else {
+                BLOCK_FILL
+            };
+            let fill = fill.to_string().repeat(label_width - label.chars().count());
+
+            queue!(
+                out,
+                cursor::MoveTo(block.x, chart_height - 1 - block.row),
+                SetBackgroundColor(block_color),
+                SetForegroundColor(text_color),
+                Print(BLOCK_EDGE),
+                Print(label),
+                SetForegroundColor(if is_focused { text_color } else { block_color }),
+                Print(fill),
+            )?;
+        }
+
+        queue!(out, style::ResetColor)?;
+        for (i, line) in self.details_lines().iter().enumerate() {
+            queue!(
+                out,
+                cursor::MoveTo(0, chart_height + i as u16),
+                Print(fit_to_width(line, width as usize).trim_end()),
+            )?;
+        }
+
+        out.flush()
+    }
+
+    fn display_name(&self, node_id: FsNodeId) -> String {
+        if node_id == FsNodeId::ROOT {
+            self.fs_tree.root_path().display().to_string()
+        } else {
+            self.fs_tree.name(node_id).to_string_lossy().into()
+        }
+    }
+
+    fn details_lines(&self) -> Vec<String> {
+        let status = format!(
+            "{} {} entries  ·  ←/→ siblings  ↑ largest child  ↓ parent  \
+             enter zoom in  backspace zoom out  q quit",
+            if self.is_scan_complete {
+                "scanned"
+            } else {
+                "scanning…"
+            },
+            // The root, and everything below it
+            self.fs_tree.root().map_or(0, |root| {
+                1 + self.fs_tree.descendent_file_count(root)
+                    + self.fs_tree.descendent_dir_count(root)
+            })
+        );
+
+        let focused = match self.focused {
+            Some(focused) => focused,
+            None => return vec![String::new(), status],
+        };
+
+        let size_in_bytes = self.fs_tree.aggregate_size(focused);
+        let parent_size = self
+            .fs_tree
+            .parent(focused)
+            .map_or(size_in_bytes, |p| self.fs_tree.aggregate_size(p));
+        let root_size = self.fs_tree.aggregate_size(FsNodeId::ROOT);
+        let kind = match self.fs_tree.kind(focused) {
+            FsNodeKind::Dir => format!(
+                "directory, {} items",
+                self.fs_tree.children(focused).count()
+            ),
+            FsNodeKind::File => "file".into(),
+            FsNodeKind::Symlink => "symlink".into(),
+            FsNodeKind::Other => "other".into(),
+        };
+
+        vec![
+            String::new(),
+            self.fs_tree.path(focused).display().to_string(),
+            format!(
+                "{} ({} bytes)  ·  {} of parent  ·  {} of root  ·  {}",
+                human_readable_size(size_in_bytes),
+                size_in_bytes,
+                percentage(size_in_bytes, parent_size),
+                percentage(size_in_bytes, root_size),
+                kind,
+            ),
+            String::new(),
+            status,
+        ]
+    }
+}
+
+/// Pads or truncates (with an ellipsis) `text` to exactly `width` characters. Blocks too
+/// narrow to show anything but an ellipsis are left blank.
+fn fit_to_width(text: &str, width: usize) -> String {
+    let char_count = text.chars().count();
+    if char_count <= width {
+        format!("{:width$}", text, width = width)
+    } else if width < 3 {
+        " ".repeat(width)
+    } else {
+        let mut truncated: String = text.chars().take(width - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+fn to_terminal_color(color: Color) -> style::Color {
+    let [r, g, b, _] = color.as_rgba_f32();
+    style::Color::Rgb {
+        r: (r * 255.0) as u8,
+        g: (g * 255.0) as u8,
+        b: (b * 255.0) as u8,
+    }
+}
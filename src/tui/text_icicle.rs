@@ -0,0 +1,89 @@
+use crate::{
+    fs::{FsNodeId, FsNodeKind},
+    ui::{layout_icicle, DescendentColorRange, IcicleParams, LayoutRect, NodeColorer},
+};
+use bevy::prelude::Color;
+
+/// Children narrower than this many cells are not displayed
+const MIN_CHILD_CELLS: f32 = 1.0;
+
+/// A node's block in the text icicle. Rows are counted up from the layout root, which
+/// sits on the bottom row, just like in the GUI.
+#[derive(Clone, Copy, Debug)]
+pub struct TextBlock {
+    pub node_id: FsNodeId,
+    pub parent_id: Option<FsNodeId>,
+    pub row: u16,
+    pub x: u16,
+    pub width: u16,
+    pub color: Color,
+}
+
+/// Lays out `root` and its descendents across `width` cells and at most `max_rows` rows,
+/// with the same [`layout_icicle`] as the GUI, one cell to a unit and without gaps.
+/// Blocks are colored by `colorer`, except for the root, which the GUI leaves transparent
+/// but which needs to be visible in a terminal to be navigable.
+pub fn layout_text_icicle(
+    colorer: &NodeColorer,
+    root: FsNodeId,
+    width: u16,
+    max_rows: u16,
+) -> Vec<TextBlock> {
+    if width == 0 || max_rows == 0 {
+        return vec![];
+    }
+
+    let params = IcicleParams {
+        gap_width: 0.0,
+        min_child_width_with_gap: MIN_CHILD_CELLS,
+        max_levels: max_rows - 1,
+        visible_min_x: 0.0,
+        visible_max_x: width as f32,
+    };
+    let viewport = LayoutRect {
+        x: 0.0,
+        y: 0.0,
+        w: width as f32,
+        h: 1.0,
+    };
+    let layout = layout_icicle(
+        colorer.fs_tree,
+        root,
+        viewport,
+        DescendentColorRange::default(),
+        &params,
+    );
+
+    let mut root_color = colorer.theme.root;
+    root_color.set_a(1.0);
+    layout
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            // Widths are whole units, apart from the rounding remainder given to the last
+            // child, so rounding both edges keeps neighbors from overlapping
+            let x = node.rect.x.round() as u16;
+            let right = ((node.rect.x + node.rect.w).round() as u16).min(width);
+            if right <= x {
+                return None;
+            }
+
+            let color = match node.parent {
+                None => root_color,
+                Some(_) => {
+                    let color_range = (colorer.fs_tree.kind(node.id) == FsNodeKind::Dir)
+                        .then_some(&node.color_range);
+                    colorer.color(node.id, color_range, node.level)
+                }
+            };
+            Some(TextBlock {
+                node_id: node.id,
+                parent_id: node.parent,
+                row: node.level,
+                x,
+                width: right - x,
+                color,
+            })
+        })
+        .collect()
+}
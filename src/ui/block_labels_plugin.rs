@@ -0,0 +1,251 @@
+use super::{
+    small_items_group::SmallItemsGroup,
+    tree_view_plugin::{DiskUsageTreeOptions, TreeViewLayout},
+    tree_view_theme::contrasting_text_color,
+    tree_view_treemap::TREEMAP_HEADER_HEIGHT,
+};
+use crate::fs::{
+    human_readable_size, FsAggregateSize, FsEntityComponent, FsNodeId, FsTree,
+};
+use bevy::{prelude::*, transform::TransformSystem, utils::HashSet};
+
+const LABEL_FONT_SIZE: f32 = 13.0;
+/// Both label fonts are monospaced, with glyphs 0.6em wide, which lets us truncate without
+/// measuring
+const LABEL_CHAR_WIDTH: f32 = LABEL_FONT_SIZE * 0.6;
+const LABEL_PADDING: f32 = 4.0;
+/// Labels are drawn above the blocks, and below the hover and focus overlays
+const LABEL_Z: f32 = 1.5;
+const SIZE_SEPARATOR: &str = "  ";
+
+struct LabelFonts {
+    name: Handle<Font>,
+    size: Handle<Font>,
+}
+
+/// Links an fs entity to the entity drawing its label.
+///
+/// Labels aren't children of their blocks, because blocks are scaled to their size, and
+/// the text would be scaled along with them.
+#[derive(Component)]
+struct BlockLabel(Entity);
+
+/// Links a label back to its block
+#[derive(Component)]
+struct LabelOf(Entity);
+
+pub struct BlockLabelsPlugin;
+impl Plugin for BlockLabelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_label_fonts)
+            // Blocks are despawned during the update, and linking a label to a block
+            // that's gone would fail
+            .add_system_to_stage(CoreStage::PostUpdate, create_block_labels)
+            // Blocks can be animating, so we wait for their final positions for the frame
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_block_labels.after(TransformSystem::TransformPropagate),
+            )
+            // Removed components are only reported until the end of the frame, so this
+            // runs after every other stage has had its despawns applied
+            .add_system_to_stage(CoreStage::Last, despawn_orphaned_labels);
+    }
+}
+
+fn load_label_fonts(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LabelFonts {
+        name: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        size: asset_server.load("fonts/Hack-Regular.ttf"),
+    });
+}
+
+/// Every block (including groups of small items) gets a label entity when its sprite is
+/// created. The label's text is updated in place from then on.
+#[allow(clippy::type_complexity)]
+fn create_block_labels(
+    mut commands: Commands,
+    new_blocks_query: Query<
+        Entity,
+        (
+            Or<(With<FsEntityComponent>, With<SmallItemsGroup>)>,
+            Added<Sprite>,
+        ),
+    >,
+    fonts: Res<LabelFonts>,
+) {
+    for entity in new_blocks_query.iter() {
+        let text_style = |font: &Handle<Font>| TextStyle {
+            font: font.clone(),
+            font_size: LABEL_FONT_SIZE,
+            color: Color::WHITE,
+        };
+        let label = commands
+            .spawn_bundle(Text2dBundle {
+                text: Text {
+                    sections: vec![
+                        TextSection {
+                            value: String::new(),
+                            style: text_style(&fonts.name),
+                        },
+                        TextSection {
+                            value: String::new(),
+                            style: text_style(&fonts.size),
+                        },
+                    ],
+                    alignment: TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Left,
+                    },
+                },
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(LabelOf(entity))
+            .id();
+        commands.entity(entity).insert(BlockLabel(label));
+    }
+}
+
+/// Despawns the labels of blocks that were despawned, like those below a rescanned or
+/// deleted directory
+fn despawn_orphaned_labels(
+    mut commands: Commands,
+    removed_blocks: RemovedComponents<BlockLabel>,
+    labels_query: Query<(Entity, &LabelOf)>,
+) {
+    let removed_blocks: HashSet<Entity> = removed_blocks.iter().collect();
+    if removed_blocks.is_empty() {
+        return;
+    }
+    for (label, LabelOf(block)) in labels_query.iter() {
+        if removed_blocks.contains(block) {
+            commands.entity(label).despawn();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_block_labels(
+    fs_tree: Res<FsTree>,
+    tree_options: Res<DiskUsageTreeOptions>,
+    blocks_query: Query<
+        (
+            &BlockLabel,
+            Option<(&FsEntityComponent, &FsAggregateSize)>,
+            Option<&SmallItemsGroup>,
+            &GlobalTransform,
+            &Sprite,
+            &Visibility,
+        ),
+        Or<(
+            Changed<GlobalTransform>,
+            Changed<Visibility>,
+            Changed<FsAggregateSize>,
+            Changed<Sprite>,
+            Changed<SmallItemsGroup>,
+        )>,
+    >,
+    mut labels_query: Query<
+        (
+            &mut Text,
+            &mut Transform,
+            &mut GlobalTransform,
+            &mut Visibility,
+        ),
+        Without<BlockLabel>,
+    >,
+) {
+    for (label, maybe_fs, maybe_group, block_transform, block_sprite, block_vis) in
+        blocks_query.iter()
+    {
+        let (block_name, block_size) = match (maybe_fs, maybe_group) {
+            (Some((fs_entity, fs_size)), _) => (
+                display_name(&fs_tree, fs_entity.node_id),
+                fs_size.size_in_bytes,
+            ),
+            (None, Some(group)) => (group.label(), group.size_in_bytes),
+            (None, None) => continue,
+        };
+        let (mut text, mut transform, mut global_transform, mut vis) =
+            match labels_query.get_mut(label.0) {
+                Ok(label) => label,
+                Err(_) => continue,
+            };
+
+        let block_w = block_transform.scale.x;
+        let block_h = block_transform.scale.y;
+        // Treemap labels sit in the header strip along the top of each block
+        let label_y = match tree_options.layout {
+            TreeViewLayout::Treemap => block_h - TREEMAP_HEADER_HEIGHT / 2.0,
+            _ => block_h / 2.0,
+        };
+        let max_chars = ((block_w - LABEL_PADDING * 2.0) / LABEL_CHAR_WIDTH).floor();
+        let label_parts = if block_vis.is_visible && block_h >= LABEL_FONT_SIZE {
+            fit_label(
+                &block_name,
+                &human_readable_size(block_size),
+                max_chars.max(0.0) as usize,
+            )
+        } else {
+            None
+        };
+
+        let (name, size) = match label_parts {
+            Some(parts) => parts,
+            None => {
+                if vis.is_visible {
+                    vis.is_visible = false;
+                }
+                continue;
+            }
+        };
+
+        vis.is_visible = true;
+        *transform = Transform::from_xyz(
+            block_transform.translation.x + LABEL_PADDING,
+            block_transform.translation.y + label_y,
+            LABEL_Z,
+        );
+        // Labels are top-level entities, and transform propagation has already run
+        *global_transform = (*transform).into();
+
+        let color = contrasting_text_color(block_sprite.color);
+        if text.sections[0].value != name || text.sections[0].style.color != color {
+            text.sections[0].value = name;
+            text.sections[0].style.color = color;
+        }
+        let mut size_color = color;
+        size_color.set_a(0.7);
+        if text.sections[1].value != size || text.sections[1].style.color != size_color {
+            text.sections[1].value = size;
+            text.sections[1].style.color = size_color;
+        }
+    }
+}
+
+fn display_name(fs_tree: &FsTree, node_id: FsNodeId) -> String {
+    if node_id == FsNodeId::ROOT {
+        fs_tree.root_path().display().to_string()
+    } else {
+        fs_tree.name(node_id).to_string_lossy().into()
+    }
+}
+
+/// Fits a name and a size into `max_chars`. The size is dropped first, then the name is
+/// truncated with an ellipsis. Labels with room for fewer than 3 characters aren't shown.
+fn fit_label(name: &str, size: &str, max_chars: usize) -> Option<(String, String)> {
+    if max_chars < 3 {
+        return None;
+    }
+
+    let name_chars = name.chars().count();
+    if name_chars + SIZE_SEPARATOR.len() + size.chars().count() <= max_chars {
+        Some((name.into(), format!("{SIZE_SEPARATOR}{size}")))
+    } else if name_chars <= max_chars {
+        Some((name.into(), String::new()))
+    } else {
+        let mut truncated: String = name.chars().take(max_chars - 1).collect();
+        truncated.push('…');
+        Some((truncated, String::new()))
+    }
+}
@@ -0,0 +1,427 @@
+use super::tree_view_theme::{hsl_get_color, shade_for_depth, DescendentColorRange};
+use crate::{
+    fs::{FsNodeId, FsNodeKind, FsTree},
+    theme::Theme,
+};
+use bevy::prelude::Color;
+use colorous::Gradient;
+use std::{
+    fmt,
+    os::unix::ffi::OsStrExt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Directories in the modes that only color files
+const DIRECTORY_COLOR: Color = Color::rgb(0.3, 0.32, 0.35);
+/// Anything older than this is colored as the oldest
+const MAX_AGE_DAYS: f32 = 3650.0;
+
+/// What a node's color is derived from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Directories are colored by their position in their parent's color range
+    Position,
+    /// Like `Position`, but with hues walked around the HSL color wheel
+    Hsl,
+    /// Files are colored by their extension
+    FileType,
+    /// Nodes are colored by how recently they were modified
+    Age,
+    /// Nodes are colored by the user that owns them
+    Owner,
+    /// Nodes are colored by their share of their parent's size
+    RelativeSize,
+    /// Nodes are colored by a hash of their path, so colors don't shift as sizes change
+    /// during a scan, and are the same on every scan
+    Stable,
+}
+
+/// Where a mode gets its colors from
+#[derive(Clone, Copy)]
+pub enum Palette {
+    /// The mode's own coloring
+    Builtin,
+    Continuous(&'static Gradient),
+    Categorical(&'static [colorous::Color]),
+}
+
+pub struct NamedPalette {
+    pub name: &'static str,
+    pub palette: Palette,
+    /// Readable with the common forms of color blindness
+    pub is_colorblind_safe: bool,
+}
+
+const fn named(
+    name: &'static str,
+    palette: Palette,
+    is_colorblind_safe: bool,
+) -> NamedPalette {
+    NamedPalette {
+        name,
+        palette,
+        is_colorblind_safe,
+    }
+}
+
+/// Okabe and Ito's palette, which stays distinguishable with the common forms of color
+/// blindness. Its black is left out, since it would disappear into dark themes.
+const OKABE_ITO: [colorous::Color; 7] = [
+    rgb(0xe6, 0x9f, 0x00),
+    rgb(0x56, 0xb4, 0xe9),
+    rgb(0x00, 0x9e, 0x73),
+    rgb(0xf0, 0xe4, 0x42),
+    rgb(0x00, 0x72, 0xb2),
+    rgb(0xd5, 0x5e, 0x00),
+    rgb(0xcc, 0x79, 0xa7),
+];
+
+const fn rgb(r: u8, g: u8, b: u8) -> colorous::Color {
+    colorous::Color { r, g, b }
+}
+
+const POSITION_PALETTES: [NamedPalette; 3] = [
+    named("rainbow", Palette::Builtin, false),
+    named("sinebow", Palette::Continuous(&colorous::SINEBOW), false),
+    named("viridis", Palette::Continuous(&colorous::VIRIDIS), true),
+];
+const HSL_PALETTES: [NamedPalette; 2] = [
+    named("hsl", Palette::Builtin, false),
+    named("cividis", Palette::Continuous(&colorous::CIVIDIS), true),
+];
+// ColorBrewer's qualitative palettes are only colorblind safe up to three colors, and
+// file types and owners use all of them
+const FILE_TYPE_PALETTES: [NamedPalette; 4] = [
+    named(
+        "tableau10",
+        Palette::Categorical(&colorous::TABLEAU10),
+        false,
+    ),
+    named("okabe-ito", Palette::Categorical(&OKABE_ITO), true),
+    named("dark2", Palette::Categorical(&colorous::DARK2), false),
+    named("set2", Palette::Categorical(&colorous::SET2), false),
+];
+const AGE_PALETTES: [NamedPalette; 3] = [
+    named("turbo", Palette::Continuous(&colorous::TURBO), false),
+    named("cividis", Palette::Continuous(&colorous::CIVIDIS), true),
+    named("viridis", Palette::Continuous(&colorous::VIRIDIS), true),
+];
+const OWNER_PALETTES: [NamedPalette; 3] = [
+    named(
+        "category10",
+        Palette::Categorical(&colorous::CATEGORY10),
+        false,
+    ),
+    named("okabe-ito", Palette::Categorical(&OKABE_ITO), true),
+    named("dark2", Palette::Categorical(&colorous::DARK2), false),
+];
+const RELATIVE_SIZE_PALETTES: [NamedPalette; 2] = [
+    named("inferno", Palette::Continuous(&colorous::INFERNO), true),
+    named("cividis", Palette::Continuous(&colorous::CIVIDIS), true),
+];
+const STABLE_PALETTES: [NamedPalette; 3] = [
+    named("hsl", Palette::Builtin, false),
+    named("sinebow", Palette::Continuous(&colorous::SINEBOW), false),
+    named("okabe-ito", Palette::Categorical(&OKABE_ITO), true),
+];
+
+impl ColorMode {
+    const ALL: [ColorMode; 7] = [
+        ColorMode::Position,
+        ColorMode::Hsl,
+        ColorMode::FileType,
+        ColorMode::Age,
+        ColorMode::Owner,
+        ColorMode::RelativeSize,
+        ColorMode::Stable,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Position => "position",
+            ColorMode::Hsl => "hsl",
+            ColorMode::FileType => "file-type",
+            ColorMode::Age => "age",
+            ColorMode::Owner => "owner",
+            ColorMode::RelativeSize => "relative-size",
+            ColorMode::Stable => "stable",
+        }
+    }
+
+    fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|mode| mode == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// The palettes this mode can be drawn with. The first is the default.
+    pub fn palettes(&self) -> &'static [NamedPalette] {
+        match self {
+            ColorMode::Position => &POSITION_PALETTES,
+            ColorMode::Hsl => &HSL_PALETTES,
+            ColorMode::FileType => &FILE_TYPE_PALETTES,
+            ColorMode::Age => &AGE_PALETTES,
+            ColorMode::Owner => &OWNER_PALETTES,
+            ColorMode::RelativeSize => &RELATIVE_SIZE_PALETTES,
+            ColorMode::Stable => &STABLE_PALETTES,
+        }
+    }
+}
+
+impl fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or(())
+    }
+}
+
+/// How the tree is colored: a mode, and one of its palettes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub mode: ColorMode,
+    palette_index: usize,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self::new(ColorMode::Position)
+    }
+}
+
+impl ColorScheme {
+    /// `mode`, with its default palette
+    pub fn new(mode: ColorMode) -> Self {
+        Self {
+            mode,
+            palette_index: 0,
+        }
+    }
+
+    pub fn palette(&self) -> &'static NamedPalette {
+        &self.mode.palettes()[self.palette_index]
+    }
+
+    pub fn next_mode(&mut self) {
+        *self = Self::new(self.mode.next());
+    }
+
+    pub fn next_palette(&mut self) {
+        self.palette_index = (self.palette_index + 1) % self.mode.palettes().len();
+    }
+}
+
+impl fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.mode, self.palette().name)
+    }
+}
+
+/// A mode, optionally followed by one of its palettes, as in `age:cividis`
+impl FromStr for ColorScheme {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, palette_name) = match s.split_once(':') {
+            Some((mode, palette_name)) => {
+                (mode.parse::<ColorMode>()?, Some(palette_name))
+            }
+            None => (s.parse()?, None),
+        };
+        let palette_index = match palette_name {
+            Some(palette_name) => mode
+                .palettes()
+                .iter()
+                .position(|palette| palette.name == palette_name)
+                .ok_or(())?,
+            None => 0,
+        };
+        Ok(Self {
+            mode,
+            palette_index,
+        })
+    }
+}
+
+/// Colors nodes of `fs_tree` according to `scheme`
+pub struct NodeColorer<'a> {
+    pub scheme: &'a ColorScheme,
+    /// Where files get their color in the modes that don't color them
+    pub theme: &'a Theme,
+    pub fs_tree: &'a FsTree,
+}
+
+impl NodeColorer<'_> {
+    /// The color of a node drawn `level` levels above the layout root. Directories pass
+    /// their color range, which is where positional colors come from.
+    pub fn color(
+        &self,
+        node_id: FsNodeId,
+        color_range: Option<&DescendentColorRange>,
+        level: u16,
+    ) -> Color {
+        let palette = self.scheme.palette().palette;
+        match self.scheme.mode {
+            ColorMode::Position | ColorMode::Hsl => match (color_range, palette) {
+                (None, _) => self.theme.file,
+                (Some(range), Palette::Builtin) if self.scheme.mode == ColorMode::Hsl => {
+                    hsl_get_color(range.start(), level)
+                }
+                (Some(range), Palette::Continuous(gradient)) => {
+                    shade_for_depth(gradient_color(gradient, range.start()), level)
+                }
+                (Some(range), _) => range.get_color(0.0, level),
+            },
+            ColorMode::FileType => {
+                if self.fs_tree.kind(node_id) == FsNodeKind::Dir {
+                    return shade_for_depth(DIRECTORY_COLOR, level);
+                }
+                let name = self.fs_tree.name(node_id).to_string_lossy();
+                match name.rsplit_once('.') {
+                    Some((stem, extension)) if !stem.is_empty() => self.palette_color(
+                        palette,
+                        stable_hash(extension.to_lowercase().as_bytes()),
+                        0.0,
+                    ),
+                    _ => self.theme.file,
+                }
+            }
+            ColorMode::Age => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since_epoch| since_epoch.as_secs() as i64);
+                let mtime = self.fs_tree.metadata(node_id).mtime_secs;
+                let age_days = ((now - mtime) as f32 / 86_400.0).max(0.0);
+                // Ages are spread logarithmically, newest first
+                let t =
+                    1.0 - ((1.0 + age_days).ln() / (1.0 + MAX_AGE_DAYS).ln()).min(1.0);
+                self.palette_color(palette, 0, t)
+            }
+            ColorMode::Owner => {
+                let uid = self.fs_tree.metadata(node_id).uid;
+                self.palette_color(palette, uid as u64, 0.0)
+            }
+            ColorMode::RelativeSize => {
+                let size = self.fs_tree.aggregate_size(node_id);
+                let parent_size = self
+                    .fs_tree
+                    .parent(node_id)
+                    .map_or(size, |parent| self.fs_tree.aggregate_size(parent));
+                let fraction = if parent_size == 0 {
+                    1.0
+                } else {
+                    size as f32 / parent_size as f32
+                };
+                // Most children are a small share of their parent, so small shares are
+                // spread out
+                self.palette_color(palette, 0, fraction.sqrt())
+            }
+            ColorMode::Stable => {
+                // Shaded by depth in the tree rather than in the view, so that zooming
+                // doesn't change colors either
+                let hash = relative_path_hash(self.fs_tree, node_id);
+                let t = (hash % 1024) as f32 / 1024.0;
+                let depth = self.fs_tree.depth(node_id);
+                match palette {
+                    Palette::Builtin => hsl_get_color(t, depth),
+                    _ => shade_for_depth(self.palette_color(palette, hash, t), depth),
+                }
+            }
+        }
+    }
+
+    /// Categorical palettes use `index`, and continuous palettes use `t`
+    fn palette_color(&self, palette: Palette, index: u64, t: f32) -> Color {
+        match palette {
+            Palette::Categorical(colors) => {
+                let color = colors[(index % colors.len() as u64) as usize];
+                Color::rgb_u8(color.r, color.g, color.b)
+            }
+            Palette::Continuous(gradient) => gradient_color(gradient, t),
+            Palette::Builtin => self.theme.file,
+        }
+    }
+}
+
+fn gradient_color(gradient: &Gradient, t: f32) -> Color {
+    let color = gradient.eval_continuous(t.clamp(0.0, 1.0) as f64);
+    Color::rgb_u8(color.r, color.g, color.b)
+}
+
+/// FNV-1a, which unlike `std`'s hasher is guaranteed to give the same result on every
+/// run and every platform
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    extend_stable_hash(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+/// The [`stable_hash`] of some bytes followed by `bytes`, given the hash of the former
+fn extend_stable_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The [`stable_hash`] of a node's path relative to the scan root, so that colors survive
+/// moving the tree. The hash is extended from the root down, one name at a time, rather
+/// than built from the path.
+fn relative_path_hash(fs_tree: &FsTree, node_id: FsNodeId) -> u64 {
+    let parent = match fs_tree.parent(node_id) {
+        Some(parent) => parent,
+        None => return stable_hash(b""),
+    };
+    let mut hash = relative_path_hash(fs_tree, parent);
+    if fs_tree.parent(parent).is_some() {
+        hash = extend_stable_hash(hash, b"/");
+    }
+    extend_stable_hash(hash, fs_tree.name(node_id).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::FsEntity;
+    use std::path::PathBuf;
+
+    #[test]
+    fn relative_path_hash_matches_hashing_the_relative_path() {
+        let metadata = std::fs::metadata("/").unwrap();
+        let mut fs_tree = FsTree::new();
+        let mut parent_id = None;
+        for (index, path) in [
+            "/scan",
+            "/scan/usr",
+            "/scan/usr/lib",
+            "/scan/usr/lib/libc.so",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let id = fs_tree.insert(&FsEntity {
+                path: PathBuf::from(path),
+                metadata: metadata.clone(),
+                id: FsNodeId::from_index(index),
+                parent_id,
+                depth: index as u16,
+            });
+            parent_id = Some(id);
+        }
+
+        for id in fs_tree.iter() {
+            let path = fs_tree.relative_path(id);
+            assert_eq!(
+                relative_path_hash(&fs_tree, id),
+                stable_hash(path.as_os_str().as_bytes()),
+                "{path:?}"
+            );
+        }
+    }
+}
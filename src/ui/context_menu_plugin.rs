@@ -0,0 +1,412 @@
+use super::{
+    delete_dialog_plugin::DeleteRequest,
+    mouse_interactions_plugin::HoverableClicked,
+    panel_plugin::{menu_item_color, panel_bundle, PanelBackground, PanelFont},
+};
+use crate::{
+    fs::{DeleteMode, FsEntityComponent, FsNodeId, FsNodeKind, FsRescanRequest, FsTree},
+    shell::{os_str_bytes, os_string_from_bytes, shell_quote},
+    theme::{user_config_path, Theme},
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+};
+
+const MENU_FONT_SIZE: f32 = 14.0;
+/// Commands added by the user, appended to the built-in items
+const USER_COMMANDS_FILE_NAME: &str = "context_menu.ron";
+
+/// What a [`ContextMenuItem`] does to the node that the menu was opened on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContextMenuAction {
+    CopyPath,
+    Rescan,
+    /// Asks to move the node to the trash
+    Trash,
+    /// Asks to delete the node for good
+    DeletePermanently,
+    /// A bash command template. `{path}` is replaced by the node's path, `{dir}` by the
+    /// node if it's a directory or else by its parent, and `{name}` by its file name, all
+    /// quoted for the shell.
+    Command(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ContextMenuItem {
+    pub label: String,
+    pub action: ContextMenuAction,
+}
+
+impl ContextMenuItem {
+    pub fn new(label: impl Into<String>, action: ContextMenuAction) -> Self {
+        Self {
+            label: label.into(),
+            action,
+        }
+    }
+}
+
+/// The items of the context menu, in order. Plugins can add their own, and users can add
+/// commands in `context_menu.ron` in the user config directory.
+#[derive(Deref, DerefMut)]
+pub struct ContextMenuItems(pub Vec<ContextMenuItem>);
+
+impl Default for ContextMenuItems {
+    fn default() -> Self {
+        Self(vec![
+            ContextMenuItem::new("Copy path", ContextMenuAction::CopyPath),
+            ContextMenuItem::new(
+                "Open in file manager",
+                ContextMenuAction::Command("xdg-open {dir}".into()),
+            ),
+            ContextMenuItem::new(
+                "Open terminal here",
+                ContextMenuAction::Command(
+                    "cd {dir} && exec ${TERMINAL:-x-terminal-emulator}".into(),
+                ),
+            ),
+            ContextMenuItem::new("Rescan", ContextMenuAction::Rescan),
+            ContextMenuItem::new("Move to trash…", ContextMenuAction::Trash),
+            ContextMenuItem::new(
+                "Delete permanently…",
+                ContextMenuAction::DeletePermanently,
+            ),
+        ])
+    }
+}
+
+/// An entry of a user commands file, which holds a list of them:
+/// `[(label: "Open in editor", command: "code {path}")]`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CommandTemplate {
+    label: String,
+    command: String,
+}
+
+impl ContextMenuItems {
+    /// Appends the commands from a RON file of command templates
+    pub fn load_commands(&mut self, path: &Path) -> io::Result<()> {
+        let templates: Vec<CommandTemplate> =
+            ron::from_str(&fs::read_to_string(path)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.extend(templates.into_iter().map(|template| {
+            ContextMenuItem::new(
+                template.label,
+                ContextMenuAction::Command(template.command),
+            )
+        }));
+        Ok(())
+    }
+}
+
+/// The open menu, and what it was opened on
+#[derive(Component)]
+struct ContextMenu {
+    node_id: FsNodeId,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+/// A menu button, with the index of its [`ContextMenuItem`]
+#[derive(Component)]
+struct ContextMenuButton(usize);
+
+pub struct ContextMenuPlugin;
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContextMenuItems>()
+            .add_startup_system(load_user_commands)
+            .add_system(open_context_menu)
+            .add_system(handle_context_menu_interactions);
+    }
+}
+
+fn load_user_commands(mut items: ResMut<ContextMenuItems>) {
+    let path = match user_config_path(USER_COMMANDS_FILE_NAME) {
+        Some(path) if path.is_file() => path,
+        _ => return,
+    };
+    match items.load_commands(&path) {
+        Ok(()) => info!(path = %path.display(), "context menu commands loaded"),
+        Err(err) => {
+            warn!(path = %path.display(), %err, "context menu commands not loaded")
+        }
+    }
+}
+
+/// A right click on an fs block opens the menu at the cursor. Escape, or a click anywhere
+/// but the menu, closes it.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn open_context_menu(
+    mut commands: Commands,
+    mut clicked_events: EventReader<HoverableClicked>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    fs_tree: Res<FsTree>,
+    items: Res<ContextMenuItems>,
+    theme: Res<Theme>,
+    font: Res<PanelFont>,
+    fs_entity_query: Query<&FsEntityComponent>,
+    menu_query: Query<Entity, With<ContextMenu>>,
+    menu_interactions_query: Query<
+        &Interaction,
+        Or<(With<ContextMenu>, With<ContextMenuButton>)>,
+    >,
+) {
+    let is_menu_pressed = menu_interactions_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if keys.just_pressed(KeyCode::Escape)
+        || (mouse_buttons.get_just_pressed().next().is_some() && !is_menu_pressed)
+    {
+        for menu in menu_query.iter() {
+            commands.entity(menu).despawn_recursive();
+        }
+    }
+
+    let clicked_fs_entity = clicked_events
+        .iter()
+        .filter(|clicked| clicked.button == MouseButton::Right)
+        .filter_map(|clicked| fs_entity_query.get(clicked.entity).ok())
+        .next_back();
+    let (fs_entity, window) = match (clicked_fs_entity, windows.get_primary()) {
+        (Some(fs_entity), Some(window)) => (fs_entity, window),
+        _ => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // The menu opens away from the window's nearest edges, so that it stays inside it.
+    // UI positions are measured from the bottom left, like the cursor's.
+    let (width, height) = (window.width(), window.height());
+    let position = Rect {
+        left: px_if(cursor.x < width / 2.0, cursor.x),
+        right: px_if(cursor.x >= width / 2.0, width - cursor.x),
+        top: px_if(cursor.y >= height / 2.0, height - cursor.y),
+        bottom: px_if(cursor.y < height / 2.0, cursor.y),
+    };
+
+    commands
+        .spawn_bundle(panel_bundle(
+            &theme,
+            Style {
+                position_type: PositionType::Absolute,
+                position,
+                padding: Rect::all(Val::Px(4.0)),
+                ..default()
+            },
+        ))
+        .insert(PanelBackground)
+        .insert(Interaction::default())
+        .insert(ContextMenu {
+            node_id: fs_entity.node_id,
+            path: fs_tree.path(fs_entity.node_id),
+            is_dir: fs_tree.kind(fs_entity.node_id) == FsNodeKind::Dir,
+        })
+        .with_children(|parent| {
+            for (i, item) in items.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            padding: Rect::all(Val::Px(6.0)),
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .insert(ContextMenuButton(i))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                item.label.clone(),
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: MENU_FONT_SIZE,
+                                    color: if item.action
+                                        == ContextMenuAction::DeletePermanently
+                                    {
+                                        theme.danger_text
+                                    } else {
+                                        theme.panel_text
+                                    },
+                                },
+                                default(),
+                            ),
+                            ..default()
+                        });
+                    });
+            }
+        });
+}
+
+fn px_if(condition: bool, px: f32) -> Val {
+    if condition {
+        Val::Px(px)
+    } else {
+        Val::Undefined
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_context_menu_interactions(
+    mut commands: Commands,
+    mut button_query: Query<
+        (&Interaction, &ContextMenuButton, &Parent, &mut UiColor),
+        Changed<Interaction>,
+    >,
+    menu_query: Query<&ContextMenu>,
+    items: Res<ContextMenuItems>,
+    theme: Res<Theme>,
+    mut rescan_requests: EventWriter<FsRescanRequest>,
+    mut delete_requests: EventWriter<DeleteRequest>,
+) {
+    for (interaction, button, menu_entity, mut color) in button_query.iter_mut() {
+        *color = menu_item_color(&theme, *interaction);
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let (menu, item) = match (menu_query.get(menu_entity.0), items.get(button.0)) {
+            (Ok(menu), Some(item)) => (menu, item),
+            _ => continue,
+        };
+        debug!(label = %item.label, path = %menu.path.display(), "context menu action");
+        let result = match &item.action {
+            ContextMenuAction::CopyPath => copy_to_clipboard(menu.path.as_os_str()),
+            ContextMenuAction::Rescan => {
+                rescan_requests.send(FsRescanRequest(menu.node_id));
+                Ok(())
+            }
+            ContextMenuAction::Trash => {
+                delete_requests.send(DeleteRequest {
+                    node_id: menu.node_id,
+                    mode: DeleteMode::Trash,
+                });
+                Ok(())
+            }
+            ContextMenuAction::DeletePermanently => {
+                delete_requests.send(DeleteRequest {
+                    node_id: menu.node_id,
+                    mode: DeleteMode::Permanent,
+                });
+                Ok(())
+            }
+            ContextMenuAction::Command(template) => {
+                spawn_shell_command(&expand_command(template, menu))
+            }
+        };
+        if let Err(err) = result {
+            warn!(label = %item.label, %err, "context menu action failed");
+        }
+        commands.entity(menu_entity.0).despawn_recursive();
+    }
+}
+
+/// Replaces the placeholders of a [`ContextMenuAction::Command`] template. Paths are
+/// quoted byte for byte, so that commands run on the paths even when they aren't UTF-8.
+fn expand_command(template: &str, menu: &ContextMenu) -> OsString {
+    let dir = if menu.is_dir {
+        menu.path.as_path()
+    } else {
+        menu.path.parent().unwrap_or(&menu.path)
+    };
+    let name = menu.path.file_name().unwrap_or(menu.path.as_os_str());
+    let placeholders = [
+        ("{path}", menu.path.as_os_str()),
+        ("{dir}", dir.as_os_str()),
+        ("{name}", name),
+    ];
+
+    let mut command = Vec::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        command.extend_from_slice(&rest.as_bytes()[..start]);
+        rest = &rest[start..];
+        match placeholders
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                command.extend_from_slice(&shell_quote(&os_str_bytes(value)));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                command.push(b'{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    command.extend_from_slice(rest.as_bytes());
+    os_string_from_bytes(command)
+}
+
+/// Runs `command` with bash, which reads the `$'...'` quotes of paths with control
+/// characters in them
+fn spawn_shell_command(command: &OsStr) -> io::Result<()> {
+    let child = Command::new("bash")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .spawn()?;
+    reap_in_background(child);
+    Ok(())
+}
+
+/// Uses `wl-copy` under Wayland, and `xclip` otherwise
+fn copy_to_clipboard(text: &OsStr) -> io::Result<()> {
+    let mut command = if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Command::new("wl-copy")
+    } else {
+        let mut command = Command::new("xclip");
+        command.args(["-selection", "clipboard"]);
+        command
+    };
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    // Dropping stdin closes it, which lets the clipboard tool take over the text
+    let written = child
+        .stdin
+        .take()
+        .map_or(Ok(()), |mut stdin| stdin.write_all(&os_str_bytes(text)));
+    reap_in_background(child);
+    written
+}
+
+/// Clipboard tools and launched programs can outlive the menu by a long way, so they're
+/// waited on away from the app
+fn reap_in_background(mut child: Child) {
+    thread::spawn(move || child.wait());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_name_paths_that_arent_utf8_byte_for_byte() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let menu = ContextMenu {
+            node_id: FsNodeId::ROOT,
+            path: OsStr::from_bytes(b"/tmp/it's \xff").into(),
+            is_dir: false,
+        };
+        let command = expand_command("mv {path} {dir}/{name}.old {other}", &menu);
+        assert_eq!(
+            command.as_bytes(),
+            b"mv '/tmp/it'\\''s \xff' '/tmp'/'it'\\''s \xff'.old {other}"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use super::{
+    panel_plugin::{button_color, panel_bundle, PanelBackground, PanelFont},
+    tree_view_focus_plugin::{TreeSearch, TreeViewFocus},
+    tree_view_zoom_plugin::{TreeViewZoom, ZoomCommand},
+};
+use crate::{
+    fs::{
+        delete_from_disk, human_readable_size, DeleteMode, FsEntityComponent,
+        FsEntityMap, FsNodeDeleted, FsNodeId, FsNodeKind, FsRescanRequest, FsTree,
+    },
+    theme::Theme,
+};
+use bevy::prelude::*;
+use crossbeam_channel::{bounded, Receiver, TryRecvError};
+use std::{io, path::PathBuf, thread, time::Duration};
+
+const DIALOG_FONT_SIZE: f32 = 14.0;
+/// How long the message about a failed deletion stays up
+const FAILURE_MESSAGE_DURATION: Duration = Duration::from_secs(8);
+const BACKDROP_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.4);
+
+/// Asks for a node to be deleted from disk, which happens once the user confirms it
+pub struct DeleteRequest {
+    pub node_id: FsNodeId,
+    pub mode: DeleteMode,
+}
+
+/// The open dialog, and what it would delete
+#[derive(Component)]
+struct DeleteDialog {
+    node_id: FsNodeId,
+    path: PathBuf,
+    mode: DeleteMode,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum DeleteDialogButton {
+    Cancel,
+    Confirm(DeleteMode),
+}
+
+impl DeleteDialogButton {
+    fn color(&self, theme: &Theme, interaction: Interaction) -> UiColor {
+        match self {
+            DeleteDialogButton::Confirm(DeleteMode::Permanent) => match interaction {
+                Interaction::Clicked => theme.danger_button_pressed.into(),
+                Interaction::Hovered => theme.danger_button_hovered.into(),
+                Interaction::None => theme.danger_button.into(),
+            },
+            _ => button_color(theme, interaction),
+        }
+    }
+}
+
+/// A confirmed deletion, running on a background thread
+struct PendingDeletion {
+    node_id: FsNodeId,
+    path: PathBuf,
+    mode: DeleteMode,
+    receiver: Receiver<io::Result<()>>,
+}
+
+#[derive(Default, Deref, DerefMut)]
+struct PendingDeletions(Vec<PendingDeletion>);
+
+/// Reports a deletion that failed, in part or in full
+struct DeleteFailed {
+    path: PathBuf,
+    mode: DeleteMode,
+    err: io::Error,
+}
+
+/// Shows why the last deletions failed, until its timer runs out
+#[derive(Component)]
+struct DeleteFailureMessage(Timer);
+
+pub struct DeleteDialogPlugin;
+impl Plugin for DeleteDialogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DeleteRequest>()
+            .add_event::<DeleteFailed>()
+            .init_resource::<PendingDeletions>()
+            .add_system(request_delete_on_keys)
+            .add_system(open_delete_dialog.after(request_delete_on_keys))
+            .add_system(handle_delete_dialog_interactions)
+            .add_system(finish_deletions)
+            .add_system(show_delete_failures.after(finish_deletions));
+    }
+}
+
+/// Delete moves the focused node to the trash, and Shift+Delete deletes it permanently
+fn request_delete_on_keys(
+    keys: Res<Input<KeyCode>>,
+    focus: Res<TreeViewFocus>,
+    search: Res<TreeSearch>,
+    fs_entity_query: Query<&FsEntityComponent>,
+    mut delete_requests: EventWriter<DeleteRequest>,
+) {
+    if search.is_active() || !keys.just_pressed(KeyCode::Delete) {
+        return;
+    }
+    let fs_entity = match focus.focused().map(|focused| fs_entity_query.get(focused)) {
+        Some(Ok(fs_entity)) => fs_entity,
+        _ => return,
+    };
+    let mode = if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        DeleteMode::Permanent
+    } else {
+        DeleteMode::Trash
+    };
+    delete_requests.send(DeleteRequest {
+        node_id: fs_entity.node_id,
+        mode,
+    });
+}
+
+/// Asks to confirm a [`DeleteRequest`], with what's about to be deleted and its size.
+/// Escape closes the dialog.
+fn open_delete_dialog(
+    mut commands: Commands,
+    mut delete_requests: EventReader<DeleteRequest>,
+    keys: Res<Input<KeyCode>>,
+    fs_tree: Res<FsTree>,
+    theme: Res<Theme>,
+    font: Res<PanelFont>,
+    dialog_query: Query<Entity, With<DeleteDialog>>,
+) {
+    let request = delete_requests.iter().last();
+    if keys.just_pressed(KeyCode::Escape) || request.is_some() {
+        for dialog in dialog_query.iter() {
+            commands.entity(dialog).despawn_recursive();
+        }
+    }
+    let DeleteRequest { node_id, mode } = match request {
+        // The scanned directory itself isn't ours to delete
+        Some(request)
+            if fs_tree.contains(request.node_id) && request.node_id != FsNodeId::ROOT =>
+        {
+            request
+        }
+        _ => return,
+    };
+
+    let path = fs_tree.path(*node_id);
+    let title = match mode {
+        DeleteMode::Trash => "Move to the trash?\n".to_string(),
+        DeleteMode::Permanent => {
+            "Delete permanently? This can't be undone.\n".to_string()
+        }
+    };
+    let size = human_readable_size(fs_tree.aggregate_size(*node_id));
+    let details = match fs_tree.kind(*node_id) {
+        FsNodeKind::Dir => format!(
+            "{}\n{size} in {} files and {} directories",
+            path.display(),
+            fs_tree.descendent_file_count(*node_id),
+            fs_tree.descendent_dir_count(*node_id),
+        ),
+        _ => format!("{}\n{size}", path.display()),
+    };
+
+    let text_style = |color: Color| TextStyle {
+        font: font.clone(),
+        font_size: DIALOG_FONT_SIZE,
+        color,
+    };
+    let spawn_button =
+        |parent: &mut ChildBuilder, button: DeleteDialogButton, label: &str| {
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(6.0)),
+                        margin: Rect {
+                            left: Val::Px(8.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    color: button.color(&theme, Interaction::None),
+                    ..default()
+                })
+                .insert(button)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            label,
+                            text_style(theme.panel_text),
+                            default(),
+                        ),
+                        ..default()
+                    });
+                });
+        };
+
+    // The backdrop covers the window, which keeps clicks from reaching the tree
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: BACKDROP_COLOR.into(),
+            ..default()
+        })
+        .insert(Interaction::default())
+        .insert(DeleteDialog {
+            node_id: *node_id,
+            path,
+            mode: *mode,
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(panel_bundle(
+                    &theme,
+                    Style {
+                        padding: Rect::all(Val::Px(12.0)),
+                        ..default()
+                    },
+                ))
+                .insert(PanelBackground)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![
+                                TextSection {
+                                    value: title,
+                                    style: text_style(match mode {
+                                        DeleteMode::Trash => theme.panel_text,
+                                        DeleteMode::Permanent => theme.danger_text,
+                                    }),
+                                },
+                                TextSection {
+                                    value: details,
+                                    style: text_style(theme.panel_text),
+                                },
+                            ],
+                            ..default()
+                        },
+                        ..default()
+                    });
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                justify_content: JustifyContent::FlexEnd,
+                                margin: Rect {
+                                    top: Val::Px(12.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            spawn_button(parent, DeleteDialogButton::Cancel, "Cancel");
+                            let confirm_label = match mode {
+                                DeleteMode::Trash => "Move to trash",
+                                DeleteMode::Permanent => "Delete permanently",
+                            };
+                            spawn_button(
+                                parent,
+                                DeleteDialogButton::Confirm(*mode),
+                                confirm_label,
+                            );
+                        });
+                });
+        });
+}
+
+fn handle_delete_dialog_interactions(
+    mut commands: Commands,
+    mut button_query: Query<
+        (&Interaction, &DeleteDialogButton, &mut UiColor),
+        Changed<Interaction>,
+    >,
+    dialog_query: Query<(Entity, &DeleteDialog)>,
+    theme: Res<Theme>,
+    mut pending_deletions: ResMut<PendingDeletions>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        *color = button.color(&theme, *interaction);
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        for (dialog_entity, dialog) in dialog_query.iter() {
+            if *button == DeleteDialogButton::Confirm(dialog.mode) {
+                info!(path = %dialog.path.display(), mode = ?dialog.mode, "deleting");
+                let (sender, receiver) = bounded(1);
+                let (path, mode) = (dialog.path.clone(), dialog.mode);
+                thread::spawn(move || sender.send(delete_from_disk(&path, mode)));
+                pending_deletions.push(PendingDeletion {
+                    node_id: dialog.node_id,
+                    path: dialog.path.clone(),
+                    mode: dialog.mode,
+                    receiver,
+                });
+            }
+            commands.entity(dialog_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Removes what was deleted from the tree, zooming out of it first if it's being viewed.
+/// A failed permanent delete may have removed some of what it was deleting, so that's
+/// rescanned instead, once the scan allows it.
+#[allow(clippy::too_many_arguments)]
+fn finish_deletions(
+    mut pending_deletions: ResMut<PendingDeletions>,
+    fs_tree: Res<FsTree>,
+    fs_entity_map: Res<FsEntityMap>,
+    fs_entity_query: Query<&FsEntityComponent>,
+    zoom: Res<TreeViewZoom>,
+    mut zoom_commands: EventWriter<ZoomCommand>,
+    mut deleted_events: EventWriter<FsNodeDeleted>,
+    mut rescan_requests: EventWriter<FsRescanRequest>,
+    mut failed_events: EventWriter<DeleteFailed>,
+) {
+    pending_deletions.retain(|deletion| {
+        let result = match deletion.receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "deletion didn't finish",
+            )),
+        };
+        let node_id = deletion.node_id;
+        if !fs_tree.contains(node_id) {
+            return false;
+        }
+
+        match result {
+            Ok(()) => {
+                info!(path = %deletion.path.display(), "deleted");
+                let layout_node = zoom
+                    .layout_root()
+                    .and_then(|layout_root| fs_entity_query.get(layout_root).ok())
+                    .map(|fs_entity| fs_entity.node_id);
+                let is_viewed = matches!(
+                    layout_node,
+                    Some(layout_node) if layout_node == node_id
+                        || fs_tree.ancestors(layout_node).any(|a| a == node_id)
+                );
+                let parent_entity = fs_tree
+                    .parent(node_id)
+                    .and_then(|parent| fs_entity_map.get(&parent));
+                if let (true, Some(parent_entity)) = (is_viewed, parent_entity) {
+                    zoom_commands.send(ZoomCommand::To(*parent_entity));
+                }
+                deleted_events.send(FsNodeDeleted(node_id));
+            }
+            Err(err) => {
+                warn!(path = %deletion.path.display(), %err, "couldn't delete");
+                if deletion.mode == DeleteMode::Permanent {
+                    rescan_requests.send(FsRescanRequest(node_id));
+                }
+                failed_events.send(DeleteFailed {
+                    path: deletion.path.clone(),
+                    mode: deletion.mode,
+                    err,
+                });
+            }
+        }
+        false
+    });
+}
+
+/// Failed deletions are listed in the bottom right corner, above the selection's status,
+/// replacing the last list. The list goes away on its own, or when clicked.
+fn show_delete_failures(
+    mut commands: Commands,
+    mut failed_events: EventReader<DeleteFailed>,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    font: Res<PanelFont>,
+    mut message_query: Query<(Entity, &mut DeleteFailureMessage, &Interaction)>,
+) {
+    let lines: Vec<String> = failed_events
+        .iter()
+        .map(|DeleteFailed { path, mode, err }| {
+            let verb = match mode {
+                DeleteMode::Trash => "move to the trash",
+                DeleteMode::Permanent => "delete",
+            };
+            format!("Couldn't {verb} {}: {err}", path.display())
+        })
+        .collect();
+
+    for (entity, mut message, interaction) in message_query.iter_mut() {
+        if !lines.is_empty()
+            || *interaction == Interaction::Clicked
+            || message.0.tick(time.delta()).finished()
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn_bundle(panel_bundle(
+            &theme,
+            Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(56.0),
+                    right: Val::Px(16.0),
+                    ..default()
+                },
+                padding: Rect::all(Val::Px(8.0)),
+                ..default()
+            },
+        ))
+        .insert(PanelBackground)
+        .insert(Interaction::default())
+        .insert(DeleteFailureMessage(Timer::new(
+            FAILURE_MESSAGE_DURATION,
+            false,
+        )))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    lines.join("\n"),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: DIALOG_FONT_SIZE,
+                        color: theme.danger_text,
+                    },
+                    default(),
+                ),
+                ..default()
+            });
+        });
+}
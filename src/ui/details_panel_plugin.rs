@@ -0,0 +1,241 @@
+use super::{
+    mouse_interactions_plugin::Hoverable,
+    panel_plugin::{panel_bundle, PanelBackground, PanelFont, PanelText},
+    small_items_group::SmallItemsGroup,
+    tree_view_focus_plugin::TreeViewFocus,
+};
+use crate::{
+    fs::{
+        format_mtime, format_permissions, human_readable_size, owner_name, percentage,
+        FsAggregateSize, FsEntityComponent, FsNodeKind, FsTree,
+    },
+    theme::Theme,
+};
+use bevy::prelude::*;
+use std::fs;
+
+const PANEL_FONT_SIZE: f32 = 14.0;
+
+#[derive(Component)]
+struct DetailsPanel;
+
+#[derive(Component)]
+struct DetailsPanelText;
+
+pub struct DetailsPanelPlugin;
+impl Plugin for DetailsPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(create_details_panel)
+            .add_system(update_details_panel);
+    }
+}
+
+/// The panel sits in the top right corner, across from the diagnostics text
+fn create_details_panel(mut commands: Commands, theme: Res<Theme>, font: Res<PanelFont>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            visibility: Visibility { is_visible: false },
+            ..panel_bundle(
+                &theme,
+                Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        top: Val::Px(16.0),
+                        right: Val::Px(16.0),
+                        ..default()
+                    },
+                    padding: Rect::all(Val::Px(8.0)),
+                    ..default()
+                },
+            )
+        })
+        .insert(PanelBackground)
+        .insert(DetailsPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: PANEL_FONT_SIZE,
+                            color: theme.panel_text,
+                        },
+                        default(),
+                    ),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(DetailsPanelText)
+                .insert(PanelText);
+        });
+}
+
+/// Describes the hovered node or group of small items, or the focused node when nothing
+/// is hovered
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_details_panel(
+    focus: Res<TreeViewFocus>,
+    fs_tree: Res<FsTree>,
+    hovered_query: Query<
+        (Entity, &Hoverable),
+        Or<(With<FsEntityComponent>, With<SmallItemsGroup>)>,
+    >,
+    fs_entity_query: Query<(
+        &FsEntityComponent,
+        &FsAggregateSize,
+        ChangeTrackers<FsAggregateSize>,
+    )>,
+    group_query: Query<(&SmallItemsGroup, ChangeTrackers<SmallItemsGroup>)>,
+    mut panel_query: Query<
+        &mut Visibility,
+        (With<DetailsPanel>, Without<DetailsPanelText>),
+    >,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<DetailsPanelText>>,
+    mut last_described: Local<Option<Entity>>,
+) {
+    let described = hovered_query
+        .iter()
+        .find(|(_, hoverable)| hoverable.is_hovered)
+        .map(|(entity, _)| entity)
+        .or_else(|| focus.focused())
+        .filter(|entity| {
+            fs_entity_query.contains(*entity) || group_query.contains(*entity)
+        });
+
+    let is_described_changed = matches!(
+        described.and_then(|entity| fs_entity_query.get(entity).ok()),
+        Some((_, _, size_tracker)) if size_tracker.is_changed()
+    ) || matches!(
+        described.and_then(|entity| group_query.get(entity).ok()),
+        Some((_, group_tracker)) if group_tracker.is_changed()
+    );
+    if described == *last_described && !is_described_changed {
+        return;
+    }
+    *last_described = described;
+
+    let lines = match described {
+        Some(entity) => match fs_entity_query.get(entity) {
+            Ok((fs_entity, fs_size, _)) => describe_node(&fs_tree, fs_entity, fs_size),
+            Err(_) => {
+                let (group, _) = group_query.get(entity).unwrap();
+                describe_small_items(&fs_tree, group, &fs_entity_query)
+            }
+        },
+        None => vec![],
+    };
+
+    for mut vis in panel_query.iter_mut() {
+        vis.is_visible = !lines.is_empty();
+    }
+    for (mut text, mut vis) in text_query.iter_mut() {
+        vis.is_visible = !lines.is_empty();
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn describe_node(
+    fs_tree: &FsTree,
+    fs_entity: &FsEntityComponent,
+    fs_size: &FsAggregateSize,
+) -> Vec<String> {
+    let node_id = fs_entity.node_id;
+    let metadata = fs_tree.metadata(node_id);
+    let path = fs_tree.path(node_id);
+    let aggregate_size = fs_size.size_in_bytes;
+    let parent_size = fs_tree
+        .parent(node_id)
+        .map_or(aggregate_size, |parent| fs_tree.aggregate_size(parent));
+    let root_size = fs_tree.aggregate_size(fs_tree.root().unwrap_or(node_id));
+
+    let mut lines = vec![
+        path.display().to_string(),
+        String::new(),
+        format!(
+            "size       {} ({} bytes)",
+            human_readable_size(aggregate_size),
+            aggregate_size
+        ),
+    ];
+    // A directory's own apparent size isn't counted, so only files have one to show
+    if !fs_entity.is_dir() {
+        lines.push(format!(
+            "apparent   {}",
+            human_readable_size(fs_tree.size_in_bytes(node_id))
+        ));
+    }
+    lines.push(format!(
+        "of parent  {}    of root  {}",
+        percentage(aggregate_size, parent_size),
+        percentage(aggregate_size, root_size)
+    ));
+    if fs_entity.is_dir() {
+        lines.push(format!(
+            "contains   {} files, {} directories",
+            fs_tree.descendent_file_count(node_id),
+            fs_tree.descendent_dir_count(node_id)
+        ));
+    }
+    lines.push(format!("modified   {}", format_mtime(metadata)));
+    lines.push(format!("owner      {}", owner_name(metadata.uid)));
+    lines.push(format!("mode       {}", format_permissions(metadata)));
+    if metadata.kind == FsNodeKind::Symlink {
+        let target = fs::read_link(&path)
+            .map(|target| target.display().to_string())
+            .unwrap_or_else(|e| format!("unreadable ({e})"));
+        lines.push(format!("target     {target}"));
+    }
+    lines
+}
+
+fn describe_small_items(
+    fs_tree: &FsTree,
+    group: &SmallItemsGroup,
+    fs_entity_query: &Query<(
+        &FsEntityComponent,
+        &FsAggregateSize,
+        ChangeTrackers<FsAggregateSize>,
+    )>,
+) -> Vec<String> {
+    let parent_node_id = match fs_entity_query.get(group.parent) {
+        Ok((parent_fs, _, _)) => parent_fs.node_id,
+        Err(_) => return vec![],
+    };
+    let parent_size = fs_tree.aggregate_size(parent_node_id);
+    let root_size = fs_tree.aggregate_size(fs_tree.root().unwrap_or(parent_node_id));
+    let largest = group
+        .items
+        .iter()
+        .filter_map(|item| fs_entity_query.get(*item).ok())
+        .max_by_key(|(_, fs_size, _)| fs_size.size_in_bytes);
+
+    let mut lines = vec![
+        format!(
+            "{} in {}",
+            group.label(),
+            fs_tree.path(parent_node_id).display()
+        ),
+        String::new(),
+        format!(
+            "size       {} ({} bytes)",
+            human_readable_size(group.size_in_bytes),
+            group.size_in_bytes
+        ),
+        format!(
+            "of parent  {}    of root  {}",
+            percentage(group.size_in_bytes, parent_size),
+            percentage(group.size_in_bytes, root_size)
+        ),
+    ];
+    if let Some((largest_fs, largest_size, _)) = largest {
+        lines.push(format!(
+            "largest    {} ({})",
+            fs_tree.name(largest_fs.node_id).to_string_lossy(),
+            human_readable_size(largest_size.size_in_bytes)
+        ));
+    }
+    lines.push(String::new());
+    lines.push("click to expand".into());
+    lines
+}
@@ -1,8 +1,40 @@
+pub mod block_labels_plugin;
+pub mod color_scheme;
+pub mod context_menu_plugin;
+pub mod delete_dialog_plugin;
+pub mod details_panel_plugin;
 pub mod mouse_interactions_plugin;
+pub mod panel_plugin;
+pub mod small_items_group;
+pub mod tree_layout;
+pub mod tree_navigation;
+pub mod tree_view_animation_plugin;
+pub mod tree_view_focus_plugin;
 pub mod tree_view_plugin;
+pub mod tree_view_selection_plugin;
+mod tree_view_sunburst;
+pub mod tree_view_table_plugin;
 #[allow(dead_code)]
 pub mod tree_view_theme;
+mod tree_view_treemap;
+pub mod tree_view_viewport_plugin;
+pub mod tree_view_zoom_plugin;
 
+pub use block_labels_plugin::*;
+pub use color_scheme::*;
+pub use context_menu_plugin::*;
+pub use delete_dialog_plugin::*;
+pub use details_panel_plugin::*;
 pub use mouse_interactions_plugin::*;
+pub use panel_plugin::*;
+pub use small_items_group::*;
+pub use tree_layout::*;
+pub use tree_navigation::*;
+pub use tree_view_animation_plugin::*;
+pub use tree_view_focus_plugin::*;
 pub use tree_view_plugin::*;
+pub use tree_view_selection_plugin::*;
+pub use tree_view_table_plugin::*;
 pub use tree_view_theme::*;
+pub use tree_view_viewport_plugin::*;
+pub use tree_view_zoom_plugin::*;
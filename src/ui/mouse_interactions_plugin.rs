@@ -1,4 +1,5 @@
 use bevy::{prelude::*, render::camera::RenderTarget};
+use std::f32::consts::TAU;
 use tracing::debug;
 use valuable::Valuable;
 
@@ -8,8 +9,105 @@ pub struct Hoverable {
     pub debug_tag: String,
 }
 
+/// The region of a [`Hoverable`] that responds to the cursor. Hoverables without one are
+/// treated as [`HoverShape::Rect`].
+#[derive(Component, Clone, Copy, Debug)]
+pub enum HoverShape {
+    /// The rectangle from the entity's global translation to translation + scale
+    Rect,
+    /// A ring segment, in world coordinates. Angles are in radians, counterclockwise from
+    /// the positive x axis, and `end_angle` may exceed `TAU`.
+    AnnularSector {
+        center: Vec2,
+        inner_radius: f32,
+        outer_radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    },
+}
+
+impl Default for HoverShape {
+    fn default() -> Self {
+        HoverShape::Rect
+    }
+}
+
+impl HoverShape {
+    pub fn contains(&self, transform: &GlobalTransform, point: Vec2) -> bool {
+        match *self {
+            HoverShape::Rect => {
+                let min = transform.translation.truncate();
+                let max = min + transform.scale.truncate();
+                min.x <= point.x
+                    && min.y <= point.y
+                    && point.x <= max.x
+                    && point.y <= max.y
+            }
+            HoverShape::AnnularSector {
+                center,
+                inner_radius,
+                outer_radius,
+                start_angle,
+                end_angle,
+            } => {
+                let offset = point - center;
+                let radius = offset.length();
+                let angle_from_start =
+                    (offset.y.atan2(offset.x) - start_angle).rem_euclid(TAU);
+                inner_radius <= radius
+                    && radius <= outer_radius
+                    && angle_from_start <= end_angle - start_angle
+            }
+        }
+    }
+}
+
+/// How far the cursor has to move with the left button held before it's a drag, rather
+/// than a click
+const DRAG_THRESHOLD: f32 = 4.0;
+
 #[derive(Deref, DerefMut)]
-struct MouseCursorWorldPosition(Option<Vec2>);
+pub(crate) struct MouseCursorWorldPosition(Option<Vec2>);
+
+/// Sent when a mouse button is pressed over a hovered [`Hoverable`]. The left button is
+/// reported when it's released instead, and only if the cursor wasn't dragged in between.
+/// Presses that land on UI nodes (buttons and the like) are not reported.
+pub struct HoverableClicked {
+    pub entity: Entity,
+    pub button: MouseButton,
+}
+
+/// The cursor being dragged with the left button held. Presses that start on UI nodes
+/// are neither drags nor clicks.
+#[derive(Default)]
+pub struct CursorDrag {
+    pressed_at: Option<Vec2>,
+    last_position: Vec2,
+    is_dragging: bool,
+    /// How far the cursor was dragged this frame, in world coordinates
+    pub delta: Vec2,
+}
+
+impl CursorDrag {
+    /// Whether the last left press turned into a drag
+    pub fn is_dragging(&self) -> bool {
+        self.is_dragging
+    }
+
+    /// Whether releasing the last left press clicks whatever is under the cursor
+    fn is_click(&self) -> bool {
+        self.pressed_at.is_some() && !self.is_dragging
+    }
+}
+
+/// A hoverable that the UI under the cursor stands for, such as the block of a table row.
+/// It's hovered as if the cursor were on it, although the UI covers the hoverables.
+#[derive(Default, Deref, DerefMut)]
+pub struct UiHoverTarget(pub Option<Entity>);
+
+/// The system that marks the hovered [`Hoverable`], for ordering around it
+#[derive(SystemLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MarkHoverables;
 
 /// Marks the camera that should be used when mapping cursor position into world coordinates
 #[derive(Component)]
@@ -20,7 +118,16 @@ impl Plugin for MouseInteractionsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MouseCursorWorldPosition(Vec2::ZERO.into()))
             .add_system_to_stage(CoreStage::PreUpdate, update_cursor_position)
-            .add_system(mark_hoverables);
+            .init_resource::<CursorDrag>()
+            .init_resource::<UiHoverTarget>()
+            .add_event::<HoverableClicked>()
+            .add_system(mark_hoverables.label(MarkHoverables))
+            .add_system(track_cursor_drag)
+            .add_system(
+                emit_hoverable_clicks
+                    .after(mark_hoverables)
+                    .after(track_cursor_drag),
+            );
     }
 }
 
@@ -73,11 +180,35 @@ struct LastHovered(Option<Entity>);
 
 fn mark_hoverables(
     cursor_world_pos: Res<MouseCursorWorldPosition>,
+    ui_hover_target: Res<UiHoverTarget>,
     mut hoverables_query: Query<(Entity, &mut Hoverable)>,
-    mut visuals_query: Query<(Entity, &GlobalTransform, &Visibility), With<Hoverable>>,
+    mut visuals_query: Query<
+        (Entity, &GlobalTransform, &Visibility, Option<&HoverShape>),
+        With<Hoverable>,
+    >,
+    ui_interactions_query: Query<&Interaction>,
     mut last_hovered: Local<LastHovered>,
 ) {
-    if cursor_world_pos.0 == None {
+    // The UI is drawn over the hoverables, so nothing beneath it is hovered, except for
+    // what the UI stands for
+    let is_over_ui = ui_interactions_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if is_over_ui {
+        let target = ui_hover_target.filter(|entity| hoverables_query.contains(*entity));
+        if last_hovered.0 != target {
+            if let Some(entity) = last_hovered.0 {
+                if let Ok((_, mut hoverable)) = hoverables_query.get_mut(entity) {
+                    hoverable.is_hovered = false;
+                }
+            }
+            if let Some(entity) = target {
+                let (_, mut hoverable) = hoverables_query.get_mut(entity).unwrap();
+                hoverable.is_hovered = true;
+            }
+            last_hovered.0 = target;
+        }
+    } else if cursor_world_pos.0 == None {
         if cursor_world_pos.is_changed() {
             debug!("no cursor position — removing any existing hover states");
             if let Some(entity) = last_hovered.0 {
@@ -91,24 +222,26 @@ fn mark_hoverables(
         let _enter_guard = span.enter();
 
         let cursor_world_pos = cursor_world_pos.unwrap();
-        let mut z_ordered_hoverables: Vec<(Entity, &GlobalTransform, &Visibility)> =
-            visuals_query
-                .iter_mut()
-                // Don't include hidden sprites
-                .filter(|(_, _, vis)| vis.is_visible)
-                .collect();
-        z_ordered_hoverables.sort_by(|(_, t_a, _), (_, t_b, _)| {
+        let mut z_ordered_hoverables: Vec<(
+            Entity,
+            &GlobalTransform,
+            &Visibility,
+            Option<&HoverShape>,
+        )> = visuals_query
+            .iter_mut()
+            // Don't include hidden sprites
+            .filter(|(_, _, vis, _)| vis.is_visible)
+            .collect();
+        z_ordered_hoverables.sort_by(|(_, t_a, _, _), (_, t_b, _, _)| {
             t_b.translation.z.total_cmp(&t_a.translation.z)
         });
 
         let has_last_hovered = last_hovered.0 != None;
-        for (entity, transform, _) in z_ordered_hoverables {
-            let min = transform.translation.truncate();
-            let max = min + transform.scale.truncate();
-            let new_is_hovered = min.x <= cursor_world_pos.x
-                && min.y <= cursor_world_pos.y
-                && cursor_world_pos.x <= max.x
-                && cursor_world_pos.y <= max.y;
+        for (entity, transform, _, maybe_shape) in z_ordered_hoverables {
+            let new_is_hovered = maybe_shape
+                .copied()
+                .unwrap_or_default()
+                .contains(transform, cursor_world_pos);
 
             let is_hovered_changed = if let Ok(hoverable) =
                 hoverables_query.get_component::<Hoverable>(entity)
@@ -144,6 +277,86 @@ fn mark_hoverables(
                     break;
                 }
             }
+
+            // Nested hoverables (like a treemap's) are left alone beneath the topmost one
+            if new_is_hovered {
+                break;
+            }
         }
     }
 }
+
+fn track_cursor_drag(
+    mouse_buttons: Res<Input<MouseButton>>,
+    cursor_world_pos: Res<MouseCursorWorldPosition>,
+    ui_interactions_query: Query<&Interaction>,
+    mut drag: ResMut<CursorDrag>,
+) {
+    if drag.delta != Vec2::ZERO {
+        drag.delta = Vec2::ZERO;
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        let is_on_ui = ui_interactions_query
+            .iter()
+            .any(|interaction| *interaction != Interaction::None);
+        drag.pressed_at = cursor_world_pos.0.filter(|_| !is_on_ui);
+        drag.last_position = cursor_world_pos.unwrap_or_default();
+        drag.is_dragging = false;
+        return;
+    }
+    if !mouse_buttons.pressed(MouseButton::Left) {
+        return;
+    }
+
+    let (pressed_at, position) = match (drag.pressed_at, cursor_world_pos.0) {
+        (Some(pressed_at), Some(position)) => (pressed_at, position),
+        _ => return,
+    };
+    if !drag.is_dragging && pressed_at.distance(position) >= DRAG_THRESHOLD {
+        debug!("drag started");
+        drag.is_dragging = true;
+    }
+    if drag.is_dragging && position != drag.last_position {
+        drag.delta = position - drag.last_position;
+        drag.last_position = position;
+    }
+}
+
+fn emit_hoverable_clicks(
+    mouse_buttons: Res<Input<MouseButton>>,
+    drag: Res<CursorDrag>,
+    hoverables_query: Query<(Entity, &Hoverable, &Visibility)>,
+    ui_interactions_query: Query<&Interaction>,
+    mut clicked_events: EventWriter<HoverableClicked>,
+) {
+    // The UI is drawn over the hoverables, so it gets first dibs
+    if ui_interactions_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    let hovered_entity = hoverables_query
+        .iter()
+        .find(|(_, hoverable, vis)| hoverable.is_hovered && vis.is_visible)
+        .map(|(entity, _, _)| entity);
+    let hovered_entity = match hovered_entity {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let left_click = (mouse_buttons.just_released(MouseButton::Left) && drag.is_click())
+        .then_some(&MouseButton::Left);
+    let other_presses = mouse_buttons
+        .get_just_pressed()
+        .filter(|button| **button != MouseButton::Left);
+    for button in left_click.into_iter().chain(other_presses) {
+        debug!(?button, "hoverable clicked");
+        clicked_events.send(HoverableClicked {
+            entity: hovered_entity,
+            button: *button,
+        });
+    }
+}
@@ -0,0 +1,79 @@
+use crate::theme::Theme;
+use bevy::prelude::*;
+
+/// The font of the text in panels, menus and dialogs
+#[derive(Deref)]
+pub struct PanelFont(pub Handle<Font>);
+
+/// Marks the background of a panel, which follows [`Theme::panel`]
+#[derive(Component)]
+pub struct PanelBackground;
+
+/// Marks panel text that follows [`Theme::panel_text`]. Panels whose text has other
+/// colors recolor it themselves.
+#[derive(Component)]
+pub struct PanelText;
+
+/// A panel that lays out its children top down, colored by the theme. Panels are marked
+/// with [`PanelBackground`] to follow theme changes, and those that take clicks add an
+/// [`Interaction`], which keeps clicks on their padding from reaching the blocks below.
+pub fn panel_bundle(theme: &Theme, style: Style) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            // The UI's y axis points up, so columns are reversed to read top down
+            flex_direction: FlexDirection::ColumnReverse,
+            ..style
+        },
+        color: theme.panel.into(),
+        ..default()
+    }
+}
+
+/// The color of a button for its interaction
+pub fn button_color(theme: &Theme, interaction: Interaction) -> UiColor {
+    match interaction {
+        Interaction::Clicked => theme.panel_button_pressed.into(),
+        Interaction::Hovered => theme.panel_button_hovered.into(),
+        Interaction::None => theme.panel_button.into(),
+    }
+}
+
+/// The color of a menu item, which only stands out from the menu while the cursor is on it
+pub fn menu_item_color(theme: &Theme, interaction: Interaction) -> UiColor {
+    match interaction {
+        Interaction::Clicked => theme.panel_button_hovered.into(),
+        Interaction::Hovered => theme.panel_button.into(),
+        Interaction::None => Color::NONE.into(),
+    }
+}
+
+pub struct PanelPlugin;
+impl Plugin for PanelPlugin {
+    fn build(&self, app: &mut App) {
+        // Panels are created by startup systems, which need the font
+        app.add_startup_system_to_stage(StartupStage::PreStartup, load_panel_font)
+            .add_system(apply_theme_to_panels);
+    }
+}
+
+fn load_panel_font(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PanelFont(asset_server.load("fonts/FiraMono-Medium.ttf")));
+}
+
+fn apply_theme_to_panels(
+    theme: Res<Theme>,
+    mut background_query: Query<&mut UiColor, With<PanelBackground>>,
+    mut text_query: Query<&mut Text, With<PanelText>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for mut color in background_query.iter_mut() {
+        *color = theme.panel.into();
+    }
+    for mut text in text_query.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme.panel_text;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use super::{
+    mouse_interactions_plugin::{HoverShape, Hoverable},
+    tree_layout::{HiddenChildren, LayoutRect},
+    tree_view_sunburst::{Sector, SunburstSector},
+};
+use crate::theme::Theme;
+use bevy::{prelude::*, sprite::Anchor, utils::HashMap};
+
+/// A block standing in for the children of `parent` that are too small to draw on their
+/// own. Group entities aren't part of the fs hierarchy, and are reused as groups come
+/// and go.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct SmallItemsGroup {
+    pub parent: Entity,
+    /// The items that are materialized. There may be more that are too small for that.
+    pub items: Vec<Entity>,
+    pub count: usize,
+    pub size_in_bytes: u64,
+}
+
+impl SmallItemsGroup {
+    pub fn label(&self) -> String {
+        format!("{} small items", self.count)
+    }
+}
+
+/// Where a group is drawn: as a block in the icicle, or as a sector of the sunburst
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum SmallItemsGroupShape {
+    Rect(LayoutRect),
+    Sector(Sector),
+}
+
+pub(crate) struct PlacedSmallItemsGroup {
+    pub hidden: HiddenChildren<Entity>,
+    pub shape: SmallItemsGroupShape,
+}
+
+/// The groups placed by the last layout
+#[derive(Default)]
+pub(crate) struct SmallItemsGroups(pub Vec<PlacedSmallItemsGroup>);
+
+pub(crate) fn sync_small_items_groups(
+    mut commands: Commands,
+    groups: Res<SmallItemsGroups>,
+    theme: Res<Theme>,
+    mut group_query: Query<(
+        Entity,
+        &mut SmallItemsGroup,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+        &mut SunburstSector,
+        &mut HoverShape,
+    )>,
+) {
+    if !groups.is_changed() && !theme.is_changed() {
+        return;
+    }
+
+    let wanted_parents: Vec<Entity> =
+        groups.0.iter().map(|placed| placed.hidden.parent).collect();
+    let mut entities_by_parent: HashMap<Entity, Entity> = HashMap::default();
+    let mut spare_entities: Vec<Entity> = vec![];
+    for (entity, group, ..) in group_query.iter() {
+        if wanted_parents.contains(&group.parent) {
+            entities_by_parent.insert(group.parent, entity);
+        } else {
+            spare_entities.push(entity);
+        }
+    }
+
+    for PlacedSmallItemsGroup { hidden, shape } in groups.0.iter() {
+        let group = SmallItemsGroup {
+            parent: hidden.parent,
+            items: hidden.children.clone(),
+            count: hidden.count,
+            size_in_bytes: hidden.size,
+        };
+        // Groups are top-level, so their transforms are in world coordinates. Sunburst
+        // groups are drawn by a mesh, like the sectors of fs entities, so their sprites
+        // are collapsed.
+        let (transform, sunburst_sector, hover_shape) = match shape {
+            SmallItemsGroupShape::Rect(rect) => (
+                Transform {
+                    translation: Vec3::new(rect.x, rect.y, 0.0),
+                    scale: Vec3::new(rect.w, rect.h, 1.0),
+                    ..default()
+                },
+                SunburstSector(None),
+                HoverShape::Rect,
+            ),
+            SmallItemsGroupShape::Sector(sector) => (
+                Transform::from_scale(Vec3::new(0.0, 1.0, 1.0)),
+                SunburstSector(Some(*sector)),
+                sector.hover_shape(),
+            ),
+        };
+
+        let reused = entities_by_parent
+            .get(&hidden.parent)
+            .copied()
+            .or_else(|| spare_entities.pop());
+        match reused {
+            Some(entity) => {
+                let (
+                    _,
+                    mut existing_group,
+                    mut existing_transform,
+                    mut sprite,
+                    mut vis,
+                    mut existing_sunburst_sector,
+                    mut existing_hover_shape,
+                ) = group_query.get_mut(entity).unwrap();
+                if *existing_group != group {
+                    *existing_group = group;
+                }
+                *existing_transform = transform;
+                sprite.color = theme.small_items;
+                vis.is_visible = true;
+                if *existing_sunburst_sector != sunburst_sector {
+                    *existing_sunburst_sector = sunburst_sector;
+                    *existing_hover_shape = hover_shape;
+                }
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: theme.small_items,
+                            anchor: Anchor::BottomLeft,
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    })
+                    .insert(Hoverable {
+                        debug_tag: group.label(),
+                        ..default()
+                    })
+                    .insert(hover_shape)
+                    .insert(sunburst_sector)
+                    .insert(group);
+            }
+        }
+    }
+
+    for entity in spare_entities {
+        let (_, _, _, _, mut vis, _, _) = group_query.get_mut(entity).unwrap();
+        if vis.is_visible {
+            vis.is_visible = false;
+        }
+    }
+}
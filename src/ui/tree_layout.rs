@@ -0,0 +1,676 @@
+use super::tree_view_theme::DescendentColorRange;
+use crate::fs::{FsNodeId, FsTree};
+use bevy::prelude::*;
+use grouping_by::GroupingBy;
+use std::hash::Hash;
+
+/// The tree being laid out. This is the fs tree as far as it's been materialized, but
+/// layout only needs ids, sizes and children, so it can run without a `World`.
+pub trait LayoutTree {
+    type Id: Copy + Eq + Hash;
+
+    fn size(&self, id: Self::Id) -> u64;
+    /// The children of `id`, in the order they're laid out
+    fn children(&self, id: Self::Id) -> Vec<Self::Id>;
+    /// The number of children `id` has, including any that are too small to have been
+    /// materialized, and so aren't among its [`LayoutTree::children`]
+    fn child_count(&self, id: Self::Id) -> usize {
+        self.children(id).len()
+    }
+}
+
+/// The whole fs tree, for front ends that have all of it at hand, like the terminal's
+impl LayoutTree for FsTree {
+    type Id = FsNodeId;
+
+    fn size(&self, id: FsNodeId) -> u64 {
+        self.aggregate_size(id)
+    }
+
+    fn children(&self, id: FsNodeId) -> Vec<FsNodeId> {
+        FsTree::children(self, id).collect()
+    }
+}
+
+/// Narrows the root of a tree down to some of its children. The root's size becomes the
+/// size of those children, so that they fill the whole layout.
+pub struct RootChildrenSubset<'a, T: LayoutTree> {
+    pub tree: &'a T,
+    pub root: T::Id,
+    pub children: &'a [T::Id],
+}
+
+impl<T: LayoutTree> LayoutTree for RootChildrenSubset<'_, T> {
+    type Id = T::Id;
+
+    fn size(&self, id: Self::Id) -> u64 {
+        if id == self.root {
+            self.children
+                .iter()
+                .map(|child| self.tree.size(*child))
+                .sum()
+        } else {
+            self.tree.size(id)
+        }
+    }
+
+    fn children(&self, id: Self::Id) -> Vec<Self::Id> {
+        if id == self.root {
+            self.children.to_vec()
+        } else {
+            self.tree.children(id)
+        }
+    }
+
+    fn child_count(&self, id: Self::Id) -> usize {
+        if id == self.root {
+            self.children.len()
+        } else {
+            self.tree.child_count(id)
+        }
+    }
+}
+
+/// A rectangle in screen units, from its bottom left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl LayoutRect {
+    /// The rect covered by a unit sprite, anchored at its bottom left, with `transform`
+    pub fn from_transform(transform: &Transform) -> Self {
+        Self {
+            x: transform.translation.x,
+            y: transform.translation.y,
+            w: transform.scale.x,
+            h: transform.scale.y,
+        }
+    }
+
+    /// The transform that places this rect inside a unit-sized parent drawn at `parent`
+    pub fn relative_transform(&self, parent: &LayoutRect) -> Transform {
+        Transform {
+            translation: Vec3::new(
+                (self.x - parent.x) / parent.w,
+                (self.y - parent.y) / parent.h,
+                0.0,
+            ),
+            scale: Vec3::new(self.w / parent.w, self.h / parent.h, 1.0),
+            ..default()
+        }
+    }
+
+    /// Whether any of this rect is inside `min_x..max_x`. Rects that only touch it aren't.
+    fn is_within_x(&self, min_x: f32, max_x: f32) -> bool {
+        self.x < max_x && min_x < self.x + self.w
+    }
+
+    /// This rect, less any part of it outside of `min_x..max_x`
+    fn clipped_to_x(&self, min_x: f32, max_x: f32) -> LayoutRect {
+        let x = self.x.max(min_x);
+        LayoutRect {
+            x,
+            w: ((self.x + self.w).min(max_x) - x).max(0.0),
+            ..*self
+        }
+    }
+}
+
+/// The rules shared by every icicle
+#[derive(Clone, Copy, Debug)]
+pub struct IcicleParams {
+    /// The space between siblings, and between rows
+    pub gap_width: f32,
+    /// Children narrower than this (including their gap) are hidden
+    pub min_child_width_with_gap: f32,
+    /// The number of levels laid out above the root
+    pub max_levels: u16,
+    /// The span of x that's on screen. Nodes outside of it are hidden along with their
+    /// subtrees, and the rest are clipped to it.
+    pub visible_min_x: f32,
+    pub visible_max_x: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct IcicleNode<Id> {
+    pub id: Id,
+    /// `None` for the layout root
+    pub parent: Option<Id>,
+    /// The number of levels above the layout root
+    pub level: u16,
+    pub rect: LayoutRect,
+    pub color_range: DescendentColorRange,
+}
+
+/// The children of a node that were too narrow to draw. Children that were too small to
+/// be materialized at all are counted in, but aren't listed.
+#[derive(Clone, Debug)]
+pub struct HiddenChildren<Id> {
+    pub parent: Id,
+    pub children: Vec<Id>,
+    pub count: usize,
+    pub size: u64,
+    /// The width the hidden children would have taken up together
+    pub width: f32,
+    /// Where the hidden children are drawn as one group, after the visible children. This
+    /// is `None` when they aren't wide enough together for a group.
+    pub group_rect: Option<LayoutRect>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IcicleLayout<Id> {
+    /// Every node that's drawn, parents before their children
+    pub nodes: Vec<IcicleNode<Id>>,
+    pub hidden_children: Vec<HiddenChildren<Id>>,
+    /// The roots of subtrees that aren't drawn at all. This includes the hidden children,
+    /// the children of nodes at `max_levels`, and children outside of the visible span.
+    pub hidden_subtrees: Vec<Id>,
+}
+
+/// Lays out the subtree rooted at `root` as an icicle, with `root` filling `viewport`.
+/// Each level is a row of the viewport's height above its parent's, and each child's
+/// width is its share of its parent's size, less the gaps between siblings. Widths are
+/// rounded down to whole units, and the remainder is given to the last child.
+///
+/// The viewport can be wider than the visible span (see [`IcicleParams::visible_min_x`]),
+/// to magnify part of the tree.
+pub fn layout_icicle<T: LayoutTree>(
+    tree: &T,
+    root: T::Id,
+    viewport: LayoutRect,
+    root_color_range: DescendentColorRange,
+    params: &IcicleParams,
+) -> IcicleLayout<T::Id> {
+    let mut layout = IcicleLayout {
+        nodes: vec![IcicleNode {
+            id: root,
+            parent: None,
+            level: 0,
+            rect: viewport,
+            color_range: root_color_range,
+        }],
+        hidden_children: vec![],
+        hidden_subtrees: vec![],
+    };
+
+    let mut next_parent = 0;
+    while next_parent < layout.nodes.len() {
+        let parent = layout.nodes[next_parent].clone();
+        next_parent += 1;
+
+        if parent.level >= params.max_levels {
+            layout.hidden_subtrees.extend(tree.children(parent.id));
+            continue;
+        }
+        layout_children(tree, &parent, params, &mut layout);
+    }
+
+    // Children are placed within their parents' full width, so nothing is clipped until
+    // everything has been placed. Anything left without any width can't be drawn, and
+    // would only scale its children by zero.
+    let (min_x, max_x) = (params.visible_min_x, params.visible_max_x);
+    let mut clipped_away = vec![];
+    layout.nodes.retain_mut(|node| {
+        node.rect = node.rect.clipped_to_x(min_x, max_x);
+        if node.rect.w > 0.0 || node.parent.is_none() {
+            true
+        } else {
+            clipped_away.push(node.id);
+            false
+        }
+    });
+    layout.hidden_subtrees.extend(clipped_away);
+    for hidden in layout.hidden_children.iter_mut() {
+        hidden.group_rect = hidden
+            .group_rect
+            .filter(|group_rect| group_rect.is_within_x(min_x, max_x))
+            .map(|group_rect| group_rect.clipped_to_x(min_x, max_x))
+            .filter(|group_rect| group_rect.w > 0.0);
+    }
+    layout
+}
+
+/// How a parent's children split into those wide enough to be drawn on their own, and
+/// the rest
+#[derive(Clone, Debug)]
+pub struct ChildPartition<Id> {
+    /// The children wide enough to draw, with their share of the parent's size
+    pub visible: Vec<(Id, f32)>,
+    /// The materialized children that are too narrow
+    pub hidden: Vec<Id>,
+    /// The number of hidden children, including those that aren't materialized
+    pub hidden_count: usize,
+    /// Whatever the visible children leave of the parent's size
+    pub hidden_size: u64,
+    /// The hidden children's share of the parent's size
+    pub hidden_fraction: f32,
+    /// Whether the hidden children are wide enough together to be drawn as a group
+    pub is_grouped: bool,
+}
+
+/// Splits `parent`'s children into those that are at least `min_child_width_with_gap`
+/// wide when the parent is `parent_w` wide, and the rest. Every layout that groups small
+/// items splits children this way.
+pub fn partition_children<T: LayoutTree>(
+    tree: &T,
+    parent: T::Id,
+    parent_w: f32,
+    min_child_width_with_gap: f32,
+) -> ChildPartition<T::Id> {
+    let parent_size = tree.size(parent);
+    let fraction_of_parent = |size: u64| {
+        if parent_size == 0 {
+            0.0
+        } else {
+            size as f32 / parent_size as f32
+        }
+    };
+
+    // Any child narrower than the minimum is hidden
+    let children_by_visibility = tree
+        .children(parent)
+        .into_iter()
+        .map(|child| (child, fraction_of_parent(tree.size(child))))
+        .grouping_by(|(_, fractional_w)| {
+            fractional_w * parent_w >= min_child_width_with_gap
+        });
+    let visible = children_by_visibility
+        .get(&true)
+        .cloned()
+        .unwrap_or_default();
+    let hidden: Vec<T::Id> = children_by_visibility
+        .get(&false)
+        .map(|hidden| hidden.iter().map(|(id, _)| *id).collect())
+        .unwrap_or_default();
+
+    // The hidden children take up whatever the visible ones leave of the parent's size,
+    // as a group if there's room for one
+    let hidden_count =
+        tree.child_count(parent).max(visible.len() + hidden.len()) - visible.len();
+    let hidden_size = parent_size
+        .saturating_sub(visible.iter().map(|(child, _)| tree.size(*child)).sum());
+    let hidden_fraction = fraction_of_parent(hidden_size);
+    ChildPartition {
+        visible,
+        hidden,
+        hidden_count,
+        hidden_size,
+        hidden_fraction,
+        is_grouped: hidden_fraction * parent_w > min_child_width_with_gap,
+    }
+}
+
+fn layout_children<T: LayoutTree>(
+    tree: &T,
+    parent: &IcicleNode<T::Id>,
+    params: &IcicleParams,
+    layout: &mut IcicleLayout<T::Id>,
+) {
+    let parent_w = parent.rect.w;
+    let partition =
+        partition_children(tree, parent.id, parent_w, params.min_child_width_with_gap);
+    let visible_children = &partition.visible;
+    let is_grouped = partition.is_grouped;
+
+    let number_of_gaps = (visible_children.len() + is_grouped as usize).max(1) - 1;
+    let available_w_minus_gaps = parent_w - number_of_gaps as f32 * params.gap_width;
+
+    let mut x = 0_f32; // Relative to the parent
+    let mut remainder_w = 0_f32;
+    for (index, (child, fractional_w)) in visible_children.iter().enumerate() {
+        let is_last = index == visible_children.len() - 1;
+
+        let child_w = fractional_w * available_w_minus_gaps;
+        remainder_w += child_w - child_w.floor();
+        let child_w = child_w.floor() + if is_last { remainder_w } else { 0.0 };
+
+        let rect = LayoutRect {
+            x: parent.rect.x + x,
+            y: parent.rect.y + parent.rect.h + params.gap_width,
+            w: child_w,
+            h: parent.rect.h,
+        };
+        if rect.is_within_x(params.visible_min_x, params.visible_max_x) {
+            layout.nodes.push(IcicleNode {
+                id: *child,
+                parent: Some(parent.id),
+                level: parent.level + 1,
+                rect,
+                color_range: parent.color_range.sub_range(x / parent_w, *fractional_w),
+            });
+        } else {
+            layout.hidden_subtrees.push(*child);
+        }
+
+        x += child_w + params.gap_width;
+    }
+
+    if partition.hidden_count > 0 {
+        layout
+            .hidden_subtrees
+            .extend(partition.hidden.iter().copied());
+        layout.hidden_children.push(HiddenChildren {
+            parent: parent.id,
+            children: partition.hidden,
+            count: partition.hidden_count,
+            size: partition.hidden_size,
+            width: partition.hidden_fraction * parent_w,
+            // The group takes the space left over after the last visible child
+            group_rect: is_grouped.then(|| LayoutRect {
+                x: parent.rect.x + x,
+                y: parent.rect.y + parent.rect.h + params.gap_width,
+                w: (partition.hidden_fraction * available_w_minus_gaps).floor(),
+                h: parent.rect.h,
+            }),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A tree built from `(id, parent, size)` triples, with children in the order they're
+    /// given. Nodes can claim more children than they're given, standing in for
+    /// unmaterialized ones.
+    #[derive(Default)]
+    struct TestTree {
+        sizes: HashMap<u32, u64>,
+        children: HashMap<u32, Vec<u32>>,
+        child_counts: HashMap<u32, usize>,
+    }
+
+    impl TestTree {
+        fn new(nodes: &[(u32, Option<u32>, u64)]) -> Self {
+            let mut tree = TestTree::default();
+            for (id, parent, size) in nodes {
+                tree.sizes.insert(*id, *size);
+                if let Some(parent) = parent {
+                    tree.children.entry(*parent).or_default().push(*id);
+                }
+            }
+            tree
+        }
+
+        fn with_child_count(mut self, id: u32, count: usize) -> Self {
+            self.child_counts.insert(id, count);
+            self
+        }
+    }
+
+    impl LayoutTree for TestTree {
+        type Id = u32;
+
+        fn size(&self, id: u32) -> u64 {
+            self.sizes[&id]
+        }
+
+        fn children(&self, id: u32) -> Vec<u32> {
+            self.children.get(&id).cloned().unwrap_or_default()
+        }
+
+        fn child_count(&self, id: u32) -> usize {
+            self.child_counts
+                .get(&id)
+                .copied()
+                .unwrap_or_else(|| self.children(id).len())
+        }
+    }
+
+    const VIEWPORT: LayoutRect = LayoutRect {
+        x: 0.0,
+        y: 0.0,
+        w: 1000.0,
+        h: 10.0,
+    };
+
+    fn params() -> IcicleParams {
+        IcicleParams {
+            gap_width: 1.0,
+            min_child_width_with_gap: 4.0,
+            max_levels: 8,
+            visible_min_x: VIEWPORT.x,
+            visible_max_x: VIEWPORT.x + VIEWPORT.w,
+        }
+    }
+
+    fn layout(tree: &TestTree, params: &IcicleParams) -> IcicleLayout<u32> {
+        layout_icicle(tree, 0, VIEWPORT, DescendentColorRange::default(), params)
+    }
+
+    fn node(layout: &IcicleLayout<u32>, id: u32) -> &IcicleNode<u32> {
+        layout.nodes.iter().find(|node| node.id == id).unwrap()
+    }
+
+    #[test]
+    fn child_widths_and_gaps_fill_the_parent() {
+        let tree = TestTree::new(&[
+            (0, None, 1000),
+            (1, Some(0), 333),
+            (2, Some(0), 333),
+            (3, Some(0), 334),
+            (4, Some(1), 200),
+            (5, Some(1), 133),
+        ]);
+        let layout = layout(&tree, &params());
+
+        for (parent, children) in [(0, vec![1, 2, 3]), (1, vec![4, 5])] {
+            let parent_rect = node(&layout, parent).rect;
+            let rects: Vec<LayoutRect> = children
+                .iter()
+                .map(|child| node(&layout, *child).rect)
+                .collect();
+            let widths: f32 = rects.iter().map(|rect| rect.w).sum();
+            let gaps = (rects.len() - 1) as f32 * params().gap_width;
+            assert!((widths + gaps - parent_rect.w).abs() < 1e-3);
+
+            // Siblings are packed from the parent's left edge, a gap apart, a row above
+            assert_eq!(rects[0].x, parent_rect.x);
+            for pair in rects.windows(2) {
+                assert_eq!(pair[0].x + pair[0].w + params().gap_width, pair[1].x);
+            }
+            for rect in rects.iter() {
+                assert_eq!(rect.y, parent_rect.y + parent_rect.h + params().gap_width);
+            }
+        }
+    }
+
+    #[test]
+    fn whole_widths_leave_the_remainder_to_the_last_child() {
+        let tree = TestTree::new(&[
+            (0, None, 3),
+            (1, Some(0), 1),
+            (2, Some(0), 1),
+            (3, Some(0), 1),
+        ]);
+        let layout = layout(&tree, &params());
+
+        assert_eq!(node(&layout, 1).rect.w, 332.0);
+        assert_eq!(node(&layout, 2).rect.w, 332.0);
+        assert!((node(&layout, 3).rect.w - 334.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn narrow_children_are_hidden_and_grouped() {
+        // Children 3 and 4 are each narrower than the minimum, but wide enough together
+        let tree = TestTree::new(&[
+            (0, None, 1000),
+            (1, Some(0), 600),
+            (2, Some(0), 390),
+            (3, Some(0), 3),
+            (4, Some(0), 3),
+        ]);
+        let layout = layout(&tree, &params());
+
+        assert!(layout.nodes.iter().all(|node| node.id != 3 && node.id != 4));
+        assert!(layout.hidden_subtrees.contains(&3));
+        assert!(layout.hidden_subtrees.contains(&4));
+
+        let hidden = &layout.hidden_children[0];
+        assert_eq!(hidden.parent, 0);
+        assert_eq!(hidden.children, vec![3, 4]);
+        assert_eq!(hidden.count, 2);
+        // Whatever the visible children leave over
+        assert_eq!(hidden.size, 10);
+        assert!((hidden.width - 10.0).abs() < 1e-3);
+
+        // The group comes after the last visible child
+        let group_rect = hidden.group_rect.unwrap();
+        let last_visible = node(&layout, 2).rect;
+        assert_eq!(
+            group_rect.x,
+            last_visible.x + last_visible.w + params().gap_width
+        );
+        assert!(group_rect.x + group_rect.w <= VIEWPORT.w);
+    }
+
+    #[test]
+    fn unmaterialized_children_are_counted_as_hidden() {
+        // Only the big child is materialized. The other 99 are too small for that, and
+        // are only known from the parent's size and child count.
+        let tree =
+            TestTree::new(&[(0, None, 1000), (1, Some(0), 900)]).with_child_count(0, 100);
+        let layout = layout(&tree, &params());
+
+        let hidden = &layout.hidden_children[0];
+        assert!(hidden.children.is_empty());
+        assert_eq!(hidden.count, 99);
+        assert_eq!(hidden.size, 100);
+        assert!(hidden.group_rect.is_some());
+    }
+
+    #[test]
+    fn children_too_narrow_together_are_not_grouped() {
+        let tree = TestTree::new(&[(0, None, 1000), (1, Some(0), 999), (2, Some(0), 1)]);
+        let layout = layout(&tree, &params());
+
+        let hidden = &layout.hidden_children[0];
+        assert_eq!(hidden.count, 1);
+        assert!(hidden.group_rect.is_none());
+    }
+
+    #[test]
+    fn levels_beyond_max_levels_are_hidden() {
+        let tree = TestTree::new(&[
+            (0, None, 100),
+            (1, Some(0), 100),
+            (2, Some(1), 100),
+            (3, Some(2), 100),
+        ]);
+        let layout = layout(
+            &tree,
+            &IcicleParams {
+                max_levels: 2,
+                ..params()
+            },
+        );
+
+        assert_eq!(node(&layout, 2).level, 2);
+        assert!(layout.nodes.iter().all(|node| node.id != 3));
+        assert_eq!(layout.hidden_subtrees, vec![3]);
+    }
+
+    #[test]
+    fn nodes_outside_the_visible_span_are_hidden_and_the_rest_clipped() {
+        let tree = TestTree::new(&[
+            (0, None, 100),
+            (1, Some(0), 50),
+            (2, Some(0), 50),
+            (3, Some(2), 50),
+        ]);
+        // Magnified so that only the middle of the tree is on screen
+        let layout = layout_icicle(
+            &tree,
+            0,
+            LayoutRect {
+                x: -1500.0,
+                w: 3000.0,
+                ..VIEWPORT
+            },
+            DescendentColorRange::default(),
+            &params(),
+        );
+
+        assert!(layout.nodes.iter().all(|node| node.id != 1));
+        assert!(layout.hidden_subtrees.contains(&1));
+        for node in layout.nodes.iter() {
+            assert!(node.rect.x >= VIEWPORT.x);
+            assert!(node.rect.x + node.rect.w <= VIEWPORT.x + VIEWPORT.w);
+        }
+        assert_eq!(node(&layout, 3).rect.w, VIEWPORT.w);
+    }
+
+    #[test]
+    fn nodes_that_only_touch_the_visible_span_are_hidden() {
+        let tree = TestTree::new(&[
+            (0, None, 100),
+            (1, Some(0), 50),
+            (2, Some(0), 50),
+            (3, Some(1), 50),
+        ]);
+        // The first child ends exactly where the visible span starts
+        let layout = layout_icicle(
+            &tree,
+            0,
+            LayoutRect {
+                x: -1000.0,
+                w: 2001.0,
+                ..VIEWPORT
+            },
+            DescendentColorRange::default(),
+            &params(),
+        );
+
+        assert!(layout.nodes.iter().all(|node| node.id != 1 && node.id != 3));
+        assert!(layout.hidden_subtrees.contains(&1));
+        assert!(layout.nodes.iter().all(|node| node.rect.w > 0.0));
+    }
+
+    #[test]
+    fn directories_split_their_color_range_among_their_children() {
+        let tree = TestTree::new(&[
+            (0, None, 100),
+            (1, Some(0), 25),
+            (2, Some(0), 75),
+            (3, Some(2), 75),
+        ]);
+        let layout = layout(&tree, &params());
+
+        assert_eq!(node(&layout, 1).color_range.start(), 0.0);
+        assert!((node(&layout, 2).color_range.start() - 0.25).abs() < 1e-2);
+        // An only child gets its parent's whole range
+        assert_eq!(
+            node(&layout, 3).color_range.start(),
+            node(&layout, 2).color_range.start()
+        );
+
+        let range = DescendentColorRange::default().sub_range(0.5, 0.5);
+        assert_eq!(range.start(), 0.5);
+        assert_eq!(range.sub_range(0.5, 0.5).start(), 0.75);
+    }
+
+    #[test]
+    fn relative_transforms_place_children_inside_their_parents() {
+        let parent = LayoutRect {
+            x: 100.0,
+            y: 50.0,
+            w: 200.0,
+            h: 10.0,
+        };
+        let child = LayoutRect {
+            x: 150.0,
+            y: 61.0,
+            w: 100.0,
+            h: 10.0,
+        };
+        let transform = child.relative_transform(&parent);
+
+        assert_eq!(transform.translation, Vec3::new(0.25, 1.1, 0.0));
+        assert_eq!(transform.scale, Vec3::new(0.5, 1.0, 1.0));
+    }
+}
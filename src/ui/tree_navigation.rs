@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+
+/// The shape of the tree that keyboard navigation moves through. This is the
+/// `Parent`/`Children` hierarchy, narrowed down to the nodes that are drawn, with their
+/// sizes. Keeping it behind a trait means the navigation rules can be exercised without
+/// a `World`.
+pub trait NavigationHierarchy {
+    fn parent(&self, entity: Entity) -> Option<Entity>;
+    /// The displayed children of `entity`, in left-to-right order
+    fn displayed_children(&self, entity: Entity) -> Vec<Entity>;
+    fn size(&self, entity: Entity) -> u64;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavigationMove {
+    PreviousSibling,
+    NextSibling,
+    Parent,
+    LargestChild,
+}
+
+/// Finds the node reached by moving from `focused`, or `None` if there is nowhere to go.
+/// Navigation never leaves the subtree rooted at `layout_root`.
+pub fn navigate<H: NavigationHierarchy>(
+    hierarchy: &H,
+    layout_root: Entity,
+    focused: Entity,
+    navigation_move: NavigationMove,
+) -> Option<Entity> {
+    match navigation_move {
+        NavigationMove::PreviousSibling => {
+            sibling_of(hierarchy, layout_root, focused, -1)
+        }
+        NavigationMove::NextSibling => sibling_of(hierarchy, layout_root, focused, 1),
+        NavigationMove::Parent if focused != layout_root => hierarchy.parent(focused),
+        NavigationMove::Parent => None,
+        NavigationMove::LargestChild => hierarchy
+            .displayed_children(focused)
+            .into_iter()
+            // Ties go to the leftmost child
+            .rev()
+            .max_by_key(|child| hierarchy.size(*child)),
+    }
+}
+
+fn sibling_of<H: NavigationHierarchy>(
+    hierarchy: &H,
+    layout_root: Entity,
+    entity: Entity,
+    offset: isize,
+) -> Option<Entity> {
+    if entity == layout_root {
+        return None;
+    }
+
+    let siblings = hierarchy.displayed_children(hierarchy.parent(entity)?);
+    let index = siblings.iter().position(|s| *s == entity)? as isize + offset;
+    if index < 0 {
+        None
+    } else {
+        siblings.get(index as usize).copied()
+    }
+}
+
+/// Whether `entity` is `ancestor`, or one of its descendents
+pub fn is_within<H: NavigationHierarchy>(
+    hierarchy: &H,
+    ancestor: Entity,
+    entity: Entity,
+) -> bool {
+    let mut next = Some(entity);
+    while let Some(current) = next {
+        if current == ancestor {
+            return true;
+        }
+        next = hierarchy.parent(current);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A hierarchy built from `(child, parent, size)` triples, with children in the order
+    /// they're given
+    struct TestHierarchy {
+        parents: HashMap<Entity, Entity>,
+        children: HashMap<Entity, Vec<Entity>>,
+        sizes: HashMap<Entity, u64>,
+    }
+
+    impl TestHierarchy {
+        fn new(nodes: &[(u32, Option<u32>, u64)]) -> Self {
+            let mut hierarchy = TestHierarchy {
+                parents: HashMap::new(),
+                children: HashMap::new(),
+                sizes: HashMap::new(),
+            };
+            for (id, parent, size) in nodes {
+                let entity = Entity::from_raw(*id);
+                hierarchy.sizes.insert(entity, *size);
+                if let Some(parent) = parent {
+                    let parent = Entity::from_raw(*parent);
+                    hierarchy.parents.insert(entity, parent);
+                    hierarchy.children.entry(parent).or_default().push(entity);
+                }
+            }
+            hierarchy
+        }
+    }
+
+    impl NavigationHierarchy for TestHierarchy {
+        fn parent(&self, entity: Entity) -> Option<Entity> {
+            self.parents.get(&entity).copied()
+        }
+
+        fn displayed_children(&self, entity: Entity) -> Vec<Entity> {
+            self.children.get(&entity).cloned().unwrap_or_default()
+        }
+
+        fn size(&self, entity: Entity) -> u64 {
+            self.sizes[&entity]
+        }
+    }
+
+    fn e(id: u32) -> Entity {
+        Entity::from_raw(id)
+    }
+
+    ///      0
+    ///    / | \
+    ///   1  2  3
+    ///  / \
+    /// 4   5
+    fn tree() -> TestHierarchy {
+        TestHierarchy::new(&[
+            (0, None, 100),
+            (1, Some(0), 50),
+            (2, Some(0), 30),
+            (3, Some(0), 20),
+            (4, Some(1), 25),
+            (5, Some(1), 25),
+        ])
+    }
+
+    #[test]
+    fn siblings_are_left_and_right() {
+        let tree = tree();
+        assert_eq!(
+            navigate(&tree, e(0), e(2), NavigationMove::PreviousSibling),
+            Some(e(1))
+        );
+        assert_eq!(
+            navigate(&tree, e(0), e(2), NavigationMove::NextSibling),
+            Some(e(3))
+        );
+    }
+
+    #[test]
+    fn there_is_no_sibling_past_either_end() {
+        let tree = tree();
+        assert_eq!(
+            navigate(&tree, e(0), e(1), NavigationMove::PreviousSibling),
+            None
+        );
+        assert_eq!(
+            navigate(&tree, e(0), e(3), NavigationMove::NextSibling),
+            None
+        );
+    }
+
+    #[test]
+    fn parent_and_largest_child() {
+        let tree = tree();
+        assert_eq!(
+            navigate(&tree, e(0), e(4), NavigationMove::Parent),
+            Some(e(1))
+        );
+        assert_eq!(
+            navigate(&tree, e(0), e(0), NavigationMove::LargestChild),
+            Some(e(1))
+        );
+    }
+
+    #[test]
+    fn largest_child_ties_go_to_the_leftmost() {
+        let tree = tree();
+        assert_eq!(
+            navigate(&tree, e(0), e(1), NavigationMove::LargestChild),
+            Some(e(4))
+        );
+    }
+
+    #[test]
+    fn leaves_have_no_largest_child() {
+        let tree = tree();
+        assert_eq!(
+            navigate(&tree, e(0), e(5), NavigationMove::LargestChild),
+            None
+        );
+    }
+
+    #[test]
+    fn navigation_stays_within_the_layout_root() {
+        let tree = tree();
+        // The layout root has neither a parent nor siblings, even though 1 has both
+        for navigation_move in [
+            NavigationMove::Parent,
+            NavigationMove::PreviousSibling,
+            NavigationMove::NextSibling,
+        ] {
+            assert_eq!(navigate(&tree, e(1), e(1), navigation_move), None);
+        }
+        assert_eq!(navigate(&tree, e(0), e(0), NavigationMove::Parent), None);
+    }
+
+    #[test]
+    fn is_within_covers_the_whole_subtree() {
+        let tree = tree();
+        assert!(is_within(&tree, e(1), e(1)));
+        assert!(is_within(&tree, e(1), e(5)));
+        assert!(is_within(&tree, e(0), e(5)));
+    }
+
+    #[test]
+    fn is_within_excludes_siblings_and_ancestors() {
+        let tree = tree();
+        assert!(!is_within(&tree, e(1), e(2)));
+        assert!(!is_within(&tree, e(1), e(0)));
+        assert!(!is_within(&tree, e(2), e(4)));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::fs::FsEntityComponent;
+use bevy::{prelude::*, transform::TransformSystem};
+use std::{fmt, str::FromStr, time::Duration};
+
+/// How layout changes are animated
+#[derive(Clone, Copy, Debug)]
+pub struct TreeViewAnimationOptions {
+    /// A zero duration turns animation off
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl Default for TreeViewAnimationOptions {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_millis(250),
+            easing: Easing::CubicInOut,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadraticInOut,
+    CubicOut,
+    CubicInOut,
+}
+
+impl Easing {
+    const NAMES: [&'static str; 4] =
+        ["linear", "quad-in-out", "cubic-out", "cubic-in-out"];
+
+    /// Maps linear progress in `[0..1]` to eased progress in `[0..1]`
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::QuadraticInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Easing::Linear => Self::NAMES[0],
+            Easing::QuadraticInOut => Self::NAMES[1],
+            Easing::CubicOut => Self::NAMES[2],
+            Easing::CubicInOut => Self::NAMES[3],
+        }
+    }
+}
+
+impl fmt::Display for Easing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Easing {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Easing::Linear,
+            Easing::QuadraticInOut,
+            Easing::CubicOut,
+            Easing::CubicInOut,
+        ]
+        .into_iter()
+        .find(|easing| easing.name() == s)
+        .ok_or(())
+    }
+}
+
+/// Tracks what a sprite looks like on screen, as opposed to the target values that the
+/// layout wrote into its components.
+///
+/// The layout systems are unaware of animation. They write their results directly into
+/// `Transform`, `Sprite::color` and `Visibility`, and each frame we notice those writes,
+/// remember them as the new targets, then overwrite the components with the in-between
+/// values.
+#[derive(Component)]
+struct LayoutTransition {
+    from_transform: Transform,
+    to_transform: Transform,
+    displayed_transform: Transform,
+    from_opacity: f32,
+    to_opacity: f32,
+    displayed_opacity: f32,
+    /// The layout's color, at its full opacity
+    color: Color,
+    started_at: f64,
+}
+
+impl LayoutTransition {
+    fn new(transform: Transform, color: Color, is_visible: bool) -> Self {
+        let opacity = if is_visible { 1.0 } else { 0.0 };
+        Self {
+            from_transform: transform,
+            to_transform: transform,
+            displayed_transform: transform,
+            from_opacity: opacity,
+            to_opacity: opacity,
+            displayed_opacity: opacity,
+            color,
+            started_at: 0.0,
+        }
+    }
+
+    fn is_displayed(&self) -> bool {
+        self.displayed_opacity > 0.0
+    }
+
+    /// Starts a new transition from whatever is currently on screen
+    fn restart(&mut self, now: f64) {
+        self.from_transform = self.displayed_transform;
+        self.from_opacity = self.displayed_opacity;
+        self.started_at = now;
+    }
+}
+
+pub struct TreeViewAnimationPlugin;
+impl Plugin for TreeViewAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeViewAnimationOptions>()
+            .add_system_to_stage(CoreStage::PostUpdate, track_new_fs_sprites)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                animate_layout_transitions
+                    .after(track_new_fs_sprites)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn track_new_fs_sprites(
+    mut commands: Commands,
+    new_sprites_query: Query<
+        (Entity, &Transform, &Sprite, &Visibility),
+        (With<FsEntityComponent>, Without<LayoutTransition>),
+    >,
+) {
+    for (entity, transform, sprite, vis) in new_sprites_query.iter() {
+        commands.entity(entity).insert(LayoutTransition::new(
+            *transform,
+            sprite.color,
+            vis.is_visible,
+        ));
+    }
+}
+
+fn animate_layout_transitions(
+    mut transitions_query: Query<(
+        &mut LayoutTransition,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+    options: Res<TreeViewAnimationOptions>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    let duration = options.duration.as_secs_f64();
+
+    for (mut transition, mut transform, mut sprite, mut vis) in
+        transitions_query.iter_mut()
+    {
+        // Our own writes from the last frame aren't reported as changes, so anything
+        // changed here was written by the layout
+        if sprite.is_changed() {
+            transition.color = sprite.color;
+        }
+
+        let to_opacity = if vis.is_changed() {
+            if vis.is_visible {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            transition.to_opacity
+        };
+        let to_transform = if transform.is_changed() {
+            *transform
+        } else {
+            transition.to_transform
+        };
+
+        if to_opacity != transition.to_opacity || to_transform != transition.to_transform
+        {
+            let was_displayed = transition.is_displayed();
+            transition.restart(now);
+            transition.to_opacity = to_opacity;
+            transition.to_transform = to_transform;
+
+            // Sprites that are appearing fade in where they land, rather than flying in
+            // from wherever they were last seen
+            if !was_displayed {
+                transition.from_transform = to_transform;
+            }
+        }
+
+        let t = if duration <= 0.0 {
+            1.0
+        } else {
+            ((now - transition.started_at) / duration).clamp(0.0, 1.0) as f32
+        };
+        let eased_t = options.easing.apply(t);
+
+        transition.displayed_transform = Transform {
+            translation: transition
+                .from_transform
+                .translation
+                .lerp(transition.to_transform.translation, eased_t),
+            rotation: transition
+                .from_transform
+                .rotation
+                .slerp(transition.to_transform.rotation, eased_t),
+            scale: transition
+                .from_transform
+                .scale
+                .lerp(transition.to_transform.scale, eased_t),
+        };
+        transition.displayed_opacity = transition.from_opacity
+            + (transition.to_opacity - transition.from_opacity) * eased_t;
+
+        // Only write what differs, so we aren't marking every sprite changed every frame
+        if *transform != transition.displayed_transform {
+            *transform = transition.displayed_transform;
+        }
+        let displayed_color = {
+            let mut color = transition.color;
+            color.set_a(color.a() * transition.displayed_opacity);
+            color
+        };
+        if sprite.color != displayed_color {
+            sprite.color = displayed_color;
+        }
+        let is_visible = transition.is_displayed();
+        if vis.is_visible != is_visible {
+            vis.is_visible = is_visible;
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use super::{
+    mouse_interactions_plugin::HoverableClicked,
+    panel_plugin::PanelFont,
+    tree_navigation::{is_within, navigate, NavigationHierarchy, NavigationMove},
+    tree_view_plugin::{
+        is_modifier_pressed, DiskUsageTreeOptions, FocusSprite, TreeViewOrientation,
+    },
+    tree_view_zoom_plugin::{TreeViewZoom, ZoomCommand},
+};
+use crate::fs::{
+    FsAggregateSize, FsEntityComponent, FsEntityMap, FsNodeId, FsRootComponent, FsTree,
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use tracing::debug;
+use valuable::Valuable;
+
+/// The number of frames we wait for a search result to be laid out before settling for
+/// focusing its closest displayed ancestor
+const MAX_REVEAL_FRAMES: u32 = 10;
+
+/// The node that keyboard navigation moves from
+#[derive(Default)]
+pub struct TreeViewFocus {
+    focused: Option<Entity>,
+}
+
+impl TreeViewFocus {
+    pub fn focused(&self) -> Option<Entity> {
+        self.focused
+    }
+}
+
+/// The state of the `/` search box. While it's open, it receives all key presses.
+#[derive(Default)]
+pub struct TreeSearch {
+    is_active: bool,
+    query: String,
+    last_match: Option<FsNodeId>,
+}
+
+impl TreeSearch {
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+}
+
+/// A search result that should be focused once it has been materialized and laid out
+#[derive(Default, Deref, DerefMut)]
+struct PendingReveal(Option<FsNodeId>);
+
+#[derive(Component)]
+struct SearchText;
+
+/// The entity hierarchy, narrowed to the nodes that are currently drawn
+#[derive(SystemParam)]
+struct DisplayedFsHierarchy<'w, 's> {
+    parent_query: Query<'w, 's, &'static Parent, With<FsEntityComponent>>,
+    children_query: Query<'w, 's, &'static Children, With<FsEntityComponent>>,
+    node_query: Query<'w, 's, (&'static FsAggregateSize, &'static Visibility)>,
+}
+
+impl NavigationHierarchy for DisplayedFsHierarchy<'_, '_> {
+    fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.parent_query
+            .get(entity)
+            .ok()
+            .map(|parent| parent.0)
+            // The fs root's parent is the transform root
+            .filter(|parent| self.node_query.contains(*parent))
+    }
+
+    fn displayed_children(&self, entity: Entity) -> Vec<Entity> {
+        self.children_query
+            .get(entity)
+            .map(|children| {
+                children
+                    .iter()
+                    .filter(|child| {
+                        matches!(self.node_query.get(**child), Ok((_, vis)) if vis.is_visible)
+                    })
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn size(&self, entity: Entity) -> u64 {
+        self.node_query
+            .get(entity)
+            .map_or(0, |(size, _)| size.size_in_bytes)
+    }
+}
+
+pub struct TreeViewFocusPlugin;
+impl Plugin for TreeViewFocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeViewFocus>()
+            .init_resource::<TreeSearch>()
+            .init_resource::<PendingReveal>()
+            .add_startup_system(create_search_text)
+            .add_system(handle_focus_keys)
+            .add_system(handle_search_input.after(handle_focus_keys))
+            .add_system(focus_on_click)
+            .add_system(reveal_pending_focus)
+            .add_system(keep_focus_within_layout_root)
+            .add_system(
+                sync_focus_sprite
+                    .after(handle_focus_keys)
+                    .after(focus_on_click)
+                    .after(reveal_pending_focus)
+                    .after(keep_focus_within_layout_root),
+            );
+    }
+}
+
+/// The arrow keys move between siblings, to the parent and to the largest child, following
+/// the orientation (see [`navigation_move`]). Enter zooms into the focused directory,
+/// Backspace zooms out, and / starts a search (n repeats it).
+#[allow(clippy::too_many_arguments)]
+fn handle_focus_keys(
+    keys: Res<Input<KeyCode>>,
+    mut focus: ResMut<TreeViewFocus>,
+    mut search: ResMut<TreeSearch>,
+    mut pending_reveal: ResMut<PendingReveal>,
+    zoom: Res<TreeViewZoom>,
+    tree_options: Res<DiskUsageTreeOptions>,
+    hierarchy: DisplayedFsHierarchy,
+    fs_entity_query: Query<&FsEntityComponent>,
+    fs_root_query: Query<Entity, With<FsRootComponent>>,
+    fs_tree: Res<FsTree>,
+    mut zoom_commands: EventWriter<ZoomCommand>,
+) {
+    // Alt+arrows belong to the zoom history, and the search box gets all the keys while
+    // it's open
+    if search.is_active || keys.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
+        return;
+    }
+
+    let layout_root = match zoom
+        .layout_root()
+        .or_else(|| fs_root_query.get_single().ok())
+    {
+        Some(layout_root) => layout_root,
+        None => return,
+    };
+    let focused = focus.focused().unwrap_or(layout_root);
+
+    if let Some(navigation_move) = navigation_move(&keys, tree_options.orientation) {
+        let next = navigate(&hierarchy, layout_root, focused, navigation_move);
+        debug!(?navigation_move, ?next, "keyboard navigation");
+        focus.focused = Some(next.unwrap_or(focused));
+    }
+
+    if keys.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter])
+        && focused != layout_root
+        && matches!(fs_entity_query.get(focused), Ok(fs_entity) if fs_entity.is_dir())
+    {
+        zoom_commands.send(ZoomCommand::To(focused));
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        // Focusing the old layout root keeps our place after zooming out
+        focus.focused = Some(layout_root);
+        zoom_commands.send(ZoomCommand::Out);
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        focus.focused = None;
+    }
+
+    if keys.just_pressed(KeyCode::Slash) {
+        search.is_active = true;
+        search.query.clear();
+    }
+    if keys.just_pressed(KeyCode::N)
+        && !is_modifier_pressed(&keys)
+        && !search.query.is_empty()
+    {
+        let next_match = find_match(&fs_tree, &search.query, search.last_match);
+        search.last_match = next_match;
+        pending_reveal.0 = next_match;
+    }
+}
+
+/// The arrow pointing toward the children moves to the largest child, and the one pointing
+/// back moves to the parent. The other two move between siblings, in layout order.
+fn navigation_move(
+    keys: &Input<KeyCode>,
+    orientation: TreeViewOrientation,
+) -> Option<NavigationMove> {
+    let (to_parent, to_child, to_previous, to_next) = match orientation {
+        TreeViewOrientation::TopDown => {
+            (KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right)
+        }
+        TreeViewOrientation::BottomUp => {
+            (KeyCode::Down, KeyCode::Up, KeyCode::Left, KeyCode::Right)
+        }
+        TreeViewOrientation::LeftToRight => {
+            (KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down)
+        }
+    };
+    [
+        (to_previous, NavigationMove::PreviousSibling),
+        (to_next, NavigationMove::NextSibling),
+        (to_parent, NavigationMove::Parent),
+        (to_child, NavigationMove::LargestChild),
+    ]
+    .into_iter()
+    .find(|(key, _)| keys.just_pressed(*key))
+    .map(|(_, navigation_move)| navigation_move)
+}
+
+fn handle_search_input(
+    keys: Res<Input<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut search: ResMut<TreeSearch>,
+    mut pending_reveal: ResMut<PendingReveal>,
+    fs_tree: Res<FsTree>,
+    mut search_text_query: Query<(&mut Text, &mut Visibility), With<SearchText>>,
+) {
+    let typed: String = received_characters
+        .iter()
+        .map(|event| event.char)
+        // Names can't contain slashes, and this way the / that opened the search isn't
+        // included in the query
+        .filter(|c| !c.is_control() && *c != '/')
+        .collect();
+
+    if search.is_active {
+        search.query.push_str(&typed);
+        if keys.just_pressed(KeyCode::Back) {
+            search.query.pop();
+        }
+
+        if keys.just_pressed(KeyCode::Escape) {
+            search.is_active = false;
+        } else if keys.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter]) {
+            search.is_active = false;
+            let first_match = find_match(&fs_tree, &search.query, None);
+            debug!(
+                query = search.query.as_value(),
+                ?first_match,
+                "search submitted"
+            );
+            search.last_match = first_match;
+            pending_reveal.0 = first_match;
+        }
+    }
+
+    if !search.is_changed() {
+        return;
+    }
+    for (mut text, mut vis) in search_text_query.iter_mut() {
+        vis.is_visible = search.is_active;
+        text.sections[0].value = format!("/{}", search.query);
+    }
+}
+
+/// Finds the next node (in the order of the tree, after `after`) whose name contains `query`,
+/// ignoring case
+fn find_match(
+    fs_tree: &FsTree,
+    query: &str,
+    after: Option<FsNodeId>,
+) -> Option<FsNodeId> {
+    let query = query.to_lowercase();
+    let start = after.map_or(0, |node_id| node_id.index() + 1);
+    fs_tree
+        .iter()
+        .skip_while(|node_id| node_id.index() < start)
+        .chain(fs_tree.iter().take_while(|node_id| node_id.index() < start))
+        .filter(|node_id| *node_id != FsNodeId::ROOT)
+        .find(|node_id| {
+            fs_tree
+                .name(*node_id)
+                .to_string_lossy()
+                .to_lowercase()
+                .contains(&query)
+        })
+}
+
+/// Search results can be anywhere in the tree, including below the depth limit (where
+/// they have no entity). We zoom into the deepest ancestor that does have an entity, which
+/// materializes the levels below it, until the result itself can be focused.
+#[allow(clippy::too_many_arguments)]
+fn reveal_pending_focus(
+    mut pending_reveal: ResMut<PendingReveal>,
+    mut focus: ResMut<TreeViewFocus>,
+    fs_tree: Res<FsTree>,
+    fs_entity_map: Res<FsEntityMap>,
+    zoom: Res<TreeViewZoom>,
+    visibility_query: Query<&Visibility, With<FsEntityComponent>>,
+    fs_root_query: Query<Entity, With<FsRootComponent>>,
+    mut zoom_commands: EventWriter<ZoomCommand>,
+    mut frames_waited: Local<u32>,
+) {
+    let node_id = match pending_reveal.0 {
+        Some(node_id) => node_id,
+        None => return,
+    };
+    if pending_reveal.is_changed() {
+        *frames_waited = 0;
+    }
+
+    let maybe_entity = fs_entity_map.get(&node_id).copied();
+    if let Some(entity) = maybe_entity {
+        if matches!(visibility_query.get(entity), Ok(vis) if vis.is_visible) {
+            focus.focused = Some(entity);
+            pending_reveal.0 = None;
+            return;
+        }
+    }
+
+    let closest_ancestor = fs_tree
+        .ancestors(node_id)
+        .find_map(|ancestor| fs_entity_map.get(&ancestor).copied());
+    let closest_ancestor = match closest_ancestor {
+        Some(ancestor) => ancestor,
+        None => return,
+    };
+
+    let layout_root = zoom
+        .layout_root()
+        .or_else(|| fs_root_query.get_single().ok());
+    if layout_root != Some(closest_ancestor) {
+        zoom_commands.send(ZoomCommand::To(closest_ancestor));
+        *frames_waited = 0;
+    } else if *frames_waited >= MAX_REVEAL_FRAMES {
+        // The result is too small to be drawn, even with its parent filling the view
+        focus.focused = Some(maybe_entity.unwrap_or(closest_ancestor));
+        pending_reveal.0 = None;
+    } else {
+        *frames_waited += 1;
+    }
+}
+
+fn focus_on_click(
+    mut clicked_events: EventReader<HoverableClicked>,
+    fs_entity_query: Query<(), With<FsEntityComponent>>,
+    mut focus: ResMut<TreeViewFocus>,
+) {
+    for HoverableClicked { entity, button } in clicked_events.iter() {
+        if *button == MouseButton::Left && fs_entity_query.contains(*entity) {
+            focus.focused = Some(*entity);
+        }
+    }
+}
+
+/// After zooming, focus moves to the layout root if it was left outside of it
+fn keep_focus_within_layout_root(
+    zoom: Res<TreeViewZoom>,
+    mut focus: ResMut<TreeViewFocus>,
+    hierarchy: DisplayedFsHierarchy,
+) {
+    if !zoom.is_changed() {
+        return;
+    }
+
+    if let (Some(layout_root), Some(focused)) = (zoom.layout_root(), focus.focused()) {
+        if !is_within(&hierarchy, layout_root, focused) {
+            focus.focused = Some(layout_root);
+        }
+    }
+}
+
+fn sync_focus_sprite(
+    focus: Res<TreeViewFocus>,
+    mut focus_sprite_query: Query<&mut FocusSprite>,
+) {
+    if !focus.is_changed() {
+        return;
+    }
+
+    for mut focus_sprite in focus_sprite_query.iter_mut() {
+        focus_sprite.0 = focus.focused();
+    }
+}
+
+/// The search box sits above the breadcrumb bar, and is only shown while typing
+fn create_search_text(mut commands: Commands, font: Res<PanelFont>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(56.0),
+                    left: Val::Px(16.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::with_section(
+                "/",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+                default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(SearchText);
+}
@@ -1,55 +1,411 @@
-use super::mouse_interactions_plugin::Hoverable;
+use super::{
+    color_scheme::{ColorScheme, NodeColorer},
+    mouse_interactions_plugin::{HoverShape, Hoverable},
+    small_items_group::{
+        sync_small_items_groups, PlacedSmallItemsGroup, SmallItemsGroup,
+        SmallItemsGroupShape, SmallItemsGroups,
+    },
+    tree_layout::{
+        layout_icicle, IcicleLayout, IcicleParams, LayoutRect, LayoutTree,
+        RootChildrenSubset,
+    },
+    tree_view_focus_plugin::TreeSearch,
+    tree_view_sunburst::{
+        clear_sunburst_sectors, despawn_orphaned_segments, invalidate_sunburst_recursive,
+        place_sunburst_center, sync_sunburst_segments, SunburstGeometry, SunburstSector,
+    },
+    tree_view_treemap::{invalidate_treemap_recursive, TreemapRect},
+    tree_view_viewport_plugin::TreeViewViewport,
+    tree_view_zoom_plugin::TreeViewZoom,
+};
 use crate::{
     app_scaffold::WindowSize,
-    fs::{FsAggregateSize, FsEntityComponent, FsEntityKey, FsRootComponent},
+    fs::{
+        FsAggregateSize, FsEntityComponent, FsEntityKey, FsMaterializationOptions,
+        FsNodeId, FsRootComponent, FsTree,
+    },
+    theme::Theme,
     ui::DescendentColorRange,
 };
-use bevy::{prelude::*, sprite::Anchor};
-use grouping_by::GroupingBy;
+use bevy::{ecs::query::WorldQuery, prelude::*, sprite::Anchor};
+use std::{collections::HashMap, f32::consts::PI, fmt, str::FromStr};
 use tracing::debug;
 use valuable::Valuable;
 
-const ROOT_COLOR: Color = Color::rgba(0.097, 0.104, 0.120, 0.0);
-const FILE_COLOR: Color = Color::rgb(0.502, 0.502, 0.502);
 const TRANSPARENT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.0);
-const _SMALL_SLICE_COLOR: Color = Color::rgb(0.231, 0.240, 0.263);
 const LAYER_HEIGHT: f32 = 36.0;
-const GAP_WIDTH: f32 = 0.5;
+/// Left-to-right layers are wide, to leave room for long names
+const HORIZONTAL_LAYER_WIDTH: f32 = 180.0;
+pub(crate) const GAP_WIDTH: f32 = 0.5;
 
 const MIN_CHILD_WIDTH: f32 = 1.0;
-const MIN_CHILD_WIDTH_WITH_GAP: f32 = MIN_CHILD_WIDTH + GAP_WIDTH;
+pub(crate) const MIN_CHILD_WIDTH_WITH_GAP: f32 = MIN_CHILD_WIDTH + GAP_WIDTH;
+/// The hover overlay is drawn above the blocks and their labels
+pub(crate) const HOVER_Z: f32 = 2.0;
+/// The focus overlay is drawn above the hover overlay
+const FOCUS_Z: f32 = HOVER_Z + 0.1;
+
+/// The deepest that `max_depth` can be set, with the keyboard or on the command line
+pub const MAX_DEPTH_LIMIT: u16 = 64;
 
+#[derive(Clone, Debug)]
 pub struct DiskUsageTreeOptions {
-    max_depth: u16,
+    /// The number of levels drawn above the layout root
+    pub max_depth: u16,
+    /// When set, `max_depth` is kept at the number of levels that fit in the window
+    pub is_depth_adaptive: bool,
+    pub layout: TreeViewLayout,
+    pub orientation: TreeViewOrientation,
 }
 
 impl Default for DiskUsageTreeOptions {
     fn default() -> Self {
-        Self { max_depth: 6 }
+        Self {
+            max_depth: 6,
+            is_depth_adaptive: false,
+            layout: TreeViewLayout::Icicle,
+            orientation: TreeViewOrientation::BottomUp,
+        }
+    }
+}
+
+/// How the tree is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeViewLayout {
+    /// Depth maps to rows, and size to width
+    Icicle,
+    /// Depth maps to rings around the layout root, and size to angle
+    Sunburst,
+    /// Directories are nested rectangles, with their names in a header, and size maps
+    /// to area
+    Treemap,
+}
+
+impl TreeViewLayout {
+    const ALL: [TreeViewLayout; 3] = [
+        TreeViewLayout::Icicle,
+        TreeViewLayout::Sunburst,
+        TreeViewLayout::Treemap,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            TreeViewLayout::Icicle => "icicle",
+            TreeViewLayout::Sunburst => "sunburst",
+            TreeViewLayout::Treemap => "treemap",
+        }
+    }
+
+    fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|layout| layout == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for TreeViewLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TreeViewLayout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Which way an icicle grows from its root
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeViewOrientation {
+    /// The root along the top, with children below their parents
+    TopDown,
+    /// The root along the bottom, with children above their parents, like a flame graph
+    BottomUp,
+    /// The root down the left side, with children to the right of their parents
+    LeftToRight,
+}
+
+impl TreeViewOrientation {
+    const ALL: [TreeViewOrientation; 3] = [
+        TreeViewOrientation::TopDown,
+        TreeViewOrientation::BottomUp,
+        TreeViewOrientation::LeftToRight,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            TreeViewOrientation::TopDown => "icicle",
+            TreeViewOrientation::BottomUp => "flame",
+            TreeViewOrientation::LeftToRight => "horizontal",
+        }
+    }
+
+    fn next(&self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|orientation| orientation == self)
+            .unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Where the layout root is drawn in a window of `window_size`
+    fn root_rect(&self, window_size: Vec2) -> LayoutRect {
+        let left = -window_size.x / 2.0;
+        let bottom = -window_size.y / 2.0;
+        match self {
+            TreeViewOrientation::TopDown => LayoutRect {
+                x: left + window_size.x / 10.0,
+                y: -bottom - window_size.y / 4.0 - LAYER_HEIGHT,
+                w: window_size.x - window_size.x / 5.0,
+                h: LAYER_HEIGHT,
+            },
+            TreeViewOrientation::BottomUp => LayoutRect {
+                x: left + window_size.x / 10.0,
+                y: bottom + window_size.y / 4.0,
+                w: window_size.x - window_size.x / 5.0,
+                h: LAYER_HEIGHT,
+            },
+            TreeViewOrientation::LeftToRight => LayoutRect {
+                x: left + window_size.x / 20.0,
+                y: bottom + window_size.y / 10.0,
+                w: HORIZONTAL_LAYER_WIDTH,
+                h: window_size.y - window_size.y / 5.0,
+            },
+        }
+    }
+
+    /// The number of levels that fit between the root and the edge of the window that
+    /// the tree grows toward
+    fn levels_that_fit(&self, window_size: Vec2) -> u16 {
+        let root = self.root_rect(window_size);
+        let (space_beyond_root, layer_thickness) = match self {
+            TreeViewOrientation::TopDown => (root.y + window_size.y / 2.0, root.h),
+            TreeViewOrientation::BottomUp => {
+                (window_size.y / 2.0 - (root.y + root.h), root.h)
+            }
+            TreeViewOrientation::LeftToRight => {
+                (window_size.x / 2.0 - (root.x + root.w), root.w)
+            }
+        };
+        let levels = (space_beyond_root / (layer_thickness + GAP_WIDTH)).floor();
+        (levels.max(1.0) as u16).min(MAX_DEPTH_LIMIT)
+    }
+
+    /// The viewport that [`layout_icicle`] lays out a tree rooted at `root` in. Levels
+    /// grow upward from the origin, and [`Self::place`] maps them onto the screen.
+    fn layout_viewport(&self, root: &LayoutRect) -> LayoutRect {
+        let (w, h) = match self {
+            TreeViewOrientation::LeftToRight => (root.h, root.w),
+            _ => (root.w, root.h),
+        };
+        LayoutRect {
+            x: 0.0,
+            y: 0.0,
+            w,
+            h,
+        }
+    }
+
+    /// Maps a rect from the [`Self::layout_viewport`] onto the screen, where the root is
+    /// drawn at `root`. Siblings run left to right, or top to bottom.
+    fn place(&self, rect: &LayoutRect, root: &LayoutRect) -> LayoutRect {
+        match self {
+            TreeViewOrientation::TopDown => LayoutRect {
+                x: root.x + rect.x,
+                y: root.y - rect.y,
+                w: rect.w,
+                h: rect.h,
+            },
+            TreeViewOrientation::BottomUp => LayoutRect {
+                x: root.x + rect.x,
+                y: root.y + rect.y,
+                w: rect.w,
+                h: rect.h,
+            },
+            TreeViewOrientation::LeftToRight => LayoutRect {
+                x: root.x + rect.y,
+                y: root.y + root.h - rect.x - rect.w,
+                w: rect.h,
+                h: rect.w,
+            },
+        }
+    }
+
+    /// How far along the breadth of `root` that `point` is, as a fraction of it. Siblings
+    /// run from 0 to 1.
+    pub(crate) fn breadth_fraction(&self, point: Vec2, root: &LayoutRect) -> f32 {
+        let first_edge = match self {
+            TreeViewOrientation::LeftToRight => Vec2::new(root.x, root.y + root.h),
+            _ => Vec2::new(root.x, root.y),
+        };
+        self.offset_breadth_fraction(point - first_edge, root)
+    }
+
+    /// How far `offset` goes along the breadth of `root`, as a fraction of it
+    pub(crate) fn offset_breadth_fraction(&self, offset: Vec2, root: &LayoutRect) -> f32 {
+        match self {
+            TreeViewOrientation::LeftToRight => -offset.y / root.h,
+            _ => offset.x / root.w,
+        }
+    }
+
+    /// Maps every rect of `layout` onto the screen (see [`Self::place`])
+    fn place_layout(&self, layout: &mut IcicleLayout<Entity>, root: &LayoutRect) {
+        for node in layout.nodes.iter_mut() {
+            node.rect = self.place(&node.rect, root);
+        }
+        for hidden in layout.hidden_children.iter_mut() {
+            if let Some(group_rect) = hidden.group_rect.as_mut() {
+                *group_rect = self.place(group_rect, root);
+            }
+        }
+    }
+}
+
+impl fmt::Display for TreeViewOrientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TreeViewOrientation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|orientation| orientation.name() == s)
+            .ok_or(())
     }
 }
 
 #[derive(Component)]
-struct DiskUsageTreeViewTransformRoot;
+pub(crate) struct DiskUsageTreeViewTransformRoot;
 
 pub struct DiskUsageTreeViewPlugin;
 impl Plugin for DiskUsageTreeViewPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<DiskUsageTreeOptions>()
+            .init_resource::<TreeViewZoom>()
+            .init_resource::<TreeSearch>()
+            .init_resource::<SmallItemsGroups>()
+            .init_resource::<ColorScheme>()
             .add_startup_system(create_transform_root)
-            .add_system(scale_transform_root_to_window)
+            .add_system_to_stage(CoreStage::PreUpdate, sync_materialization_options)
+            .add_system(scale_transform_root_to_window.after(adjust_tree_options_on_keys))
+            .add_system(adjust_tree_options_on_keys)
+            .add_system(fit_max_depth_to_window.after(adjust_tree_options_on_keys))
             .add_system_to_stage(CoreStage::PreUpdate, initialize_fs_root_entity_sprite)
             .add_system_to_stage(CoreStage::PreUpdate, initialize_fs_entity_sprites)
             .add_system(handle_hover)
-            .add_system(invalidate_tree_from_root)
-            .add_system(update_hover_sprite.after(invalidate_tree_from_root));
+            .add_system(invalidate_tree_from_root.after(scale_transform_root_to_window))
+            .add_system(update_hover_sprite.after(invalidate_tree_from_root))
+            .add_system(update_focus_sprite.after(invalidate_tree_from_root))
+            .add_system(sync_sunburst_segments.after(invalidate_tree_from_root))
+            // Fs entities are despawned as late as `CoreStage::PostUpdate`, and their
+            // removal is only visible until the frame ends
+            .add_system_to_stage(CoreStage::Last, despawn_orphaned_segments)
+            .add_system(sync_small_items_groups.after(invalidate_tree_from_root))
+            .add_system(apply_theme_to_overlays);
+    }
+}
+
+/// Only entities that can be drawn need to be materialized from the fs tree. That's
+/// everything within `max_depth` levels of the root, and of the zoomed in layout root,
+/// that's big enough to be drawn as a block of its own.
+#[allow(clippy::too_many_arguments)]
+fn sync_materialization_options(
+    tree_options: Res<DiskUsageTreeOptions>,
+    zoom: Res<TreeViewZoom>,
+    tree_viewport: Res<TreeViewViewport>,
+    window_size: Res<WindowSize>,
+    fs_tree: Res<FsTree>,
+    fs_entity_query: Query<&FsEntityComponent>,
+    mut materialization_options: ResMut<FsMaterializationOptions>,
+) {
+    if !tree_options.is_changed()
+        && !zoom.is_changed()
+        && !tree_viewport.is_changed()
+        && !window_size.is_changed()
+    {
+        return;
+    }
+
+    let expanded_root = zoom
+        .layout_root()
+        .and_then(|layout_root| fs_entity_query.get(layout_root).ok())
+        .map(|fs_entity| fs_entity.node_id);
+    let mut min_size_fraction =
+        min_visible_size_fraction(&tree_options, window_size.0, &tree_viewport);
+    // Expanded small items fill the layout root's breadth on their own
+    if zoom.small_items().is_some() && !fs_tree.is_empty() {
+        let layout_root_size =
+            fs_tree.aggregate_size(expanded_root.unwrap_or(FsNodeId::ROOT));
+        let small_items_size: u64 = zoom
+            .small_item_nodes()
+            .iter()
+            .filter(|node_id| fs_tree.contains(**node_id))
+            .map(|node_id| fs_tree.aggregate_size(*node_id))
+            .sum();
+        if layout_root_size > 0 {
+            min_size_fraction *= small_items_size as f64 / layout_root_size as f64;
+        }
+    }
+
+    if materialization_options.max_depth != tree_options.max_depth
+        || materialization_options.expanded_root != expanded_root
+        || materialization_options.min_size_fraction != min_size_fraction
+    {
+        materialization_options.max_depth = tree_options.max_depth;
+        materialization_options.expanded_root = expanded_root;
+        materialization_options.min_size_fraction = min_size_fraction;
+    }
+}
+
+/// The smallest share of the layout root's size that can be drawn as a block in the
+/// current layout. A block's breadth is at most its share of the whole layout's breadth,
+/// so this errs on the side of materializing too much.
+fn min_visible_size_fraction(
+    tree_options: &DiskUsageTreeOptions,
+    window_size: Vec2,
+    tree_viewport: &TreeViewViewport,
+) -> f64 {
+    let min_w = MIN_CHILD_WIDTH_WITH_GAP;
+    let (visible, total_breadth) = match tree_options.layout {
+        TreeViewLayout::Icicle => {
+            let orientation = tree_options.orientation;
+            let root = orientation.root_rect(window_size);
+            (
+                min_w,
+                orientation.layout_viewport(&root).w * tree_viewport.scale(),
+            )
+        }
+        // No ring is longer than the window's inscribed circle
+        TreeViewLayout::Sunburst => (min_w, PI * window_size.min_element()),
+        // Blocks have to be wide enough both ways, and can't outgrow the window
+        TreeViewLayout::Treemap => (min_w * min_w, window_size.x * window_size.y),
+    };
+    if total_breadth > 0.0 {
+        (visible / total_breadth) as f64
+    } else {
+        0.0
     }
 }
 
 /// Creates a set of transforms that acts as the root of all sprites drawn by this graph
-fn create_transform_root(mut commands: Commands, window_size: Res<WindowSize>) {
+fn create_transform_root(
+    mut commands: Commands,
+    window_size: Res<WindowSize>,
+    tree_options: Res<DiskUsageTreeOptions>,
+    theme: Res<Theme>,
+) {
     let window_size = window_size.0;
-    let transform = root_transform_for_window_size(window_size);
+    let transform = root_transform_for_window_size(window_size, tree_options.orientation);
 
     debug!(
         window_size = ?window_size,
@@ -61,17 +417,12 @@ fn create_transform_root(mut commands: Commands, window_size: Res<WindowSize>) {
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
-                color: Color::Rgba {
-                    red: 0.0,
-                    green: 0.0,
-                    blue: 0.0,
-                    alpha: 0.36,
-                },
+                color: theme.hover_overlay,
                 anchor: Anchor::BottomLeft,
                 ..default()
             },
             transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 2.0),
+                translation: Vec3::new(0.0, 0.0, HOVER_Z),
                 scale: Vec3::new(300.0, 300.0, 1.0),
                 ..default()
             },
@@ -80,6 +431,23 @@ fn create_transform_root(mut commands: Commands, window_size: Res<WindowSize>) {
         })
         .insert(HoverSprite(None));
 
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: theme.focus_overlay,
+                anchor: Anchor::BottomLeft,
+                ..default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, FOCUS_Z),
+                scale: Vec3::new(300.0, 300.0, 1.0),
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(FocusSprite(None));
+
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
@@ -93,18 +461,27 @@ fn create_transform_root(mut commands: Commands, window_size: Res<WindowSize>) {
         .insert(DiskUsageTreeViewTransformRoot);
 }
 
-/// Keeps the transform root sized with the window
+/// Keeps the transform root sized with the window, and placed for the orientation
 fn scale_transform_root_to_window(
     mut transform_root_query: Query<&mut Transform, With<DiskUsageTreeViewTransformRoot>>,
     window_size: Res<WindowSize>,
+    tree_options: Res<DiskUsageTreeOptions>,
 ) {
-    if !transform_root_query.is_empty() && window_size.is_changed() {
+    if !transform_root_query.is_empty()
+        && (window_size.is_changed() || tree_options.is_changed())
+    {
         let mut transform = transform_root_query.single_mut();
         let window_size = window_size.0;
-        *transform = root_transform_for_window_size(window_size);
+        let new_transform =
+            root_transform_for_window_size(window_size, tree_options.orientation);
+        if *transform == new_transform {
+            return;
+        }
+        *transform = new_transform;
 
         debug!(
             window_size = window_size.to_array().as_value(),
+            orientation = %tree_options.orientation,
             translation = transform.translation.to_array().as_value(),
             scale = transform.scale.to_array().as_value(),
             "transform root resized to reflect new window size"
@@ -112,11 +489,104 @@ fn scale_transform_root_to_window(
     }
 }
 
-fn root_transform_for_window_size(window_size: Vec2) -> Transform {
+/// Whether a modifier is held, which makes a letter key part of a chord rather than a
+/// binding of its own
+pub(crate) fn is_modifier_pressed(keys: &Input<KeyCode>) -> bool {
+    keys.any_pressed([
+        KeyCode::LControl,
+        KeyCode::RControl,
+        KeyCode::LShift,
+        KeyCode::RShift,
+        KeyCode::LAlt,
+        KeyCode::RAlt,
+    ])
+}
+
+/// The keyboard shortcuts for tree options: + and - add and remove levels, A toggles
+/// adaptive depth, L switches layouts, O switches orientations, and C and P switch color
+/// modes and palettes. The letters are ignored in chords, such as Ctrl+Shift+A.
+fn adjust_tree_options_on_keys(
+    keys: Res<Input<KeyCode>>,
+    search: Res<TreeSearch>,
+    mut tree_options: ResMut<DiskUsageTreeOptions>,
+    mut color_scheme: ResMut<ColorScheme>,
+) {
+    if search.is_active() {
+        return;
+    }
+
+    let max_depth = tree_options.max_depth;
+    if keys.any_just_pressed([KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]) {
+        tree_options.is_depth_adaptive = false;
+        tree_options.max_depth = max_depth.saturating_add(1).min(MAX_DEPTH_LIMIT);
+    }
+    if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        tree_options.is_depth_adaptive = false;
+        tree_options.max_depth = max_depth.saturating_sub(1).max(1);
+    }
+    if !is_modifier_pressed(&keys) {
+        if keys.just_pressed(KeyCode::A) {
+            tree_options.is_depth_adaptive = !tree_options.is_depth_adaptive;
+        }
+        if keys.just_pressed(KeyCode::L) {
+            tree_options.layout = tree_options.layout.next();
+        }
+        if keys.just_pressed(KeyCode::O) {
+            tree_options.orientation = tree_options.orientation.next();
+        }
+        if keys.just_pressed(KeyCode::C) {
+            color_scheme.next_mode();
+        }
+        if keys.just_pressed(KeyCode::P) {
+            color_scheme.next_palette();
+        }
+    }
+
+    if tree_options.is_changed() {
+        info!(
+            max_depth = tree_options.max_depth,
+            is_depth_adaptive = tree_options.is_depth_adaptive,
+            layout = %tree_options.layout,
+            orientation = %tree_options.orientation,
+            "tree options changed"
+        );
+    }
+    if color_scheme.is_changed() {
+        let palette = color_scheme.palette();
+        info!(
+            mode = %color_scheme.mode,
+            palette = palette.name,
+            is_colorblind_safe = palette.is_colorblind_safe,
+            "color scheme changed"
+        );
+    }
+}
+
+/// In adaptive mode, shows as many levels as fit beyond the root
+fn fit_max_depth_to_window(
+    window_size: Res<WindowSize>,
+    mut tree_options: ResMut<DiskUsageTreeOptions>,
+) {
+    if !tree_options.is_depth_adaptive
+        || !(window_size.is_changed() || tree_options.is_changed())
+    {
+        return;
+    }
+
+    let max_depth = tree_options.orientation.levels_that_fit(window_size.0);
+    if tree_options.max_depth != max_depth {
+        tree_options.max_depth = max_depth;
+    }
+}
+
+fn root_transform_for_window_size(
+    window_size: Vec2,
+    orientation: TreeViewOrientation,
+) -> Transform {
+    let root_rect = orientation.root_rect(window_size);
     Transform {
-        translation: (window_size / -2.0).extend(0.0)
-            + Vec3::new(window_size.x / 10.0, window_size.y / 4.0, 0.0),
-        scale: Vec3::new(window_size.x - window_size.x / 5.0, LAYER_HEIGHT, 1.0),
+        translation: Vec3::new(root_rect.x, root_rect.y, 0.0),
+        scale: Vec3::new(root_rect.w, root_rect.h, 1.0),
         ..default()
     }
 }
@@ -132,6 +602,7 @@ fn initialize_fs_root_entity_sprite(
         Entity,
         (With<DiskUsageTreeViewTransformRoot>, Without<Children>),
     >,
+    theme: Res<Theme>,
 ) {
     if transform_root_query.is_empty() || fs_root_query.is_empty() {
         return;
@@ -144,7 +615,7 @@ fn initialize_fs_root_entity_sprite(
         .entity(fs_root)
         .insert_bundle(SpriteBundle {
             sprite: Sprite {
-                color: ROOT_COLOR,
+                color: theme.root,
                 anchor: Anchor::BottomLeft,
                 ..default()
             },
@@ -159,6 +630,8 @@ fn initialize_fs_root_entity_sprite(
             debug_tag: fs_root_key.to_string(),
             ..default()
         })
+        .insert(HoverShape::default())
+        .insert(SunburstSector::default())
         .insert(DescendentColorRange::default());
 
     // Add it to the transform root
@@ -176,26 +649,16 @@ fn initialize_fs_entity_sprites(
             Added<Parent>,
         ),
     >,
-    options: Res<DiskUsageTreeOptions>,
+    theme: Res<Theme>,
 ) {
     for (entity, fs_key, fs_entity) in new_parented_fs_entities_query.iter() {
-        if fs_entity.depth > options.max_depth {
-            debug!(
-                key = fs_key.as_value(),
-                depth = fs_entity.depth,
-                max_depth = options.max_depth,
-                "path is beyond max depth"
-            );
-            continue;
-        }
-
         debug!(key = fs_key.as_value(), "creating sprite");
         let mut entity_commands = commands.entity(entity);
         entity_commands
             .insert_bundle(SpriteBundle {
                 sprite: Sprite {
                     color: if !fs_entity.is_dir() {
-                        FILE_COLOR
+                        theme.file
                     } else {
                         TRANSPARENT_COLOR
                     },
@@ -212,7 +675,9 @@ fn initialize_fs_entity_sprites(
             .insert(Hoverable {
                 debug_tag: fs_key.to_string(),
                 ..default()
-            });
+            })
+            .insert(HoverShape::default())
+            .insert(SunburstSector::default());
         if fs_entity.is_dir() {
             entity_commands.insert(DescendentColorRange::default());
         }
@@ -231,7 +696,7 @@ fn handle_hover(
             ChangeTrackers<Hoverable>,
         ),
         (
-            With<FsEntityComponent>,
+            Or<(With<FsEntityComponent>, With<SmallItemsGroup>)>,
             Without<FsRootComponent>,
             Changed<Hoverable>,
         ),
@@ -276,7 +741,7 @@ fn handle_hover(
             hover_vis.is_visible = true;
             hover_transform.scale = transform.scale;
             hover_transform.translation =
-                Vec3::new(transform.translation.x, transform.translation.y, 2.0);
+                Vec3::new(transform.translation.x, transform.translation.y, HOVER_Z);
         }
     }
 
@@ -288,32 +753,99 @@ fn handle_hover(
 }
 
 fn update_hover_sprite(
-    mut hover_sprite_query: Query<(&HoverSprite, &mut Transform, &mut Visibility)>,
+    mut hover_sprite_query: Query<(&mut HoverSprite, &mut Transform, &mut Visibility)>,
     hoverable_transforms: Query<
         &GlobalTransform,
-        (With<FsEntityComponent>, Without<FsRootComponent>),
+        (
+            Or<(With<FsEntityComponent>, With<SmallItemsGroup>)>,
+            Without<FsRootComponent>,
+        ),
     >,
 ) {
     if hover_sprite_query.is_empty() {
         return;
     }
 
-    let (hover_sprite, mut hover_transform, mut hover_vis) =
+    let (mut hover_sprite, mut hover_transform, mut hover_vis) =
         hover_sprite_query.single_mut();
-    if hover_sprite.is_none() {
-        return;
-    }
+    let hovered = match hover_sprite.0 {
+        Some(hovered) => hovered,
+        None => return,
+    };
 
-    let global_transform = hoverable_transforms.get(hover_sprite.0.unwrap()).unwrap();
+    // The hovered block is gone if its part of the tree was removed
+    let global_transform = match hoverable_transforms.get(hovered) {
+        Ok(global_transform) => global_transform,
+        Err(_) => {
+            hover_sprite.0 = None;
+            hover_vis.is_visible = false;
+            return;
+        }
+    };
     hover_vis.is_visible = true;
     hover_transform.scale = global_transform.scale;
     hover_transform.translation = Vec3::new(
         global_transform.translation.x,
         global_transform.translation.y,
-        2.0,
+        HOVER_Z,
     );
 }
 
+/// Recolors the hover and focus highlights when the theme changes
+fn apply_theme_to_overlays(
+    theme: Res<Theme>,
+    mut hover_sprite_query: Query<&mut Sprite, (With<HoverSprite>, Without<FocusSprite>)>,
+    mut focus_sprite_query: Query<&mut Sprite, (With<FocusSprite>, Without<HoverSprite>)>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for mut sprite in hover_sprite_query.iter_mut() {
+        sprite.color = theme.hover_overlay;
+    }
+    for mut sprite in focus_sprite_query.iter_mut() {
+        sprite.color = theme.focus_overlay;
+    }
+}
+
+/// Highlights the keyboard focus, the same way that [`HoverSprite`] highlights the
+/// hovered node
+#[derive(Component, Deref, DerefMut)]
+pub(crate) struct FocusSprite(pub Option<Entity>);
+
+fn update_focus_sprite(
+    mut focus_sprite_query: Query<(&FocusSprite, &mut Transform, &mut Visibility)>,
+    fs_entity_transforms: Query<
+        (&GlobalTransform, &Visibility),
+        (With<FsEntityComponent>, Without<FocusSprite>),
+    >,
+) {
+    if focus_sprite_query.is_empty() {
+        return;
+    }
+
+    let (focus_sprite, mut focus_transform, mut focus_vis) =
+        focus_sprite_query.single_mut();
+    let focused = focus_sprite.and_then(|entity| fs_entity_transforms.get(entity).ok());
+    match focused {
+        Some((global_transform, vis)) if vis.is_visible => {
+            focus_vis.is_visible = true;
+            focus_transform.scale = global_transform.scale;
+            focus_transform.translation = Vec3::new(
+                global_transform.translation.x,
+                global_transform.translation.y,
+                FOCUS_Z,
+            );
+        }
+        _ => {
+            if focus_vis.is_visible {
+                focus_vis.is_visible = false;
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn invalidate_tree_from_root(
     fs_root_query: Query<
         (Entity, Or<(Changed<FsAggregateSize>, Changed<Children>)>),
@@ -339,16 +871,21 @@ fn invalidate_tree_from_root(
             Without<DiskUsageTreeViewTransformRoot>,
         ),
     >,
+    fs_parent_query: Query<&Parent, (With<FsEntityComponent>, Without<FsRootComponent>)>,
+    mut sunburst_query: Query<(&mut SunburstSector, &mut HoverShape)>,
+    mut small_items_groups: ResMut<SmallItemsGroups>,
+    new_blocks_query: Query<(), (With<FsEntityComponent>, Added<Hoverable>)>,
     transform_root_changed_query: Query<
         (&Transform, Changed<Transform>),
         With<DiskUsageTreeViewTransformRoot>,
     >,
     tree_options: Res<DiskUsageTreeOptions>,
-    // These two values are initialized to their defaults by Local, and remain empty. We use these
-    // as error fallbacks. Note that we've had to use a static lifetime for
-    // `default_entity_ref_vec`, which is fine because it contains no entity refs.
-    default_children_iter: Local<Children>,
-    default_entity_ref_vec: Local<Vec<(&'static Entity, f32, f32)>>,
+    zoom: Res<TreeViewZoom>,
+    tree_viewport: Res<TreeViewViewport>,
+    window_size: Res<WindowSize>,
+    fs_tree: Res<FsTree>,
+    color_scheme: Res<ColorScheme>,
+    theme: Res<Theme>,
 ) {
     let fs_root_res = fs_root_query.get_single();
     let transform_root_changed_res = transform_root_changed_query.get_single();
@@ -366,7 +903,15 @@ fn invalidate_tree_from_root(
         ) => (
             fs_root,
             root_transform,
-            fs_root_changed || root_transform_changed,
+            fs_root_changed
+                || root_transform_changed
+                || zoom.is_changed()
+                || tree_viewport.is_changed()
+                || tree_options.is_changed()
+                || color_scheme.is_changed()
+                || theme.is_changed()
+                // Zooming materializes deeper entities, which don't change any sizes
+                || !new_blocks_query.is_empty(),
             fs_root_changed,
             root_transform_changed,
         ),
@@ -381,26 +926,224 @@ fn invalidate_tree_from_root(
         debug!(
             fs_root_changed = fs_root_changed,
             root_transform_changed = root_transform_changed,
+            zoom_changed = zoom.is_changed(),
             "disk usage tree visuals invalidated"
         );
 
         let root_global_transform: GlobalTransform = (*root_transform).into();
-        invalidate_subtree_recursive(
-            &root_global_transform,
-            &fs_root,
-            &fs_entity_details_query,
-            &mut fs_entity_mutable_details_query,
-            &tree_options,
-            &default_children_iter,
-            &default_entity_ref_vec,
-        );
+        let colorer = NodeColorer {
+            scheme: &color_scheme,
+            theme: &theme,
+            fs_tree: &fs_tree,
+        };
+        let layout_root = zoom
+            .layout_root()
+            .filter(|layout_root| fs_entity_details_query.contains(*layout_root))
+            .unwrap_or(fs_root);
+
+        if layout_root == fs_root {
+            let (mut transform, mut global_transform, mut sprite, mut vis, _) =
+                fs_entity_mutable_details_query.get_mut(fs_root).unwrap();
+            *transform = Transform::identity();
+            *global_transform = root_global_transform;
+            sprite.color = theme.root;
+            vis.is_visible = true;
+        } else {
+            zoom_into_layout_root(
+                &root_global_transform,
+                &layout_root,
+                &fs_entity_details_query,
+                &mut fs_entity_mutable_details_query,
+                &fs_parent_query,
+                &colorer,
+            );
+        }
+
+        let layout_root_depth = fs_entity_details_query
+            .get_component::<FsEntityComponent>(layout_root)
+            .unwrap()
+            .depth;
+        let max_depth = layout_root_depth.saturating_add(tree_options.max_depth);
+        let tree = FsLayoutTree(&fs_entity_details_query, &fs_tree);
+        // Expanded small items are laid out as if they were the layout root's only
+        // children
+        let small_items: Option<Vec<Entity>> = zoom
+            .small_items()
+            .filter(|_| zoom.layout_root().unwrap_or(fs_root) == layout_root)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter(|item| fs_entity_details_query.contains(**item))
+                    .copied()
+                    .collect()
+            });
+        match tree_options.layout {
+            TreeViewLayout::Icicle => {
+                clear_sunburst_sectors(&mut sunburst_query);
+                let root_color_range = fs_entity_mutable_details_query
+                    .get_component::<DescendentColorRange>(layout_root)
+                    .map_or_else(|_| DescendentColorRange::default(), |range| *range);
+                let root_rect = LayoutRect {
+                    x: root_global_transform.translation.x,
+                    y: root_global_transform.translation.y,
+                    w: root_global_transform.scale.x,
+                    h: root_global_transform.scale.y,
+                };
+                let orientation = tree_options.orientation;
+                let unmagnified_viewport = orientation.layout_viewport(&root_rect);
+                let viewport = tree_viewport.magnified(&unmagnified_viewport);
+                let params = IcicleParams {
+                    gap_width: GAP_WIDTH,
+                    min_child_width_with_gap: MIN_CHILD_WIDTH_WITH_GAP,
+                    max_levels: tree_options.max_depth,
+                    visible_min_x: unmagnified_viewport.x,
+                    visible_max_x: unmagnified_viewport.x + unmagnified_viewport.w,
+                };
+
+                let mut layout = match &small_items {
+                    Some(small_items) => {
+                        hide_children_outside_small_items(
+                            &tree,
+                            layout_root,
+                            small_items,
+                            &fs_entity_details_query,
+                            &mut fs_entity_mutable_details_query,
+                        );
+                        let subset = RootChildrenSubset {
+                            tree: &tree,
+                            root: layout_root,
+                            children: small_items,
+                        };
+                        layout_icicle(
+                            &subset,
+                            layout_root,
+                            viewport,
+                            root_color_range,
+                            &params,
+                        )
+                    }
+                    None => layout_icicle(
+                        &tree,
+                        layout_root,
+                        viewport,
+                        root_color_range,
+                        &params,
+                    ),
+                };
+                orientation.place_layout(&mut layout, &root_rect);
+                apply_icicle_layout(
+                    &layout,
+                    &root_global_transform,
+                    &fs_entity_details_query,
+                    &mut fs_entity_mutable_details_query,
+                    &colorer,
+                );
+                small_items_groups.0 = layout
+                    .hidden_children
+                    .into_iter()
+                    .filter_map(|hidden| {
+                        let rect = hidden.group_rect?;
+                        Some(PlacedSmallItemsGroup {
+                            hidden,
+                            shape: SmallItemsGroupShape::Rect(rect),
+                        })
+                    })
+                    .collect();
+            }
+            TreeViewLayout::Sunburst => {
+                // The layout root's sprite is collapsed along with the rest, and drawn as
+                // the disk in the middle
+                fs_entity_mutable_details_query
+                    .get_component_mut::<Transform>(layout_root)
+                    .unwrap()
+                    .scale
+                    .x = 0.0;
+                let geometry = SunburstGeometry::for_window_size(
+                    window_size.0,
+                    tree_options.max_depth,
+                );
+                let center_sector =
+                    place_sunburst_center(&geometry, layout_root, &mut sunburst_query);
+                let mut groups = vec![];
+                match &small_items {
+                    Some(small_items) => {
+                        hide_children_outside_small_items(
+                            &tree,
+                            layout_root,
+                            small_items,
+                            &fs_entity_details_query,
+                            &mut fs_entity_mutable_details_query,
+                        );
+                        invalidate_sunburst_recursive(
+                            &RootChildrenSubset {
+                                tree: &tree,
+                                root: layout_root,
+                                children: small_items,
+                            },
+                            &geometry,
+                            layout_root,
+                            &center_sector,
+                            0,
+                            max_depth,
+                            layout_root_depth,
+                            &fs_entity_details_query,
+                            &mut fs_entity_mutable_details_query,
+                            &mut sunburst_query,
+                            &colorer,
+                            &mut groups,
+                        );
+                    }
+                    None => invalidate_sunburst_recursive(
+                        &tree,
+                        &geometry,
+                        layout_root,
+                        &center_sector,
+                        0,
+                        max_depth,
+                        layout_root_depth,
+                        &fs_entity_details_query,
+                        &mut fs_entity_mutable_details_query,
+                        &mut sunburst_query,
+                        &colorer,
+                        &mut groups,
+                    ),
+                }
+                small_items_groups.0 = groups;
+            }
+            TreeViewLayout::Treemap => {
+                clear_sunburst_sectors(&mut sunburst_query);
+                clear_small_items_groups(&mut small_items_groups);
+                let viewport = TreemapRect::for_window_size(window_size.0);
+                let layout_root_global_transform = {
+                    let (mut transform, mut global_transform, _, _, _) =
+                        fs_entity_mutable_details_query
+                            .get_mut(layout_root)
+                            .unwrap();
+                    // The layout root's ancestors are collapsed onto the transform root
+                    *transform = viewport.local_transform(&root_global_transform);
+                    *global_transform = root_global_transform.mul_transform(*transform);
+                    *global_transform
+                };
+                invalidate_treemap_recursive(
+                    &layout_root_global_transform,
+                    layout_root,
+                    layout_root_depth,
+                    max_depth,
+                    &fs_entity_details_query,
+                    &mut fs_entity_mutable_details_query,
+                    &colorer,
+                );
+            }
+        }
     }
 }
 
-/// This is not a system — it is invoked
-fn invalidate_subtree_recursive(
-    parent_global_transform: &GlobalTransform,
-    fs_parent: &Entity,
+/// Places `layout_root` where the fs root is normally drawn, by collapsing its ancestors'
+/// transforms onto the transform root. The ancestors, and every subtree off the path to
+/// the layout root, are hidden.
+fn zoom_into_layout_root(
+    root_global_transform: &GlobalTransform,
+    layout_root: &Entity,
     fs_entity_details_query: &Query<(
         &FsEntityKey,
         &FsEntityComponent,
@@ -409,7 +1152,6 @@ fn invalidate_subtree_recursive(
     )>,
     fs_entity_mutable_details_query: &mut Query<
         (
-            // Styling
             &mut Transform,
             &mut GlobalTransform,
             &mut Sprite,
@@ -421,208 +1163,211 @@ fn invalidate_subtree_recursive(
             Without<DiskUsageTreeViewTransformRoot>,
         ),
     >,
-    tree_options: &Res<DiskUsageTreeOptions>,
-    default_children_iter: &Local<Children>,
-    default_entity_ref_vec: &Local<Vec<(&'static Entity, f32, f32)>>,
+    fs_parent_query: &Query<&Parent, (With<FsEntityComponent>, Without<FsRootComponent>)>,
+    colorer: &NodeColorer,
 ) {
-    let (parent_fs_key, parent_fs_entity, parent_fs_size, maybe_children) =
-        fs_entity_details_query.get(*fs_parent).unwrap();
-    let maybe_parent_color_range: Option<DescendentColorRange> =
-        fs_entity_mutable_details_query
-            .get_component::<DescendentColorRange>(*fs_parent)
-            .ok()
-            .map(|rng| *rng); // This dereference returns the immutable borrow
-
-    if parent_fs_entity.depth >= tree_options.max_depth {
-        return;
+    {
+        let layout_root_fs = fs_entity_details_query
+            .get_component::<FsEntityComponent>(*layout_root)
+            .unwrap();
+        let (mut transform, mut global_transform, mut sprite, mut vis, maybe_color_range) =
+            fs_entity_mutable_details_query
+                .get_mut(*layout_root)
+                .unwrap();
+        *transform = Transform::identity();
+        *global_transform = *root_global_transform;
+        vis.is_visible = true;
+
+        // The layout root gets the full range of colors for its descendents
+        if let Some(mut color_range) = maybe_color_range {
+            *color_range = DescendentColorRange::default();
+            sprite.color = colorer.color(layout_root_fs.node_id, Some(&color_range), 0);
+        }
     }
 
-    debug!(
-        key = parent_fs_key.as_value(),
-        global.translation = ?parent_global_transform.translation,
-        global.scale = ?parent_global_transform.scale,
-        "invalidating subtree"
-    );
+    let mut path_child = *layout_root;
+    while let Ok(parent) = fs_parent_query.get(path_child) {
+        let ancestor = parent.0;
+        if !fs_entity_mutable_details_query.contains(ancestor) {
+            break;
+        }
 
-    // Determine the visibility of children. Any child whose coloured region is less than 1 logical
-    // pixel will not be displayed.
+        {
+            let (mut transform, mut global_transform, _, mut vis, _) =
+                fs_entity_mutable_details_query.get_mut(ancestor).unwrap();
+            *transform = Transform::identity();
+            *global_transform = *root_global_transform;
+            vis.is_visible = false;
+        }
 
-    let bytes_to_fractional_x =
-        |bytes: u64| bytes as f32 / parent_fs_size.size_in_bytes as f32;
-    let fractional_x_to_screen_x = |fractional_x: f32, total_screen_w: Option<f32>| {
-        fractional_x * total_screen_w.unwrap_or(parent_global_transform.scale.x)
-    };
-    let screen_x_to_fractional_x =
-        |screen_x: f32| screen_x / parent_global_transform.scale.x;
+        let siblings = fs_entity_details_query
+            .get_component::<Children>(ancestor)
+            .map(|children| children.to_vec())
+            .unwrap_or_default();
+        for sibling in siblings.iter().filter(|sibling| **sibling != path_child) {
+            hide_subtree_recursive(
+                sibling,
+                fs_entity_details_query,
+                fs_entity_mutable_details_query,
+            );
+        }
 
-    let children_by_visibility = maybe_children
-        .unwrap_or(default_children_iter)
-        .iter()
-        .filter(|child| fs_entity_details_query.contains(**child))
-        .map(|child| {
-            let child_fs_size = fs_entity_details_query
-                .get_component::<FsAggregateSize>(*child)
-                .unwrap();
-            let fractional_w = bytes_to_fractional_x(child_fs_size.size_in_bytes);
-            let screen_w = fractional_x_to_screen_x(fractional_w, None);
-            (child, fractional_w, screen_w)
-        })
-        .grouping_by(|(_child, _fractional_w, screen_w)| {
-            *screen_w >= MIN_CHILD_WIDTH_WITH_GAP
-        });
-    let visible_children = children_by_visibility
-        .get(&true)
-        .unwrap_or(default_entity_ref_vec);
-    let hidden_children = children_by_visibility
-        .get(&false)
-        .unwrap_or(default_entity_ref_vec);
-
-    // Determine whether we should include a synthetic child of the parent that groups together all
-    // the files too small to display.
-    let hidden_children_screen_w = hidden_children
-        .iter()
-        .map(|(_, _, screen_w)| *screen_w)
-        .sum::<f32>();
-    let use_group_for_hidden_children =
-        hidden_children_screen_w > MIN_CHILD_WIDTH_WITH_GAP;
-
-    // Calculate the number of gaps, and the unit proportion that will cut into the children's
-    // space
-    let number_of_gaps =
-        (visible_children.len() + use_group_for_hidden_children as usize).max(1) - 1;
-    let total_gap_screen_w = number_of_gaps as f32 * GAP_WIDTH;
-    let available_screen_w_minus_gaps =
-        parent_global_transform.scale.x - total_gap_screen_w;
+        path_child = ancestor;
+    }
+}
 
-    debug!(
-        visible_children_count = visible_children.len(),
-        number_of_gaps,
-        use_group_for_hidden_children,
-        hidden_children_screen_w,
-        total_gap_screen_w,
-        available_screen_w_minus_gaps,
-        "layout parameters determined"
-    );
+/// Hides the layout root's children that aren't among the expanded small items
+#[allow(clippy::type_complexity)]
+fn hide_children_outside_small_items<T: LayoutTree<Id = Entity>>(
+    tree: &T,
+    layout_root: Entity,
+    small_items: &[Entity],
+    fs_entity_details_query: &Query<(
+        &FsEntityKey,
+        &FsEntityComponent,
+        &FsAggregateSize,
+        Option<&Children>,
+    )>,
+    fs_entity_mutable_details_query: &mut Query<
+        (
+            &mut Transform,
+            &mut GlobalTransform,
+            &mut Sprite,
+            &mut Visibility,
+            Option<&mut DescendentColorRange>,
+        ),
+        (
+            With<FsEntityComponent>,
+            Without<DiskUsageTreeViewTransformRoot>,
+        ),
+    >,
+) {
+    for child in tree.children(layout_root) {
+        if !small_items.contains(&child) {
+            hide_subtree_recursive(
+                &child,
+                fs_entity_details_query,
+                fs_entity_mutable_details_query,
+            );
+        }
+    }
+}
 
-    let mut fractional_x = 0_f32; // Running child x coordinate, in the range [0..1]
-    let mut remainder_screen_w = 0_f32;
-
-    let maybe_last_visible_child = visible_children.last();
-    for (child, child_fractional_w, _) in visible_children {
-        // For some reason the grouping_by() operator maps to references of the elements, so we
-        // deref up front, shadowing the references
-        let (child, child_fractional_w) = (*child, *child_fractional_w);
-        let is_last = maybe_last_visible_child.unwrap().0 == child;
-
-        let (child_fs_key, child_fs, _, _) = fs_entity_details_query.get(*child).unwrap();
-        let (
-            mut child_transform,
-            _child_global_transform,
-            mut child_sprite,
-            mut child_vis,
-            maybe_child_color_range,
-        ) = fs_entity_mutable_details_query.get_mut(*child).unwrap();
-
-        // Set the child to visible
-        child_vis.is_visible = true;
-
-        // Update the child's position/size using fractional values
-        let child_screen_w_minus_gaps = fractional_x_to_screen_x(
-            child_fractional_w,
-            Some(available_screen_w_minus_gaps),
-        );
-        remainder_screen_w +=
-            child_screen_w_minus_gaps - child_screen_w_minus_gaps.floor();
-        let child_screen_w_minus_gaps = child_screen_w_minus_gaps.floor();
-        let child_fractional_w_minus_gaps = screen_x_to_fractional_x(
-            child_screen_w_minus_gaps + if is_last { remainder_screen_w } else { 0.0 },
-        );
-        child_transform.scale.x = child_fractional_w_minus_gaps;
-        child_transform.translation.x = fractional_x;
+/// The treemap doesn't draw groups of small items
+fn clear_small_items_groups(small_items_groups: &mut ResMut<SmallItemsGroups>) {
+    if !small_items_groups.0.is_empty() {
+        small_items_groups.0.clear();
+    }
+}
 
-        debug!(
-            key = child_fs_key.as_value(),
-            child_screen_w_minus_gaps,
-            child_fractional_w_minus_gaps,
-            child_scale_x = child_transform.scale.x,
-            child_translation_x = fractional_x,
-            remainder_screen_w,
-            "child positioned"
-        );
+/// The materialized fs entities, as the layout engine sees them. Any query that reads
+/// `FsEntityComponent`, `FsAggregateSize` and `Children` will do. Children are counted in
+/// the [`FsTree`], since the smallest ones are left unmaterialized.
+struct FsLayoutTree<'a, 'w, 's, Q: WorldQuery>(&'a Query<'w, 's, Q>, &'a FsTree);
 
-        // If child is a directory, update the sprite color and descendent color range to reflect
-        // the child's position in the parent
-        if let Some(mut child_color_range) = maybe_child_color_range {
-            let parent_color_range = maybe_parent_color_range.unwrap();
-            *child_color_range =
-                parent_color_range.sub_range(fractional_x, child_fractional_w);
-            debug!(
-                key = child_fs_key.as_value(),
-                parent_range = parent_color_range.as_value(),
-                child_range = child_color_range.as_value(),
-                fractional_x,
-                child_fractional_w,
-                "determined child color range",
-            );
-            child_sprite.color = child_color_range.get_color(0.0, child_fs.depth);
-        }
+impl<Q: WorldQuery> LayoutTree for FsLayoutTree<'_, '_, '_, Q> {
+    type Id = Entity;
 
-        // Increment x for the next child
-        fractional_x +=
-            child_fractional_w_minus_gaps + screen_x_to_fractional_x(GAP_WIDTH);
+    fn size(&self, id: Entity) -> u64 {
+        self.0
+            .get_component::<FsAggregateSize>(id)
+            .map_or(0, |size| size.size_in_bytes)
     }
 
-    // Ensure that all the hidden children are marked hidden
-    for (hidden_fs_child, _, screen_w) in hidden_children {
-        let hidden_fs_child = *hidden_fs_child;
-        let child_key = fs_entity_details_query
-            .get_component::<FsEntityKey>(*hidden_fs_child)
-            .unwrap();
-        trace!(
-            child_key = child_key.as_value(),
-            child_screen_width = screen_w,
-            "hiding child too small to display"
-        );
-        hide_subtree_recursive(
-            hidden_fs_child,
-            fs_entity_details_query,
-            fs_entity_mutable_details_query,
-        );
+    fn children(&self, id: Entity) -> Vec<Entity> {
+        self.0
+            .get_component::<Children>(id)
+            .map(|children| {
+                children
+                    .iter()
+                    .filter(|child| self.0.contains(**child))
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
-    // Invalidate the subtrees of visible children
-    for (child, _, _) in visible_children {
-        let child = *child;
+    fn child_count(&self, id: Entity) -> usize {
+        self.0
+            .get_component::<FsEntityComponent>(id)
+            .map_or(0, |fs_entity| self.1.children(fs_entity.node_id).count())
+    }
+}
 
-        let child_transform = fs_entity_mutable_details_query
-            .get_component::<Transform>(*child)
-            .unwrap();
+/// Moves the sprites to where [`layout_icicle`] placed them. The layout root has already
+/// been placed by the caller.
+fn apply_icicle_layout(
+    layout: &IcicleLayout<Entity>,
+    root_global_transform: &GlobalTransform,
+    fs_entity_details_query: &Query<(
+        &FsEntityKey,
+        &FsEntityComponent,
+        &FsAggregateSize,
+        Option<&Children>,
+    )>,
+    fs_entity_mutable_details_query: &mut Query<
+        (
+            &mut Transform,
+            &mut GlobalTransform,
+            &mut Sprite,
+            &mut Visibility,
+            Option<&mut DescendentColorRange>,
+        ),
+        (
+            With<FsEntityComponent>,
+            Without<DiskUsageTreeViewTransformRoot>,
+        ),
+    >,
+    colorer: &NodeColorer,
+) {
+    debug!(
+        drawn_count = layout.nodes.len(),
+        hidden_subtree_count = layout.hidden_subtrees.len(),
+        "applying icicle layout"
+    );
 
-        // Compute the global transform for the child (the one stored can be out of date)
-        let child_global_transform =
-            parent_global_transform.mul_transform(*child_transform);
+    // Global transforms are computed as we go, because the stored ones can be out of date
+    let mut parents: HashMap<Entity, (LayoutRect, GlobalTransform)> = HashMap::new();
+    for node in layout.nodes.iter() {
+        let (parent_rect, parent_global_transform) = match node.parent {
+            Some(parent) => parents[&parent],
+            None => {
+                parents.insert(node.id, (node.rect, *root_global_transform));
+                continue;
+            }
+        };
 
-        {
-            let mut mut_child_global_transform = fs_entity_mutable_details_query
-                .get_component_mut::<GlobalTransform>(*child)
-                .unwrap();
-            *mut_child_global_transform = child_global_transform;
-        }
+        let (mut transform, mut global_transform, mut sprite, mut vis, maybe_color_range) =
+            fs_entity_mutable_details_query.get_mut(node.id).unwrap();
+        vis.is_visible = true;
+        *transform = node.rect.relative_transform(&parent_rect);
+        *global_transform = parent_global_transform.mul_transform(*transform);
+        parents.insert(node.id, (node.rect, *global_transform));
 
-        // Invalidate the subtree rooted at child (if one exists)
-        invalidate_subtree_recursive(
-            &child_global_transform,
-            child,
+        // Directories keep their position in the parent, for positional colors
+        let node_id = fs_entity_details_query
+            .get_component::<FsEntityComponent>(node.id)
+            .unwrap()
+            .node_id;
+        sprite.color = match maybe_color_range {
+            Some(mut color_range) => {
+                *color_range = node.color_range;
+                colorer.color(node_id, Some(&color_range), node.level)
+            }
+            None => colorer.color(node_id, None, node.level),
+        };
+    }
+
+    for hidden in layout.hidden_subtrees.iter() {
+        hide_subtree_recursive(
+            hidden,
             fs_entity_details_query,
             fs_entity_mutable_details_query,
-            tree_options,
-            default_children_iter,
-            default_entity_ref_vec,
         );
     }
 }
 
-fn hide_subtree_recursive(
+pub(crate) fn hide_subtree_recursive(
     hidden_fs_parent: &Entity,
     fs_entity_details_query: &Query<(
         &FsEntityKey,
@@ -671,3 +1416,76 @@ fn hide_subtree_recursive(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: LayoutRect = LayoutRect {
+        x: -400.0,
+        y: 100.0,
+        w: 800.0,
+        h: 36.0,
+    };
+
+    /// A child a row above the root in the layout viewport, a quarter of the way along
+    fn child_in_viewport(orientation: TreeViewOrientation) -> LayoutRect {
+        let viewport = orientation.layout_viewport(&ROOT);
+        LayoutRect {
+            x: viewport.w / 4.0,
+            y: viewport.h + GAP_WIDTH,
+            w: viewport.w / 4.0,
+            h: viewport.h,
+        }
+    }
+
+    #[test]
+    fn the_root_fills_its_rect_in_every_orientation() {
+        for orientation in TreeViewOrientation::ALL {
+            let viewport = orientation.layout_viewport(&ROOT);
+            assert_eq!(orientation.place(&viewport, &ROOT), ROOT, "{orientation}");
+        }
+    }
+
+    #[test]
+    fn flame_graphs_grow_upward() {
+        let orientation = TreeViewOrientation::BottomUp;
+        let child = orientation.place(&child_in_viewport(orientation), &ROOT);
+
+        assert_eq!(child.y, ROOT.y + ROOT.h + GAP_WIDTH);
+        assert_eq!(child.x, ROOT.x + ROOT.w / 4.0);
+        assert_eq!((child.w, child.h), (ROOT.w / 4.0, ROOT.h));
+    }
+
+    #[test]
+    fn icicles_grow_downward() {
+        let orientation = TreeViewOrientation::TopDown;
+        let child = orientation.place(&child_in_viewport(orientation), &ROOT);
+
+        // The child's top edge is a gap below the root's bottom edge
+        assert_eq!(child.y + child.h, ROOT.y - GAP_WIDTH);
+        assert_eq!(child.x, ROOT.x + ROOT.w / 4.0);
+        assert_eq!((child.w, child.h), (ROOT.w / 4.0, ROOT.h));
+    }
+
+    #[test]
+    fn horizontal_icicles_grow_rightward_with_siblings_from_the_top() {
+        let orientation = TreeViewOrientation::LeftToRight;
+        let child = orientation.place(&child_in_viewport(orientation), &ROOT);
+
+        assert_eq!(child.x, ROOT.x + ROOT.w + GAP_WIDTH);
+        // A quarter of the way down from the root's top edge
+        assert_eq!(child.y + child.h, ROOT.y + ROOT.h - ROOT.h / 4.0);
+        assert_eq!((child.w, child.h), (ROOT.w, ROOT.h / 4.0));
+    }
+
+    #[test]
+    fn breadth_fractions_follow_the_siblings() {
+        for orientation in TreeViewOrientation::ALL {
+            let child = orientation.place(&child_in_viewport(orientation), &ROOT);
+            let center = Vec2::new(child.x + child.w / 2.0, child.y + child.h / 2.0);
+            let fraction = orientation.breadth_fraction(center, &ROOT);
+            assert!((fraction - 0.375).abs() < 1e-4, "{orientation}: {fraction}");
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use super::{
+    mouse_interactions_plugin::HoverableClicked,
+    panel_plugin::{panel_bundle, PanelBackground, PanelFont, PanelText},
+    tree_view_plugin::HOVER_Z,
+};
+use crate::{
+    export::{export_nodes, ExportOptions},
+    fs::{human_readable_size, FsEntityComponent, FsEntityMap, FsNodeId, FsTree},
+    theme::Theme,
+};
+use bevy::{prelude::*, sprite::Anchor, transform::TransformSystem, utils::HashSet};
+
+const STATUS_FONT_SIZE: f32 = 14.0;
+/// Selection overlays are drawn below the hover and focus overlays
+const SELECTION_Z: f32 = HOVER_Z - 0.1;
+
+/// The nodes marked with Ctrl+click and Shift+click, in the order they were selected
+#[derive(Default)]
+pub struct TreeViewSelection {
+    selected: Vec<FsNodeId>,
+    /// Where Shift+click ranges start from
+    anchor: Option<FsNodeId>,
+}
+
+impl TreeViewSelection {
+    pub fn nodes(&self) -> &[FsNodeId] {
+        &self.selected
+    }
+
+    pub fn len(&self) -> usize {
+        self.selected.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected.is_empty()
+    }
+
+    pub fn contains(&self, node_id: FsNodeId) -> bool {
+        self.selected.contains(&node_id)
+    }
+
+    /// The selected nodes that don't have a selected ancestor, which together cover the
+    /// whole selection without counting anything twice
+    pub fn top_level_nodes(&self, fs_tree: &FsTree) -> Vec<FsNodeId> {
+        let selected: HashSet<FsNodeId> = self.selected.iter().copied().collect();
+        self.selected
+            .iter()
+            .copied()
+            .filter(|id| !fs_tree.ancestors(*id).any(|a| selected.contains(&a)))
+            .collect()
+    }
+
+    /// The combined size of the selection, counting nodes within other selected nodes once
+    pub fn total_size(&self, fs_tree: &FsTree) -> u64 {
+        self.top_level_nodes(fs_tree)
+            .into_iter()
+            .map(|id| fs_tree.aggregate_size(id))
+            .sum()
+    }
+
+    pub fn toggle(&mut self, node_id: FsNodeId) {
+        match self.selected.iter().position(|id| *id == node_id) {
+            Some(index) => {
+                self.selected.remove(index);
+            }
+            None => self.selected.push(node_id),
+        }
+        self.anchor = Some(node_id);
+    }
+
+    /// Selects `range`, a run of siblings from the anchor. The anchor stays where it was,
+    /// so that the range can be changed by Shift+clicking again.
+    fn select_range(&mut self, range: impl IntoIterator<Item = FsNodeId>) {
+        for node_id in range {
+            if !self.selected.contains(&node_id) {
+                self.selected.push(node_id);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.selected.clear();
+        self.anchor = None;
+    }
+}
+
+/// Highlights a selected block
+#[derive(Component)]
+struct SelectionSprite(Entity);
+
+#[derive(Component)]
+struct SelectionStatus;
+
+#[derive(Component)]
+struct SelectionStatusText;
+
+/// The outcome of the last export, shown under the selection's size until the selection
+/// changes
+#[derive(Default, Deref, DerefMut)]
+struct LastExportMessage(Option<String>);
+
+pub struct TreeViewSelectionPlugin;
+impl Plugin for TreeViewSelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeViewSelection>()
+            .init_resource::<ExportOptions>()
+            .init_resource::<LastExportMessage>()
+            .add_startup_system(create_selection_status)
+            .add_system(select_on_click)
+            .add_system(clear_selection_on_keys)
+            .add_system(forget_removed_nodes)
+            .add_system(
+                export_selection_on_keys
+                    .after(select_on_click)
+                    .after(clear_selection_on_keys)
+                    .after(forget_removed_nodes),
+            )
+            .add_system(update_selection_status.after(export_selection_on_keys))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                sync_selection_sprites.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Ctrl+click toggles a block, and Shift+click selects the siblings from the last toggled
+/// block up to the clicked one
+fn select_on_click(
+    mut clicked_events: EventReader<HoverableClicked>,
+    keys: Res<Input<KeyCode>>,
+    mut selection: ResMut<TreeViewSelection>,
+    fs_tree: Res<FsTree>,
+    fs_entity_query: Query<(&FsEntityComponent, Option<&Parent>)>,
+    children_query: Query<&Children>,
+) {
+    let is_ctrl_down = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let is_shift_down = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    for HoverableClicked { entity, button } in clicked_events.iter() {
+        if *button != MouseButton::Left || !(is_ctrl_down || is_shift_down) {
+            continue;
+        }
+        // The scanned directory holds everything, so selecting it means nothing
+        let (fs_entity, maybe_parent) = match fs_entity_query.get(*entity) {
+            Ok((fs_entity, Some(parent))) => (fs_entity, parent),
+            _ => continue,
+        };
+        let node_id = fs_entity.node_id;
+
+        let anchor = selection
+            .anchor
+            .filter(|anchor| fs_tree.parent(*anchor) == fs_tree.parent(node_id));
+        let siblings = children_query
+            .get(maybe_parent.0)
+            .map(|children| children.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let sibling_ids: Vec<FsNodeId> = siblings
+            .iter()
+            .filter_map(|sibling| fs_entity_query.get(*sibling).ok())
+            .map(|(fs_entity, _)| fs_entity.node_id)
+            .collect();
+        let range = anchor.and_then(|anchor| {
+            let from = sibling_ids.iter().position(|id| *id == anchor)?;
+            let to = sibling_ids.iter().position(|id| *id == node_id)?;
+            Some(&sibling_ids[from.min(to)..=from.max(to)])
+        });
+
+        match range {
+            Some(range) if is_shift_down => selection.select_range(range.iter().copied()),
+            _ => selection.toggle(node_id),
+        }
+    }
+}
+
+/// Ctrl+Shift+A clears the selection
+fn clear_selection_on_keys(
+    keys: Res<Input<KeyCode>>,
+    mut selection: ResMut<TreeViewSelection>,
+) {
+    if keys.just_pressed(KeyCode::A)
+        && keys.any_pressed([KeyCode::LControl, KeyCode::RControl])
+        && keys.any_pressed([KeyCode::LShift, KeyCode::RShift])
+        && !selection.is_empty()
+    {
+        selection.clear();
+    }
+}
+
+/// Deleted and rescanned nodes drop out of the selection
+fn forget_removed_nodes(fs_tree: Res<FsTree>, mut selection: ResMut<TreeViewSelection>) {
+    if !fs_tree.is_changed() {
+        return;
+    }
+    if selection.selected.iter().any(|id| !fs_tree.contains(*id)) {
+        selection.selected.retain(|id| fs_tree.contains(*id));
+    }
+    if matches!(selection.anchor, Some(anchor) if !fs_tree.contains(anchor)) {
+        selection.anchor = None;
+    }
+}
+
+/// Ctrl+E exports the selection for review, as a path list, a dry-run cleanup script and
+/// a JSON manifest. Nodes within other selected nodes are left out.
+fn export_selection_on_keys(
+    keys: Res<Input<KeyCode>>,
+    selection: Res<TreeViewSelection>,
+    fs_tree: Res<FsTree>,
+    options: Res<ExportOptions>,
+    mut last_export_message: ResMut<LastExportMessage>,
+) {
+    if selection.is_changed() && last_export_message.is_some() {
+        last_export_message.0 = None;
+    }
+    if !keys.just_pressed(KeyCode::E)
+        || !keys.any_pressed([KeyCode::LControl, KeyCode::RControl])
+        || selection.is_empty()
+    {
+        return;
+    }
+
+    let nodes = selection.top_level_nodes(&fs_tree);
+    last_export_message.0 = Some(match export_nodes(&fs_tree, &nodes, &options) {
+        Ok(files) => {
+            info!(
+                path_list = %files.path_list.display(),
+                script = %files.script.display(),
+                manifest = %files.manifest.display(),
+                "selection exported"
+            );
+            format!(
+                "exported to {}",
+                files.manifest.with_extension("*").display()
+            )
+        }
+        Err(err) => {
+            warn!(%err, "selection not exported");
+            format!("export failed: {err}")
+        }
+    });
+}
+
+/// The status line sits in the bottom right corner, across from the breadcrumb bar
+fn create_selection_status(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    font: Res<PanelFont>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            visibility: Visibility { is_visible: false },
+            ..panel_bundle(
+                &theme,
+                Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(16.0),
+                        right: Val::Px(16.0),
+                        ..default()
+                    },
+                    padding: Rect::all(Val::Px(6.0)),
+                    ..default()
+                },
+            )
+        })
+        .insert(PanelBackground)
+        .insert(SelectionStatus)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: STATUS_FONT_SIZE,
+                            color: theme.panel_text,
+                        },
+                        default(),
+                    ),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(PanelText)
+                .insert(SelectionStatusText);
+        });
+}
+
+/// Shows the selection's count and combined size. Sizes change while a scan runs, so this
+/// follows the tree as well as the selection.
+#[allow(clippy::type_complexity)]
+fn update_selection_status(
+    selection: Res<TreeViewSelection>,
+    fs_tree: Res<FsTree>,
+    last_export_message: Res<LastExportMessage>,
+    mut status_query: Query<&mut Visibility, With<SelectionStatus>>,
+    mut status_text_query: Query<
+        (&mut Text, &mut Visibility),
+        (With<SelectionStatusText>, Without<SelectionStatus>),
+    >,
+) {
+    if !selection.is_changed()
+        && !fs_tree.is_changed()
+        && !last_export_message.is_changed()
+    {
+        return;
+    }
+
+    let is_visible = !selection.is_empty();
+    for mut vis in status_query.iter_mut() {
+        if vis.is_visible != is_visible {
+            vis.is_visible = is_visible;
+        }
+    }
+    for (mut text, mut vis) in status_text_query.iter_mut() {
+        if vis.is_visible != is_visible {
+            vis.is_visible = is_visible;
+        }
+        if !is_visible {
+            continue;
+        }
+        let count = selection.len();
+        let mut status = format!(
+            "{count} selected, {} (Ctrl+E to export, Ctrl+Shift+A to clear)",
+            human_readable_size(selection.total_size(&fs_tree))
+        );
+        if let Some(message) = &last_export_message.0 {
+            status.push('\n');
+            status.push_str(message);
+        }
+        if text.sections[0].value != status {
+            text.sections[0].value = status;
+        }
+    }
+}
+
+/// Keeps one overlay on each selected block that's drawn. Blocks can be animating, so
+/// this runs after their transforms have settled for the frame. Sunburst blocks are
+/// collapsed, and their sectors are tinted instead (see `sync_sunburst_segments`).
+#[allow(clippy::type_complexity)]
+fn sync_selection_sprites(
+    mut commands: Commands,
+    selection: Res<TreeViewSelection>,
+    theme: Res<Theme>,
+    fs_entity_map: Res<FsEntityMap>,
+    blocks_query: Query<
+        (&GlobalTransform, &Visibility),
+        (With<FsEntityComponent>, Without<SelectionSprite>),
+    >,
+    mut selection_sprites_query: Query<(
+        Entity,
+        &SelectionSprite,
+        &mut Sprite,
+        &mut Transform,
+        &mut GlobalTransform,
+        &mut Visibility,
+    )>,
+) {
+    let selected_blocks: HashSet<Entity> = selection
+        .nodes()
+        .iter()
+        .filter_map(|node_id| fs_entity_map.get(node_id).copied())
+        .collect();
+
+    let mut blocks_with_sprites = HashSet::default();
+    for (
+        sprite_entity,
+        SelectionSprite(block),
+        mut sprite,
+        mut transform,
+        mut global,
+        mut vis,
+    ) in selection_sprites_query.iter_mut()
+    {
+        let (block_transform, block_vis) = match blocks_query.get(*block) {
+            Ok(block_details) if selected_blocks.contains(block) => block_details,
+            _ => {
+                commands.entity(sprite_entity).despawn();
+                continue;
+            }
+        };
+        blocks_with_sprites.insert(*block);
+
+        if vis.is_visible != block_vis.is_visible {
+            vis.is_visible = block_vis.is_visible;
+        }
+        if sprite.color != theme.selection_overlay {
+            sprite.color = theme.selection_overlay;
+        }
+        let placed = Transform {
+            translation: block_transform.translation.truncate().extend(SELECTION_Z),
+            scale: block_transform.scale,
+            ..default()
+        };
+        if *transform != placed {
+            *transform = placed;
+            // The overlays are top-level entities, and transform propagation has already
+            // run
+            *global = placed.into();
+        }
+    }
+
+    for block in selected_blocks.difference(&blocks_with_sprites) {
+        if blocks_query.contains(*block) {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: theme.selection_overlay,
+                        anchor: Anchor::BottomLeft,
+                        ..default()
+                    },
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(SelectionSprite(*block));
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use super::{
+    color_scheme::NodeColorer,
+    mouse_interactions_plugin::{HoverShape, Hoverable},
+    small_items_group::{PlacedSmallItemsGroup, SmallItemsGroup, SmallItemsGroupShape},
+    tree_layout::{partition_children, HiddenChildren, LayoutTree},
+    tree_view_focus_plugin::TreeViewFocus,
+    tree_view_plugin::{
+        hide_subtree_recursive, DiskUsageTreeViewTransformRoot, GAP_WIDTH,
+        MIN_CHILD_WIDTH_WITH_GAP,
+    },
+    tree_view_selection_plugin::TreeViewSelection,
+    tree_view_theme::DescendentColorRange,
+};
+use crate::{
+    fs::{FsAggregateSize, FsEntityComponent, FsEntityKey, FsEntityMap},
+    theme::Theme,
+};
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashSet,
+};
+use std::f32::consts::TAU;
+use tracing::debug;
+use valuable::Valuable;
+
+/// The layout root's disk in the middle of the sunburst
+const CENTER_COLOR: Color = Color::rgb(0.231, 0.240, 0.263);
+const SEGMENT_Z: f32 = 0.5;
+/// The fraction of the window (in its shorter direction) covered by the sunburst
+const SUNBURST_WINDOW_FRACTION: f32 = 0.9;
+/// The number of straight edges used to approximate a full circle
+const ARC_STEPS_PER_TURN: f32 = 180.0;
+
+/// Where a node is drawn in the sunburst. `None` when the node is hidden, or when another
+/// layout is in use.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct SunburstSector(pub Option<Sector>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Sector {
+    pub center: Vec2,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    /// Radians, counterclockwise from the positive x axis
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub color: Color,
+}
+
+impl Sector {
+    pub fn hover_shape(&self) -> HoverShape {
+        HoverShape::AnnularSector {
+            center: self.center,
+            inner_radius: self.inner_radius,
+            outer_radius: self.outer_radius,
+            start_angle: self.start_angle,
+            end_angle: self.end_angle,
+        }
+    }
+}
+
+/// Links an fs entity, or a small items group, to the mesh entity that draws its sector
+#[derive(Component)]
+pub(crate) struct SunburstSegment(Entity);
+
+/// Links a mesh entity back to the fs entity, or small items group, whose sector it draws
+#[derive(Component)]
+pub(crate) struct SegmentOf(Entity);
+
+/// The rings of the sunburst are all the same width, sized so that `max_rings` rings fit
+/// around the center disk
+pub(crate) struct SunburstGeometry {
+    pub center: Vec2,
+    pub ring_width: f32,
+}
+
+impl SunburstGeometry {
+    pub fn for_window_size(window_size: Vec2, max_rings: u16) -> Self {
+        let radius = window_size.min_element() / 2.0 * SUNBURST_WINDOW_FRACTION;
+        Self {
+            center: Vec2::ZERO,
+            ring_width: radius / (max_rings as f32 + 1.0),
+        }
+    }
+
+    fn inner_radius(&self, ring: u16) -> f32 {
+        ring as f32 * self.ring_width
+    }
+
+    fn outer_radius(&self, ring: u16) -> f32 {
+        (ring as f32 + 1.0) * self.ring_width - GAP_WIDTH
+    }
+
+    /// The layout root's disk
+    pub fn center_sector(&self) -> Sector {
+        Sector {
+            center: self.center,
+            inner_radius: 0.0,
+            outer_radius: self.outer_radius(0),
+            start_angle: 0.0,
+            end_angle: TAU,
+            color: CENTER_COLOR,
+        }
+    }
+}
+
+/// Lays out `fs_parent`'s children in the ring outside of the parent's, with angles
+/// proportional to size. The sunburst follows the same rules as the icicle: children
+/// whose outer arc is narrower than `MIN_CHILD_WIDTH_WITH_GAP` are hidden, and drawn as
+/// one group after the visible children if there's room for it (see
+/// [`partition_children`]), the visible children are packed from the start of the
+/// parent's range, and directories take their share of the parent's
+/// [`DescendentColorRange`].
+///
+/// Sprites are collapsed to zero width while the sunburst is shown. The sectors are drawn
+/// by meshes (see [`sync_sunburst_segments`]).
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn invalidate_sunburst_recursive<T: LayoutTree<Id = Entity>>(
+    tree: &T,
+    geometry: &SunburstGeometry,
+    fs_parent: Entity,
+    parent_sector: &Sector,
+    ring: u16,
+    max_depth: u16,
+    layout_root_depth: u16,
+    fs_entity_details_query: &Query<(
+        &FsEntityKey,
+        &FsEntityComponent,
+        &FsAggregateSize,
+        Option<&Children>,
+    )>,
+    fs_entity_mutable_details_query: &mut Query<
+        (
+            &mut Transform,
+            &mut GlobalTransform,
+            &mut Sprite,
+            &mut Visibility,
+            Option<&mut DescendentColorRange>,
+        ),
+        (
+            With<FsEntityComponent>,
+            Without<DiskUsageTreeViewTransformRoot>,
+        ),
+    >,
+    sunburst_query: &mut Query<(&mut SunburstSector, &mut HoverShape)>,
+    colorer: &NodeColorer,
+    small_items_groups: &mut Vec<PlacedSmallItemsGroup>,
+) {
+    let (parent_fs_key, parent_fs_entity, _, _) =
+        fs_entity_details_query.get(fs_parent).unwrap();
+
+    if parent_fs_entity.depth >= max_depth || tree.size(fs_parent) == 0 {
+        for child in tree.children(fs_parent).iter() {
+            hide_subtree_recursive(
+                child,
+                fs_entity_details_query,
+                fs_entity_mutable_details_query,
+            );
+        }
+        return;
+    }
+
+    let maybe_parent_color_range: Option<DescendentColorRange> =
+        fs_entity_mutable_details_query
+            .get_component::<DescendentColorRange>(fs_parent)
+            .ok()
+            .copied();
+
+    let child_ring = ring + 1;
+    let inner_radius = geometry.inner_radius(child_ring);
+    let outer_radius = geometry.outer_radius(child_ring);
+    let parent_sweep = parent_sector.end_angle - parent_sector.start_angle;
+    let gap_angle = GAP_WIDTH / outer_radius;
+    let sector_between = |start_angle: f32, sweep: f32, color: Color| Sector {
+        center: geometry.center,
+        inner_radius,
+        outer_radius,
+        start_angle,
+        end_angle: start_angle + (sweep - gap_angle).max(sweep / 2.0),
+        color,
+    };
+
+    debug!(
+        key = parent_fs_key.as_value(),
+        ring = child_ring,
+        "invalidating sunburst subtree"
+    );
+
+    // Widths along the ring's outer arc decide what's visible
+    let partition = partition_children(
+        tree,
+        fs_parent,
+        parent_sweep * outer_radius,
+        MIN_CHILD_WIDTH_WITH_GAP,
+    );
+    for child in partition.hidden.iter() {
+        hide_subtree_recursive(
+            child,
+            fs_entity_details_query,
+            fs_entity_mutable_details_query,
+        );
+    }
+
+    let mut angle = parent_sector.start_angle;
+    let mut visible_children = vec![];
+    for (child, fractional_sweep) in partition.visible {
+        let sweep = fractional_sweep * parent_sweep;
+        let child_fs = fs_entity_details_query
+            .get_component::<FsEntityComponent>(child)
+            .unwrap();
+        let (mut transform, _, mut sprite, mut vis, maybe_color_range) =
+            fs_entity_mutable_details_query.get_mut(child).unwrap();
+        vis.is_visible = true;
+        transform.scale.x = 0.0;
+
+        let level = child_fs.depth - layout_root_depth;
+        let color = match (maybe_color_range, maybe_parent_color_range) {
+            (Some(mut color_range), Some(parent_color_range)) => {
+                *color_range = parent_color_range.sub_range(
+                    (angle - parent_sector.start_angle) / parent_sweep,
+                    fractional_sweep,
+                );
+                colorer.color(child_fs.node_id, Some(&color_range), level)
+            }
+            _ => colorer.color(child_fs.node_id, None, level),
+        };
+        sprite.color = color;
+
+        let sector = sector_between(angle, sweep, color);
+        let (mut sunburst_sector, mut hover_shape) =
+            sunburst_query.get_mut(child).unwrap();
+        if sunburst_sector.0 != Some(sector) {
+            sunburst_sector.0 = Some(sector);
+            *hover_shape = sector.hover_shape();
+        }
+
+        visible_children.push((child, sector));
+        angle += sweep;
+    }
+
+    if partition.hidden_count > 0 && partition.is_grouped {
+        let sweep = partition.hidden_fraction * parent_sweep;
+        small_items_groups.push(PlacedSmallItemsGroup {
+            hidden: HiddenChildren {
+                parent: fs_parent,
+                children: partition.hidden,
+                count: partition.hidden_count,
+                size: partition.hidden_size,
+                width: sweep * outer_radius,
+                group_rect: None,
+            },
+            // The group takes the rest of the parent's range
+            shape: SmallItemsGroupShape::Sector(sector_between(
+                angle,
+                sweep,
+                colorer.theme.small_items,
+            )),
+        });
+    }
+
+    for (child, sector) in visible_children {
+        invalidate_sunburst_recursive(
+            tree,
+            geometry,
+            child,
+            &sector,
+            child_ring,
+            max_depth,
+            layout_root_depth,
+            fs_entity_details_query,
+            fs_entity_mutable_details_query,
+            sunburst_query,
+            colorer,
+            small_items_groups,
+        );
+    }
+}
+
+/// Places the layout root at the center of the sunburst
+pub(crate) fn place_sunburst_center(
+    geometry: &SunburstGeometry,
+    layout_root: Entity,
+    sunburst_query: &mut Query<(&mut SunburstSector, &mut HoverShape)>,
+) -> Sector {
+    let sector = geometry.center_sector();
+    let (mut sunburst_sector, mut hover_shape) =
+        sunburst_query.get_mut(layout_root).unwrap();
+    if sunburst_sector.0 != Some(sector) {
+        sunburst_sector.0 = Some(sector);
+        *hover_shape = sector.hover_shape();
+    }
+    sector
+}
+
+/// Switching back to a rectangular layout
+pub(crate) fn clear_sunburst_sectors(
+    sunburst_query: &mut Query<(&mut SunburstSector, &mut HoverShape)>,
+) {
+    for (mut sunburst_sector, mut hover_shape) in sunburst_query.iter_mut() {
+        if sunburst_sector.0.is_some() {
+            sunburst_sector.0 = None;
+            *hover_shape = HoverShape::Rect;
+        }
+    }
+}
+
+/// Creates, updates and hides the meshes that draw the sectors of fs entities and small
+/// items groups. Hovered, focused and selected sectors are tinted with the theme's overlay
+/// colors, standing in for the overlay sprites used by rectangular layouts.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn sync_sunburst_segments(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    changed_query: Query<
+        Entity,
+        (
+            Or<(With<FsEntityComponent>, With<SmallItemsGroup>)>,
+            Without<SegmentOf>,
+            Or<(
+                Changed<SunburstSector>,
+                Changed<Visibility>,
+                Changed<Hoverable>,
+            )>,
+        ),
+    >,
+    sectors_query: Query<
+        (
+            &SunburstSector,
+            &Visibility,
+            &Hoverable,
+            Option<&SunburstSegment>,
+        ),
+        Without<SegmentOf>,
+    >,
+    all_sectors_query: Query<Entity, (With<SunburstSector>, Without<SegmentOf>)>,
+    mut segments_query: Query<
+        (&Mesh2dHandle, &Handle<ColorMaterial>, &mut Visibility),
+        With<SegmentOf>,
+    >,
+    focus: Res<TreeViewFocus>,
+    selection: Res<TreeViewSelection>,
+    fs_entity_map: Res<FsEntityMap>,
+    theme: Res<Theme>,
+    mut last_focused: Local<Option<Entity>>,
+    mut last_selected: Local<HashSet<Entity>>,
+) {
+    let mut entities: Vec<Entity> = if theme.is_changed() {
+        all_sectors_query.iter().collect()
+    } else {
+        changed_query.iter().collect()
+    };
+    if focus.is_changed() {
+        entities.extend(last_focused.take());
+        entities.extend(focus.focused());
+        *last_focused = focus.focused();
+    }
+    // Selected nodes may be materialized after they're selected
+    if selection.is_changed() || fs_entity_map.is_changed() {
+        let selected: HashSet<Entity> = selection
+            .nodes()
+            .iter()
+            .filter_map(|node_id| fs_entity_map.get(node_id).copied())
+            .collect();
+        if selected != *last_selected {
+            entities.extend(last_selected.symmetric_difference(&selected));
+            *last_selected = selected;
+        }
+    }
+
+    for entity in entities {
+        let (sunburst_sector, vis, hoverable, maybe_segment) =
+            match sectors_query.get(entity) {
+                Ok(res) => res,
+                Err(_) => continue,
+            };
+
+        let sector = match sunburst_sector.0 {
+            Some(sector) if vis.is_visible => sector,
+            _ => {
+                if let Some(segment) = maybe_segment {
+                    if let Ok((_, _, mut segment_vis)) = segments_query.get_mut(segment.0)
+                    {
+                        segment_vis.is_visible = false;
+                    }
+                }
+                continue;
+            }
+        };
+
+        let color = if hoverable.is_hovered {
+            blend(sector.color, theme.hover_overlay)
+        } else if focus.focused() == Some(entity) {
+            blend(sector.color, theme.focus_overlay)
+        } else if last_selected.contains(&entity) {
+            blend(sector.color, theme.selection_overlay)
+        } else {
+            sector.color
+        };
+
+        match maybe_segment.and_then(|segment| segments_query.get_mut(segment.0).ok()) {
+            Some((mesh_handle, material_handle, mut segment_vis)) => {
+                segment_vis.is_visible = true;
+                if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+                    *mesh = annular_sector_mesh(&sector);
+                }
+                if let Some(material) = materials.get_mut(material_handle) {
+                    material.color = color;
+                }
+            }
+            None => {
+                let segment = commands
+                    .spawn_bundle(MaterialMesh2dBundle {
+                        mesh: meshes.add(annular_sector_mesh(&sector)).into(),
+                        material: materials.add(color.into()),
+                        transform: Transform::from_xyz(0.0, 0.0, SEGMENT_Z),
+                        ..default()
+                    })
+                    .insert(SegmentOf(entity))
+                    .id();
+                commands.entity(entity).insert(SunburstSegment(segment));
+            }
+        }
+    }
+}
+
+/// Despawns the segments of fs entities that were despawned, like those below a rescanned
+/// directory
+pub(crate) fn despawn_orphaned_segments(
+    mut commands: Commands,
+    removed_fs_entities: RemovedComponents<SunburstSegment>,
+    segments_query: Query<(Entity, &SegmentOf)>,
+) {
+    let removed_fs_entities: HashSet<Entity> = removed_fs_entities.iter().collect();
+    if removed_fs_entities.is_empty() {
+        return;
+    }
+    for (segment, SegmentOf(fs_entity)) in segments_query.iter() {
+        if removed_fs_entities.contains(fs_entity) {
+            commands.entity(segment).despawn();
+        }
+    }
+}
+
+/// The color of `overlay` drawn over `color`, as an overlay sprite would be
+fn blend(color: Color, overlay: Color) -> Color {
+    let [r, g, b, a] = color.as_rgba_f32();
+    let [o_r, o_g, o_b, overlay_alpha] = overlay.as_rgba_f32();
+    Color::rgba(
+        r + (o_r - r) * overlay_alpha,
+        g + (o_g - g) * overlay_alpha,
+        b + (o_b - b) * overlay_alpha,
+        a,
+    )
+}
+
+/// Builds a ring segment out of a strip of quads, with counterclockwise winding
+fn annular_sector_mesh(sector: &Sector) -> Mesh {
+    let sweep = sector.end_angle - sector.start_angle;
+    let steps = ((sweep / TAU * ARC_STEPS_PER_TURN).ceil() as u32).max(1);
+
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(2 * (steps as usize + 1));
+    let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(positions.capacity());
+    for step in 0..=steps {
+        let t = step as f32 / steps as f32;
+        let angle = sector.start_angle + sweep * t;
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        let inner = sector.center + direction * sector.inner_radius;
+        let outer = sector.center + direction * sector.outer_radius;
+        positions.push([inner.x, inner.y, 0.0]);
+        positions.push([outer.x, outer.y, 0.0]);
+        uvs.push([t, 0.0]);
+        uvs.push([t, 1.0]);
+    }
+
+    let mut indices: Vec<u32> = Vec::with_capacity(6 * steps as usize);
+    for step in 0..steps {
+        let i = step * 2;
+        indices.extend([i, i + 1, i + 2, i + 1, i + 3, i + 2]);
+    }
+
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
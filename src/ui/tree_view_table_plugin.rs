@@ -0,0 +1,668 @@
+use super::{
+    mouse_interactions_plugin::{Hoverable, MarkHoverables, UiHoverTarget},
+    panel_plugin::{panel_bundle, PanelBackground, PanelFont, PanelText},
+    tree_view_focus_plugin::TreeSearch,
+    tree_view_plugin::is_modifier_pressed,
+    tree_view_viewport_plugin::PIXELS_PER_LINE,
+    tree_view_zoom_plugin::{TreeViewZoom, ZoomCommand},
+};
+use crate::{
+    fs::{
+        format_mtime, human_readable_size, percentage, FsAggregateSize,
+        FsEntityComponent, FsRootComponent, FsTree,
+    },
+    theme::Theme,
+};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    ui::FocusPolicy,
+};
+use std::cmp::Ordering;
+
+const TABLE_FONT_SIZE: f32 = 13.0;
+/// The table's font is monospaced, with glyphs 0.6em wide, so columns can be sized in
+/// characters
+const TABLE_CHAR_WIDTH: f32 = TABLE_FONT_SIZE * 0.6;
+const CELL_PADDING: f32 = 4.0;
+/// The rows listed at a time. Longer tables scroll.
+const MAX_ROWS: usize = 30;
+/// How many rows one line of the mouse wheel scrolls by
+const ROWS_PER_LINE: f32 = 3.0;
+/// The longest gap between the clicks of a double click
+const DOUBLE_CLICK_SECS: f64 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableColumn {
+    Name,
+    Size,
+    Percent,
+    Items,
+    Modified,
+}
+
+impl TableColumn {
+    const ALL: [TableColumn; 5] = [
+        TableColumn::Name,
+        TableColumn::Size,
+        TableColumn::Percent,
+        TableColumn::Items,
+        TableColumn::Modified,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            TableColumn::Name => "Name",
+            TableColumn::Size => "Size",
+            TableColumn::Percent => "%",
+            TableColumn::Items => "Items",
+            TableColumn::Modified => "Modified",
+        }
+    }
+
+    fn width_in_chars(&self) -> usize {
+        match self {
+            TableColumn::Name => 28,
+            TableColumn::Size => 10,
+            TableColumn::Percent => 7,
+            TableColumn::Items => 8,
+            TableColumn::Modified => 23,
+        }
+    }
+
+    /// Numbers are right aligned
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            TableColumn::Size | TableColumn::Percent | TableColumn::Items
+        )
+    }
+
+    /// Names read best A to Z, and everything else largest or newest first
+    fn is_descending_by_default(&self) -> bool {
+        *self != TableColumn::Name
+    }
+}
+
+/// Whether the table of the layout root's children is shown, and how it's sorted
+pub struct TreeViewTable {
+    pub is_open: bool,
+    pub sort_column: TableColumn,
+    pub is_descending: bool,
+    /// The index of the topmost listed row, in sort order
+    pub first_row: usize,
+}
+
+impl Default for TreeViewTable {
+    fn default() -> Self {
+        Self {
+            is_open: false,
+            sort_column: TableColumn::Size,
+            is_descending: true,
+            first_row: 0,
+        }
+    }
+}
+
+impl TreeViewTable {
+    /// Sorts by `column`, or reverses the order if the table is already sorted by it, and
+    /// scrolls back to the top
+    pub fn sort_by(&mut self, column: TableColumn) {
+        if self.sort_column == column {
+            self.is_descending = !self.is_descending;
+        } else {
+            self.sort_column = column;
+            self.is_descending = column.is_descending_by_default();
+        }
+        self.first_row = 0;
+    }
+}
+
+/// One child of the layout root, as listed in the table
+#[derive(Clone, Debug)]
+struct TableRowData {
+    entity: Entity,
+    name: String,
+    size_in_bytes: u64,
+    /// `None` for anything but directories
+    item_count: Option<u32>,
+    mtime_secs: i64,
+    modified: String,
+}
+
+impl TableRowData {
+    fn cmp_by(&self, other: &TableRowData, column: TableColumn) -> Ordering {
+        match column {
+            TableColumn::Name => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+            TableColumn::Size | TableColumn::Percent => {
+                self.size_in_bytes.cmp(&other.size_in_bytes)
+            }
+            TableColumn::Items => self.item_count.cmp(&other.item_count),
+            TableColumn::Modified => self.mtime_secs.cmp(&other.mtime_secs),
+        }
+    }
+}
+
+/// The table, and how many rows it has to scroll through
+#[derive(Component)]
+struct TablePanel {
+    row_count: usize,
+}
+
+#[derive(Component)]
+struct TableTitle;
+
+#[derive(Component)]
+struct TableFooter;
+
+#[derive(Component)]
+struct TableHeader(TableColumn);
+
+/// A row of the table, and the fs entity it's showing, if any. Rows are created along
+/// with the panel, and reused as the children change.
+#[derive(Component)]
+struct TableRow {
+    index: usize,
+    entity: Option<Entity>,
+}
+
+/// The text of a row's cell
+#[derive(Component)]
+struct TableCell {
+    row_index: usize,
+    column: TableColumn,
+}
+
+pub struct TreeViewTablePlugin;
+impl Plugin for TreeViewTablePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeViewTable>()
+            .add_system(toggle_table_on_keys)
+            .add_system(sync_table_panel.after(toggle_table_on_keys))
+            .add_system(sort_on_header_click)
+            .add_system(scroll_table.after(sort_on_header_click))
+            .add_system(
+                update_table_rows
+                    .after(sync_table_panel)
+                    .after(scroll_table),
+            )
+            .add_system(
+                hover_blocks_of_rows
+                    .after(update_table_rows)
+                    .before(MarkHoverables),
+            )
+            .add_system(
+                highlight_rows_of_hovered_blocks
+                    .after(update_table_rows)
+                    .after(MarkHoverables),
+            )
+            .add_system(zoom_on_row_double_click.after(update_table_rows));
+    }
+}
+
+/// T opens and closes the table, unless a modifier is held
+fn toggle_table_on_keys(
+    keys: Res<Input<KeyCode>>,
+    search: Res<TreeSearch>,
+    mut table: ResMut<TreeViewTable>,
+) {
+    if !search.is_active() && !is_modifier_pressed(&keys) && keys.just_pressed(KeyCode::T)
+    {
+        table.is_open = !table.is_open;
+    }
+}
+
+/// The panel is only spawned while the table is open, so that its rows don't catch the
+/// cursor while it's closed. It's spawned again when the theme changes.
+fn sync_table_panel(
+    mut commands: Commands,
+    table: Res<TreeViewTable>,
+    theme: Res<Theme>,
+    font: Res<PanelFont>,
+    panel_query: Query<Entity, With<TablePanel>>,
+) {
+    if !table.is_changed() && !theme.is_changed() {
+        return;
+    }
+    if !table.is_open || theme.is_changed() {
+        for panel in panel_query.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+    } else if !panel_query.is_empty() {
+        return;
+    }
+    if !table.is_open {
+        return;
+    }
+
+    let text = |value: &str, color: Color| {
+        Text::with_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size: TABLE_FONT_SIZE,
+                color,
+            },
+            default(),
+        )
+    };
+    let cell_style = |column: TableColumn| Style {
+        size: Size::new(
+            Val::Px(
+                column.width_in_chars() as f32 * TABLE_CHAR_WIDTH + CELL_PADDING * 2.0,
+            ),
+            Val::Auto,
+        ),
+        padding: Rect::all(Val::Px(CELL_PADDING)),
+        justify_content: if column.is_numeric() {
+            JustifyContent::FlexEnd
+        } else {
+            JustifyContent::FlexStart
+        },
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(panel_bundle(
+            &theme,
+            Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(16.0),
+                    left: Val::Px(16.0),
+                    ..default()
+                },
+                padding: Rect::all(Val::Px(4.0)),
+                ..default()
+            },
+        ))
+        .insert(PanelBackground)
+        .insert(Interaction::default())
+        .insert(TablePanel { row_count: 0 })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(CELL_PADDING)),
+                        ..default()
+                    },
+                    text: text("", theme.panel_text),
+                    ..default()
+                })
+                .insert(PanelText)
+                .insert(TableTitle);
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    color: Color::NONE.into(),
+                    focus_policy: FocusPolicy::Pass,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for column in TableColumn::ALL {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: cell_style(column),
+                                color: Color::NONE.into(),
+                                ..default()
+                            })
+                            .insert(TableHeader(column))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: text(column.title(), theme.panel_text_dim),
+                                    ..default()
+                                });
+                            });
+                    }
+                });
+
+            for row_index in 0..MAX_ROWS {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            display: Display::None,
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .insert(TableRow {
+                        index: row_index,
+                        entity: None,
+                    })
+                    .with_children(|parent| {
+                        for column in TableColumn::ALL {
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: cell_style(column),
+                                    color: Color::NONE.into(),
+                                    focus_policy: FocusPolicy::Pass,
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent
+                                        .spawn_bundle(TextBundle {
+                                            text: text("", theme.panel_text),
+                                            ..default()
+                                        })
+                                        .insert(PanelText)
+                                        .insert(TableCell { row_index, column });
+                                });
+                        }
+                    });
+            }
+
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(CELL_PADDING)),
+                        ..default()
+                    },
+                    text: text("", theme.panel_text_dim),
+                    ..default()
+                })
+                .insert(TableFooter);
+        });
+}
+
+/// Clicking a column's header sorts by it, and clicking it again reverses the order
+fn sort_on_header_click(
+    mut header_query: Query<
+        (&Interaction, &TableHeader, &mut UiColor),
+        Changed<Interaction>,
+    >,
+    theme: Res<Theme>,
+    mut table: ResMut<TreeViewTable>,
+) {
+    for (interaction, TableHeader(column), mut color) in header_query.iter_mut() {
+        *color = match *interaction {
+            Interaction::None => Color::NONE.into(),
+            _ => theme.panel_button.into(),
+        };
+        if *interaction == Interaction::Clicked {
+            table.sort_by(*column);
+        }
+    }
+}
+
+/// The mouse wheel scrolls the table while the cursor is on it, and Page Up and Page Down
+/// scroll it a page at a time. Zooming elsewhere scrolls back to the top.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn scroll_table(
+    mut wheel_events: EventReader<MouseWheel>,
+    keys: Res<Input<KeyCode>>,
+    search: Res<TreeSearch>,
+    zoom: Res<TreeViewZoom>,
+    panel_query: Query<&TablePanel>,
+    interaction_query: Query<
+        &Interaction,
+        Or<(With<TablePanel>, With<TableHeader>, With<TableRow>)>,
+    >,
+    mut table: ResMut<TreeViewTable>,
+    mut unscrolled_rows: Local<f32>,
+) {
+    let lines: f32 = wheel_events
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    if zoom.is_changed() && table.first_row != 0 {
+        table.first_row = 0;
+    }
+    let row_count = match panel_query.get_single() {
+        Ok(panel) => panel.row_count,
+        Err(_) => return,
+    };
+
+    let is_over_table = interaction_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    // The wheel scrolls toward the top when it's turned away from the user
+    let mut rows = 0.0;
+    if is_over_table {
+        rows -= lines * ROWS_PER_LINE;
+    }
+    if !search.is_active() {
+        if keys.just_pressed(KeyCode::PageUp) {
+            rows -= MAX_ROWS as f32;
+        }
+        if keys.just_pressed(KeyCode::PageDown) {
+            rows += MAX_ROWS as f32;
+        }
+    }
+    *unscrolled_rows += rows;
+    let whole_rows = unscrolled_rows.trunc();
+    *unscrolled_rows -= whole_rows;
+    if whole_rows == 0.0 {
+        return;
+    }
+
+    let max_first_row = row_count.saturating_sub(MAX_ROWS);
+    let first_row = (table.first_row.min(max_first_row) as f32 + whole_rows)
+        .clamp(0.0, max_first_row as f32) as usize;
+    if table.first_row != first_row {
+        table.first_row = first_row;
+    }
+}
+
+/// Lists the children of the layout root. Sizes change while a scan runs, so the rows are
+/// refreshed whenever a size does.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_table_rows(
+    table: Res<TreeViewTable>,
+    zoom: Res<TreeViewZoom>,
+    fs_tree: Res<FsTree>,
+    fs_root_query: Query<Entity, With<FsRootComponent>>,
+    fs_entity_query: Query<(&FsEntityComponent, &FsAggregateSize, Option<&Children>)>,
+    changed_sizes_query: Query<(), (With<FsEntityComponent>, Changed<FsAggregateSize>)>,
+    changed_children_query: Query<(), (With<FsEntityComponent>, Changed<Children>)>,
+    new_cells_query: Query<(), Added<TableCell>>,
+    mut panel_query: Query<&mut TablePanel>,
+    mut row_query: Query<(&mut TableRow, &mut Style)>,
+    mut cell_query: Query<
+        (&mut Text, &TableCell),
+        (Without<TableTitle>, Without<TableFooter>),
+    >,
+    mut title_query: Query<&mut Text, (With<TableTitle>, Without<TableFooter>)>,
+    mut footer_query: Query<&mut Text, (With<TableFooter>, Without<TableTitle>)>,
+) {
+    if row_query.is_empty()
+        || !(table.is_changed()
+            || zoom.is_changed()
+            || !changed_sizes_query.is_empty()
+            || !changed_children_query.is_empty()
+            || !new_cells_query.is_empty())
+    {
+        return;
+    }
+
+    let layout_root = match zoom
+        .layout_root()
+        .or_else(|| fs_root_query.get_single().ok())
+    {
+        Some(layout_root) => layout_root,
+        None => return,
+    };
+    let (layout_root_fs, layout_root_size, maybe_children) =
+        match fs_entity_query.get(layout_root) {
+            Ok(layout_root) => layout_root,
+            Err(_) => return,
+        };
+    let parent_size = layout_root_size.size_in_bytes;
+
+    let mut rows: Vec<TableRowData> = maybe_children
+        .map(|children| children.iter().copied().collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|child| {
+            let (fs_entity, fs_size, _) = fs_entity_query.get(child).ok()?;
+            let node_id = fs_entity.node_id;
+            let metadata = fs_tree.metadata(node_id);
+            Some(TableRowData {
+                entity: child,
+                name: fs_tree.name(node_id).to_string_lossy().into(),
+                size_in_bytes: fs_size.size_in_bytes,
+                item_count: fs_entity.is_dir().then(|| {
+                    fs_tree.descendent_file_count(node_id)
+                        + fs_tree.descendent_dir_count(node_id)
+                }),
+                mtime_secs: metadata.mtime_secs,
+                modified: format_mtime(metadata),
+            })
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        let ordering = a.cmp_by(b, table.sort_column);
+        let ordering = if table.is_descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then_with(|| a.cmp_by(b, TableColumn::Name))
+    });
+
+    for mut title in title_query.iter_mut() {
+        title.sections[0].value = format!(
+            "{}  ({} items)",
+            fs_tree.path(layout_root_fs.node_id).display(),
+            rows.len()
+        );
+    }
+    for mut panel in panel_query.iter_mut() {
+        if panel.row_count != rows.len() {
+            panel.row_count = rows.len();
+        }
+    }
+    // The rows may have shrunk since the table was scrolled
+    let first_row = table.first_row.min(rows.len().saturating_sub(MAX_ROWS));
+    let listed_rows = &rows[first_row..rows.len().min(first_row + MAX_ROWS)];
+    for mut footer in footer_query.iter_mut() {
+        footer.sections[0].value = if rows.len() > MAX_ROWS {
+            format!(
+                "{}-{} of {}, scroll or Page Up/Down for more",
+                first_row + 1,
+                first_row + listed_rows.len(),
+                rows.len()
+            )
+        } else {
+            String::new()
+        };
+    }
+
+    for (mut table_row, mut style) in row_query.iter_mut() {
+        let entity = listed_rows.get(table_row.index).map(|row| row.entity);
+        if table_row.entity != entity {
+            table_row.entity = entity;
+        }
+        let display = if entity.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    for (mut text, cell) in cell_query.iter_mut() {
+        let value = listed_rows
+            .get(cell.row_index)
+            .map_or_else(String::new, |row| cell_value(row, cell.column, parent_size));
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn cell_value(row: &TableRowData, column: TableColumn, parent_size: u64) -> String {
+    match column {
+        TableColumn::Name => truncate(&row.name, column.width_in_chars()),
+        TableColumn::Size => human_readable_size(row.size_in_bytes),
+        TableColumn::Percent => percentage(row.size_in_bytes, parent_size),
+        TableColumn::Items => row
+            .item_count
+            .map_or_else(String::new, |count| count.to_string()),
+        TableColumn::Modified => row.modified.clone(),
+    }
+}
+
+/// Shortens `name` to `max_chars`, ending it with an ellipsis when it's cut
+fn truncate(name: &str, max_chars: usize) -> String {
+    if name.chars().count() <= max_chars {
+        name.into()
+    } else {
+        let mut truncated: String = name.chars().take(max_chars - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Hovering a row hovers its block, as the cursor would
+fn hover_blocks_of_rows(
+    row_query: Query<(&TableRow, &Interaction)>,
+    mut ui_hover_target: ResMut<UiHoverTarget>,
+) {
+    let hovered_row_entity = row_query
+        .iter()
+        .find(|(_, interaction)| **interaction != Interaction::None)
+        .and_then(|(table_row, _)| table_row.entity);
+    if **ui_hover_target != hovered_row_entity {
+        **ui_hover_target = hovered_row_entity;
+    }
+}
+
+/// A hovered block highlights its row, whether it was hovered in the table or in the tree
+fn highlight_rows_of_hovered_blocks(
+    mut row_query: Query<(&TableRow, &mut UiColor)>,
+    hoverable_query: Query<&Hoverable, With<FsEntityComponent>>,
+    theme: Res<Theme>,
+) {
+    for (table_row, mut color) in row_query.iter_mut() {
+        let is_hovered = matches!(
+            table_row
+                .entity
+                .and_then(|entity| hoverable_query.get(entity).ok()),
+            Some(hoverable) if hoverable.is_hovered
+        );
+        let wanted_color = if is_hovered {
+            theme.panel_button
+        } else {
+            Color::NONE
+        };
+        if color.0 != wanted_color {
+            color.0 = wanted_color;
+        }
+    }
+}
+
+/// Double clicking a directory's row zooms into it
+fn zoom_on_row_double_click(
+    row_query: Query<(&TableRow, &Interaction), Changed<Interaction>>,
+    fs_entity_query: Query<&FsEntityComponent>,
+    time: Res<Time>,
+    mut last_click: Local<Option<(Entity, f64)>>,
+    mut zoom_commands: EventWriter<ZoomCommand>,
+) {
+    let now = time.seconds_since_startup();
+    for (table_row, interaction) in row_query.iter() {
+        let entity = match (table_row.entity, interaction) {
+            (Some(entity), Interaction::Clicked) => entity,
+            _ => continue,
+        };
+
+        let is_double_click = matches!(
+            *last_click,
+            Some((last_entity, clicked_at))
+                if last_entity == entity && now - clicked_at <= DOUBLE_CLICK_SECS
+        );
+        if !is_double_click {
+            *last_click = Some((entity, now));
+            continue;
+        }
+        *last_click = None;
+        if matches!(fs_entity_query.get(entity), Ok(fs_entity) if fs_entity.is_dir()) {
+            zoom_commands.send(ZoomCommand::To(entity));
+        }
+    }
+}
@@ -6,7 +6,7 @@ const HSL_MIN_LIGHTNESS: f32 = 0.62;
 const HSL_MAX_LIGHTNESS: f32 = 0.9;
 
 pub fn hsl_get_color(t: f32, depth: u16) -> Color {
-    let lightness_fraction = ((depth - 1) as f32).clamp(0.0, 5.0) / 5.0;
+    let lightness_fraction = (depth.saturating_sub(1) as f32).clamp(0.0, 5.0) / 5.0;
     let lightness =
         HSL_MIN_LIGHTNESS + lightness_fraction * (HSL_MAX_LIGHTNESS - HSL_MIN_LIGHTNESS);
     Color::hsl((200.0 + (t * 360.0)) % 360.0, 1.0, lightness)
@@ -15,7 +15,11 @@ pub fn hsl_get_color(t: f32, depth: u16) -> Color {
 pub fn less_angry_rainbow_get_color(t: f32, depth: u16) -> Color {
     let colorous_color = RAINBOW.eval_continuous((t as f64) % 1.0);
     let bevy_color = Color::rgb_u8(colorous_color.r, colorous_color.g, colorous_color.b);
+    shade_for_depth(bevy_color, depth)
+}
 
+/// Lightens colors the deeper they're drawn
+pub fn shade_for_depth(bevy_color: Color, depth: u16) -> Color {
     if let Color::Hsla {
         hue,
         saturation,
@@ -34,6 +38,17 @@ pub fn less_angry_rainbow_get_color(t: f32, depth: u16) -> Color {
     }
 }
 
+/// Black or white, whichever reads better on top of `background`
+pub fn contrasting_text_color(background: Color) -> Color {
+    let [r, g, b, _] = background.as_rgba_f32();
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    if luminance > 0.5 {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
+
 #[derive(Component, Clone, Copy, Debug, Valuable)]
 pub struct DescendentColorRange {
     /// [0..1]
@@ -43,6 +58,10 @@ pub struct DescendentColorRange {
 }
 
 impl DescendentColorRange {
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+
     fn len(&self) -> f32 {
         self.end - self.start
     }
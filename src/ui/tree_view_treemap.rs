@@ -0,0 +1,405 @@
+use super::{
+    color_scheme::NodeColorer,
+    tree_view_plugin::{
+        hide_subtree_recursive, DiskUsageTreeViewTransformRoot, GAP_WIDTH,
+        MIN_CHILD_WIDTH_WITH_GAP,
+    },
+    tree_view_theme::DescendentColorRange,
+};
+use crate::fs::{FsAggregateSize, FsEntityComponent, FsEntityKey};
+use bevy::prelude::*;
+use tracing::debug;
+use valuable::Valuable;
+
+/// The strip along the top of a directory that holds its name
+pub(crate) const TREEMAP_HEADER_HEIGHT: f32 = 18.0;
+/// The space between a directory's edges and its children
+const TREEMAP_PADDING: f32 = 3.0;
+/// Directories are drawn beneath their children, so every level is raised a little
+const TREEMAP_LEVEL_Z: f32 = 0.001;
+/// The space left around the treemap, which keeps it clear of the breadcrumbs and the
+/// search text along the bottom of the window
+const TREEMAP_MARGIN: f32 = 16.0;
+const TREEMAP_BOTTOM_MARGIN: f32 = 88.0;
+
+/// A rectangle in world coordinates, from its bottom left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TreemapRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl TreemapRect {
+    /// The area of the window that the layout root fills
+    pub fn for_window_size(window_size: Vec2) -> Self {
+        Self {
+            x: -window_size.x / 2.0 + TREEMAP_MARGIN,
+            y: -window_size.y / 2.0 + TREEMAP_BOTTOM_MARGIN,
+            w: (window_size.x - TREEMAP_MARGIN * 2.0).max(0.0),
+            h: (window_size.y - TREEMAP_MARGIN - TREEMAP_BOTTOM_MARGIN).max(0.0),
+        }
+    }
+
+    fn from_global_transform(transform: &GlobalTransform) -> Self {
+        Self {
+            x: transform.translation.x,
+            y: transform.translation.y,
+            w: transform.scale.x,
+            h: transform.scale.y,
+        }
+    }
+
+    /// The transform that places this rect, relative to a parent drawn at `parent`
+    pub fn local_transform(&self, parent: &GlobalTransform) -> Transform {
+        Transform {
+            translation: Vec3::new(
+                (self.x - parent.translation.x) / parent.scale.x,
+                (self.y - parent.translation.y) / parent.scale.y,
+                TREEMAP_LEVEL_Z,
+            ),
+            scale: Vec3::new(self.w / parent.scale.x, self.h / parent.scale.y, 1.0),
+            ..default()
+        }
+    }
+
+    /// The area left for a directory's children, inside its padding and beneath its
+    /// header. Directories too short for a header only get the padding.
+    fn content(&self) -> Self {
+        let header_h = if self.h >= TREEMAP_HEADER_HEIGHT * 2.0 {
+            TREEMAP_HEADER_HEIGHT
+        } else {
+            TREEMAP_PADDING
+        };
+        Self {
+            x: self.x + TREEMAP_PADDING,
+            y: self.y + TREEMAP_PADDING,
+            w: (self.w - TREEMAP_PADDING * 2.0).max(0.0),
+            h: (self.h - TREEMAP_PADDING - header_h).max(0.0),
+        }
+    }
+}
+
+/// Lays out `fs_parent`'s children inside its padding and header with [`squarify`]. The
+/// rules otherwise match the icicle's: children narrower or shorter than
+/// `MIN_CHILD_WIDTH_WITH_GAP` are hidden, siblings are separated by `GAP_WIDTH`, and
+/// directories take their share of the parent's [`DescendentColorRange`].
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn invalidate_treemap_recursive(
+    parent_global_transform: &GlobalTransform,
+    fs_parent: Entity,
+    layout_root_depth: u16,
+    max_depth: u16,
+    fs_entity_details_query: &Query<(
+        &FsEntityKey,
+        &FsEntityComponent,
+        &FsAggregateSize,
+        Option<&Children>,
+    )>,
+    fs_entity_mutable_details_query: &mut Query<
+        (
+            &mut Transform,
+            &mut GlobalTransform,
+            &mut Sprite,
+            &mut Visibility,
+            Option<&mut DescendentColorRange>,
+        ),
+        (
+            With<FsEntityComponent>,
+            Without<DiskUsageTreeViewTransformRoot>,
+        ),
+    >,
+    colorer: &NodeColorer,
+) {
+    let (parent_fs_key, parent_fs_entity, parent_fs_size, maybe_children) =
+        fs_entity_details_query.get(fs_parent).unwrap();
+    let mut children: Vec<(Entity, u64)> = maybe_children
+        .map(|children| {
+            children
+                .iter()
+                .filter_map(|child| {
+                    fs_entity_details_query
+                        .get_component::<FsAggregateSize>(*child)
+                        .ok()
+                        .map(|size| (*child, size.size_in_bytes))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let content = TreemapRect::from_global_transform(parent_global_transform).content();
+    if parent_fs_entity.depth >= max_depth
+        || parent_fs_size.size_in_bytes == 0
+        || content.w < MIN_CHILD_WIDTH_WITH_GAP
+        || content.h < MIN_CHILD_WIDTH_WITH_GAP
+    {
+        for (child, _) in children {
+            hide_subtree_recursive(
+                &child,
+                fs_entity_details_query,
+                fs_entity_mutable_details_query,
+            );
+        }
+        return;
+    }
+
+    debug!(
+        key = parent_fs_key.as_value(),
+        content = ?content,
+        "invalidating treemap subtree"
+    );
+
+    let maybe_parent_color_range: Option<DescendentColorRange> =
+        fs_entity_mutable_details_query
+            .get_component::<DescendentColorRange>(fs_parent)
+            .ok()
+            .copied();
+
+    // Squarifying needs the largest children first. Empty children have no area at all.
+    children.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let (sized_children, empty_children): (Vec<_>, Vec<_>) =
+        children.into_iter().partition(|(_, size)| *size > 0);
+    for (child, _) in empty_children {
+        hide_subtree_recursive(
+            &child,
+            fs_entity_details_query,
+            fs_entity_mutable_details_query,
+        );
+    }
+
+    let content_area = content.w * content.h;
+    let fractions: Vec<f32> = sized_children
+        .iter()
+        .map(|(_, size)| *size as f32 / parent_fs_size.size_in_bytes as f32)
+        .collect();
+    let areas: Vec<f32> = fractions
+        .iter()
+        .map(|fraction| fraction * content_area)
+        .collect();
+    let rects = squarify(&areas, content);
+
+    let mut fractional_offset = 0_f32;
+    let mut visible_children = vec![];
+    for (((child, _), fraction), rect) in sized_children.iter().zip(fractions).zip(rects)
+    {
+        let child = *child;
+        let child_offset = fractional_offset;
+        fractional_offset += fraction;
+
+        if rect.w < MIN_CHILD_WIDTH_WITH_GAP || rect.h < MIN_CHILD_WIDTH_WITH_GAP {
+            hide_subtree_recursive(
+                &child,
+                fs_entity_details_query,
+                fs_entity_mutable_details_query,
+            );
+            continue;
+        }
+
+        // The gap is taken from the top and right of every child
+        let rect = TreemapRect {
+            w: rect.w - GAP_WIDTH,
+            h: rect.h - GAP_WIDTH,
+            ..rect
+        };
+        let child_fs = fs_entity_details_query
+            .get_component::<FsEntityComponent>(child)
+            .unwrap();
+        let (
+            mut child_transform,
+            mut child_global_transform,
+            mut child_sprite,
+            mut child_vis,
+            maybe_child_color_range,
+        ) = fs_entity_mutable_details_query.get_mut(child).unwrap();
+        child_vis.is_visible = true;
+        *child_transform = rect.local_transform(parent_global_transform);
+        *child_global_transform = parent_global_transform.mul_transform(*child_transform);
+
+        let level = child_fs.depth - layout_root_depth;
+        child_sprite.color = match (maybe_child_color_range, maybe_parent_color_range) {
+            (Some(mut child_color_range), Some(parent_color_range)) => {
+                *child_color_range = parent_color_range.sub_range(child_offset, fraction);
+                colorer.color(child_fs.node_id, Some(&child_color_range), level)
+            }
+            _ => colorer.color(child_fs.node_id, None, level),
+        };
+
+        visible_children.push((child, *child_global_transform));
+    }
+
+    for (child, child_global_transform) in visible_children {
+        invalidate_treemap_recursive(
+            &child_global_transform,
+            child,
+            layout_root_depth,
+            max_depth,
+            fs_entity_details_query,
+            fs_entity_mutable_details_query,
+            colorer,
+        );
+    }
+}
+
+/// Splits `rect` into one rect per area, using the squarified treemap algorithm of Bruls,
+/// Huizing and van Wijk. Areas are laid out in rows along the shorter side of the space
+/// that's left, and a row grows for as long as that makes its worst aspect ratio better.
+///
+/// `areas` have to be sorted largest first, be positive, and sum to at most the area of
+/// `rect`.
+fn squarify(areas: &[f32], rect: TreemapRect) -> Vec<TreemapRect> {
+    let mut rects = Vec::with_capacity(areas.len());
+    let mut remaining = rect;
+    let mut row_start = 0;
+    while row_start < areas.len() {
+        let side = remaining.w.min(remaining.h);
+        let mut row_end = row_start + 1;
+        while row_end < areas.len()
+            && worst_aspect_ratio(&areas[row_start..=row_end], side)
+                <= worst_aspect_ratio(&areas[row_start..row_end], side)
+        {
+            row_end += 1;
+        }
+
+        let row = &areas[row_start..row_end];
+        let row_area: f32 = row.iter().sum();
+        if remaining.w >= remaining.h {
+            // A column down the left of the remaining space
+            let row_w = row_area / remaining.h.max(f32::EPSILON);
+            let mut y = remaining.y + remaining.h;
+            for area in row {
+                let h = area / row_w.max(f32::EPSILON);
+                y -= h;
+                rects.push(TreemapRect {
+                    x: remaining.x,
+                    y,
+                    w: row_w,
+                    h,
+                });
+            }
+            remaining.x += row_w;
+            remaining.w -= row_w;
+        } else {
+            // A row across the top of the remaining space
+            let row_h = row_area / remaining.w.max(f32::EPSILON);
+            let mut x = remaining.x;
+            for area in row {
+                let w = area / row_h.max(f32::EPSILON);
+                rects.push(TreemapRect {
+                    x,
+                    y: remaining.y + remaining.h - row_h,
+                    w,
+                    h: row_h,
+                });
+                x += w;
+            }
+            remaining.h -= row_h;
+        }
+
+        row_start = row_end;
+    }
+    rects
+}
+
+/// The worst aspect ratio of the rects in `row` (sorted largest first), when they're laid
+/// along a side of length `side`
+fn worst_aspect_ratio(row: &[f32], side: f32) -> f32 {
+    let sum: f32 = row.iter().sum();
+    let largest = row[0];
+    let smallest = row[row.len() - 1];
+    let side_squared = side * side;
+    let sum_squared = sum * sum;
+    (side_squared * largest / sum_squared).max(sum_squared / (side_squared * smallest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> TreemapRect {
+        TreemapRect { x, y, w, h }
+    }
+
+    fn aspect_ratio(rect: &TreemapRect) -> f32 {
+        (rect.w / rect.h).max(rect.h / rect.w)
+    }
+
+    fn overlap_area(a: &TreemapRect, b: &TreemapRect) -> f32 {
+        let w = (a.x + a.w).min(b.x + b.w) - a.x.max(b.x);
+        let h = (a.y + a.h).min(b.y + b.h) - a.y.max(b.y);
+        w.max(0.0) * h.max(0.0)
+    }
+
+    /// Checks that each rect has its area, lies inside `parent`, and doesn't overlap any
+    /// other, so that together they tile `parent` when the areas add up to it
+    fn assert_tiles(areas: &[f32], parent: TreemapRect, rects: &[TreemapRect]) {
+        assert_eq!(rects.len(), areas.len());
+        for (area, rect) in areas.iter().zip(rects) {
+            assert!(
+                (rect.w * rect.h - area).abs() < EPSILON,
+                "{rect:?} isn't {area}"
+            );
+            assert!(rect.x >= parent.x - EPSILON && rect.y >= parent.y - EPSILON);
+            assert!(rect.x + rect.w <= parent.x + parent.w + EPSILON);
+            assert!(rect.y + rect.h <= parent.y + parent.h + EPSILON);
+        }
+        for (index, a) in rects.iter().enumerate() {
+            for b in rects[index + 1..].iter() {
+                assert!(overlap_area(a, b) < EPSILON, "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn squarify_tiles_the_parent() {
+        // The example from the squarified treemaps paper
+        let areas = [6.0, 6.0, 4.0, 3.0, 2.0, 2.0, 1.0];
+        let parent = rect(10.0, -5.0, 6.0, 4.0);
+        let rects = squarify(&areas, parent);
+
+        assert_tiles(&areas, parent, &rects);
+        let covered: f32 = rects.iter().map(|rect| rect.w * rect.h).sum();
+        assert!((covered - parent.w * parent.h).abs() < EPSILON);
+    }
+
+    #[test]
+    fn squarify_leaves_the_rest_of_the_parent_empty() {
+        let areas = [30.0, 20.0, 10.0];
+        let parent = rect(0.0, 0.0, 10.0, 10.0);
+        let rects = squarify(&areas, parent);
+
+        assert_tiles(&areas, parent, &rects);
+    }
+
+    #[test]
+    fn squarify_keeps_aspect_ratios_bounded() {
+        let areas = [6.0, 6.0, 4.0, 3.0, 2.0, 2.0, 1.0];
+        let rects = squarify(&areas, rect(0.0, 0.0, 6.0, 4.0));
+        for rect in rects.iter() {
+            assert!(aspect_ratio(rect) <= 3.0, "{rect:?} is too thin");
+        }
+
+        // Equal areas come out close to square, even in a wide parent
+        let areas = [1.0; 32];
+        let rects = squarify(&areas, rect(0.0, 0.0, 8.0, 4.0));
+        for rect in rects.iter() {
+            assert!(aspect_ratio(rect) <= 2.0, "{rect:?} is too thin");
+        }
+    }
+
+    #[test]
+    fn squarify_does_better_than_slicing() {
+        // One large area and many small ones, where slicing the parent into strips would
+        // give the small areas an aspect ratio of 100
+        let mut areas = vec![50.0];
+        areas.extend([1.0; 50]);
+        let parent = rect(0.0, 0.0, 10.0, 10.0);
+        let rects = squarify(&areas, parent);
+
+        assert_tiles(&areas, parent, &rects);
+        let worst = rects.iter().map(aspect_ratio).fold(1.0, f32::max);
+        assert!(worst <= 4.0, "worst aspect ratio is {worst}");
+    }
+}
@@ -0,0 +1,257 @@
+use super::{
+    mouse_interactions_plugin::{CursorDrag, MouseCursorWorldPosition},
+    tree_layout::LayoutRect,
+    tree_view_focus_plugin::TreeSearch,
+    tree_view_plugin::{
+        DiskUsageTreeOptions, DiskUsageTreeViewTransformRoot, TreeViewLayout,
+    },
+    tree_view_zoom_plugin::TreeViewZoom,
+};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use tracing::debug;
+
+/// How much one line of scrolling magnifies by
+const MAGNIFICATION_PER_LINE: f32 = 1.25;
+/// Touchpads scroll in pixels, which are counted in lines of this many
+pub(crate) const PIXELS_PER_LINE: f32 = 20.0;
+/// Beyond this, sizes lose too much precision to be laid out in whole units
+const MAX_MAGNIFICATION: f32 = 1000.0;
+
+/// The part of the icicle that's on screen, when it's magnified along its breadth. This is
+/// finer grained than zooming into a directory, for seeing slivers that are too narrow to
+/// draw otherwise.
+#[derive(Debug, PartialEq)]
+pub struct TreeViewViewport {
+    scale: f32,
+    /// The fraction of the layout root's breadth before the visible part
+    start: f32,
+}
+
+impl Default for TreeViewViewport {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            start: 0.0,
+        }
+    }
+}
+
+impl TreeViewViewport {
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn is_magnified(&self) -> bool {
+        self.scale > 1.0
+    }
+
+    /// The layout viewport `unmagnified` stretched along its width, and shifted so that
+    /// the visible part of it lines up with `unmagnified`
+    pub fn magnified(&self, unmagnified: &LayoutRect) -> LayoutRect {
+        let w = unmagnified.w * self.scale;
+        LayoutRect {
+            x: unmagnified.x - self.start * w,
+            w,
+            ..*unmagnified
+        }
+    }
+
+    /// Multiplies the scale by `factor`, keeping the point at `anchor` where it is.
+    /// `anchor` is a fraction of the visible part.
+    pub fn magnify(&mut self, factor: f32, anchor: f32) {
+        let anchored_at = self.start + anchor / self.scale;
+        self.scale = (self.scale * factor).clamp(1.0, MAX_MAGNIFICATION);
+        self.start = anchored_at - anchor / self.scale;
+        self.clamp_start();
+    }
+
+    /// Scrolls by `fraction` of the visible part
+    pub fn pan(&mut self, fraction: f32) {
+        self.start += fraction / self.scale;
+        self.clamp_start();
+    }
+
+    fn clamp_start(&mut self) {
+        self.start = self.start.clamp(0.0, 1.0 - 1.0 / self.scale);
+    }
+}
+
+pub struct TreeViewViewportPlugin;
+impl Plugin for TreeViewViewportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeViewViewport>()
+            .add_system(reset_viewport)
+            .add_system(magnify_on_wheel.after(reset_viewport))
+            .add_system(pan_on_drag.after(reset_viewport));
+    }
+}
+
+/// Zooming into another directory, or pressing 0, shows the whole breadth again
+fn reset_viewport(
+    keys: Res<Input<KeyCode>>,
+    search: Res<TreeSearch>,
+    zoom: Res<TreeViewZoom>,
+    mut viewport: ResMut<TreeViewViewport>,
+) {
+    let is_reset_pressed =
+        !search.is_active() && keys.any_just_pressed([KeyCode::Key0, KeyCode::Numpad0]);
+    if (zoom.is_changed() || is_reset_pressed) && viewport.is_magnified() {
+        debug!("viewport reset");
+        *viewport = TreeViewViewport::default();
+    }
+}
+
+/// The mouse wheel magnifies the icicle around the cursor
+fn magnify_on_wheel(
+    mut wheel_events: EventReader<MouseWheel>,
+    cursor_world_pos: Res<MouseCursorWorldPosition>,
+    tree_options: Res<DiskUsageTreeOptions>,
+    transform_root_query: Query<&Transform, With<DiskUsageTreeViewTransformRoot>>,
+    ui_interactions_query: Query<&Interaction>,
+    mut viewport: ResMut<TreeViewViewport>,
+) {
+    let lines: f32 = wheel_events
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    // Panels scroll on their own
+    let is_over_ui = ui_interactions_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if lines == 0.0 || is_over_ui || tree_options.layout != TreeViewLayout::Icicle {
+        return;
+    }
+    let (cursor, root_transform) =
+        match (**cursor_world_pos, transform_root_query.get_single()) {
+            (Some(cursor), Ok(root_transform)) => (cursor, root_transform),
+            _ => return,
+        };
+
+    let root = LayoutRect::from_transform(root_transform);
+    let anchor = tree_options
+        .orientation
+        .breadth_fraction(cursor, &root)
+        .clamp(0.0, 1.0);
+    viewport.magnify(MAGNIFICATION_PER_LINE.powf(lines), anchor);
+    debug!(scale = viewport.scale(), "viewport magnified");
+}
+
+/// Dragging with the left button pans a magnified icicle
+fn pan_on_drag(
+    drag: Res<CursorDrag>,
+    tree_options: Res<DiskUsageTreeOptions>,
+    transform_root_query: Query<&Transform, With<DiskUsageTreeViewTransformRoot>>,
+    mut viewport: ResMut<TreeViewViewport>,
+) {
+    if drag.delta == Vec2::ZERO
+        || !viewport.is_magnified()
+        || tree_options.layout != TreeViewLayout::Icicle
+    {
+        return;
+    }
+    let root_transform = match transform_root_query.get_single() {
+        Ok(root_transform) => root_transform,
+        Err(_) => return,
+    };
+
+    // The tree follows the cursor, so the viewport moves the other way
+    let root = LayoutRect::from_transform(root_transform);
+    let dragged = tree_options
+        .orientation
+        .offset_breadth_fraction(drag.delta, &root);
+    viewport.pan(-dragged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn viewport(scale: f32, start: f32) -> TreeViewViewport {
+        TreeViewViewport { scale, start }
+    }
+
+    /// The fraction of the layout root's breadth at `anchor` of the visible part
+    fn point_at(viewport: &TreeViewViewport, anchor: f32) -> f32 {
+        viewport.start + anchor / viewport.scale
+    }
+
+    #[test]
+    fn magnifying_keeps_the_point_at_the_anchor() {
+        for anchor in [0.0, 0.3, 1.0] {
+            let mut viewport = viewport(4.0, 0.25);
+            let anchored_at = point_at(&viewport, anchor);
+            viewport.magnify(2.0, anchor);
+
+            assert!((viewport.scale() - 8.0).abs() < EPSILON);
+            assert!((point_at(&viewport, anchor) - anchored_at).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn magnifying_at_either_end_keeps_that_end_in_view() {
+        let mut left = TreeViewViewport::default();
+        left.magnify(2.0, 0.0);
+        assert_eq!(left, viewport(2.0, 0.0));
+
+        let mut right = TreeViewViewport::default();
+        right.magnify(2.0, 1.0);
+        assert_eq!(right, viewport(2.0, 0.5));
+    }
+
+    #[test]
+    fn unmagnifying_stops_at_the_whole_breadth() {
+        let mut viewport = viewport(4.0, 0.5);
+        viewport.magnify(0.1, 0.5);
+
+        assert!(!viewport.is_magnified());
+        assert_eq!(viewport, TreeViewViewport::default());
+    }
+
+    #[test]
+    fn magnification_is_capped() {
+        let mut viewport = TreeViewViewport::default();
+        viewport.magnify(1e6, 0.5);
+
+        assert_eq!(viewport.scale(), MAX_MAGNIFICATION);
+        assert!(point_at(&viewport, 1.0) <= 1.0);
+    }
+
+    #[test]
+    fn panning_moves_by_a_fraction_of_the_visible_part() {
+        let mut viewport = viewport(4.0, 0.25);
+        viewport.pan(0.5);
+        assert!((viewport.start - 0.375).abs() < EPSILON);
+        viewport.pan(-0.5);
+        assert!((viewport.start - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn panning_past_either_end_stops_at_it() {
+        let mut viewport = viewport(2.0, 0.25);
+        viewport.pan(-10.0);
+        assert_eq!(viewport.start, 0.0);
+
+        viewport.pan(10.0);
+        assert_eq!(viewport.start, 0.5);
+        assert!((point_at(&viewport, 1.0) - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn start_is_clamped_to_keep_the_visible_part_within_the_breadth() {
+        let mut viewport = viewport(4.0, 2.0);
+        viewport.clamp_start();
+        assert_eq!(viewport.start, 0.75);
+
+        viewport.start = -1.0;
+        viewport.clamp_start();
+        assert_eq!(viewport.start, 0.0);
+    }
+}
@@ -0,0 +1,459 @@
+use super::{
+    mouse_interactions_plugin::HoverableClicked,
+    panel_plugin::{menu_item_color, PanelFont},
+    small_items_group::SmallItemsGroup,
+};
+use crate::{
+    fs::{FsEntityComponent, FsEntityMap, FsNodeId, FsRootComponent, FsTree},
+    theme::Theme,
+};
+use bevy::{prelude::*, utils::HashSet};
+use tracing::debug;
+
+const BREADCRUMB_FONT_SIZE: f32 = 16.0;
+
+/// The directory that the tree view is laid out from, and the history of how we got there.
+/// Views are kept as fs tree nodes, since the entities they were zoomed to by may be
+/// despawned and materialized again while they're in the history.
+#[derive(Default)]
+pub struct TreeViewZoom {
+    view: ZoomView,
+    back: Vec<ZoomView>,
+    forward: Vec<ZoomView>,
+    /// The current view's entities, as of the last time they were resolved
+    resolved: ResolvedZoomView,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ZoomView {
+    /// `None` stands for the root of the scan
+    layout_root: Option<FsNodeId>,
+    /// When not empty, only these children of the layout root are shown
+    small_items: Vec<FsNodeId>,
+}
+
+impl ZoomView {
+    /// Looks up the view's entities. A layout root that has been removed from the tree is
+    /// drawn as the fs root, and small items that have been removed are left out.
+    fn resolve(&self, fs_entity_map: &FsEntityMap) -> ResolvedZoomView {
+        ResolvedZoomView {
+            layout_root: self
+                .layout_root
+                .and_then(|node_id| fs_entity_map.get(&node_id).copied()),
+            small_items: self
+                .small_items
+                .iter()
+                .filter_map(|node_id| fs_entity_map.get(node_id).copied())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ResolvedZoomView {
+    layout_root: Option<Entity>,
+    small_items: Vec<Entity>,
+}
+
+impl TreeViewZoom {
+    /// The entity that should be laid out across the full width of the view, or `None`
+    /// if that should be the fs root
+    pub fn layout_root(&self) -> Option<Entity> {
+        self.resolved.layout_root
+    }
+
+    /// The children of the layout root that were expanded from a "small items" group, or
+    /// `None` when all of its children are shown
+    pub fn small_items(&self) -> Option<&[Entity]> {
+        if self.resolved.small_items.is_empty() {
+            None
+        } else {
+            Some(&self.resolved.small_items)
+        }
+    }
+
+    /// The nodes of the expanded small items, including any that are too small to have
+    /// been materialized
+    pub fn small_item_nodes(&self) -> &[FsNodeId] {
+        &self.view.small_items
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    fn zoom_to(&mut self, layout_root: Option<FsNodeId>) {
+        self.show(ZoomView {
+            layout_root,
+            small_items: vec![],
+        });
+    }
+
+    fn show(&mut self, view: ZoomView) {
+        if view == self.view {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.view, view);
+        self.back.push(previous);
+        self.forward.clear();
+    }
+
+    fn go_back(&mut self) {
+        if let Some(view) = self.back.pop() {
+            let previous = std::mem::replace(&mut self.view, view);
+            self.forward.push(previous);
+        }
+    }
+
+    fn go_forward(&mut self) {
+        if let Some(view) = self.forward.pop() {
+            let previous = std::mem::replace(&mut self.view, view);
+            self.back.push(previous);
+        }
+    }
+}
+
+/// Requests a change to the [`TreeViewZoom`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZoomCommand {
+    /// Lay out from this directory's entity
+    To(Entity),
+    /// Lay out from the fs root
+    ToRoot,
+    /// Lay out the items of this "small items" group, from the group's parent
+    ExpandSmallItems(Entity),
+    /// Lay out from the current layout root's parent, or show all of the layout root's
+    /// children if some small items were expanded
+    Out,
+    Back,
+    Forward,
+}
+
+#[derive(Component)]
+struct BreadcrumbBar;
+
+#[derive(Component)]
+struct BreadcrumbButton {
+    command: ZoomCommand,
+    is_enabled: bool,
+}
+
+pub struct TreeViewZoomPlugin;
+impl Plugin for TreeViewZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeViewZoom>()
+            .add_event::<ZoomCommand>()
+            .add_startup_system(create_breadcrumb_bar)
+            .add_system(zoom_on_click)
+            .add_system(zoom_on_keys)
+            .add_system(handle_breadcrumb_interactions)
+            .add_system_to_stage(CoreStage::PostUpdate, apply_zoom_commands)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                resolve_zoom_view.after(apply_zoom_commands),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                rebuild_breadcrumbs.after(resolve_zoom_view),
+            );
+    }
+}
+
+/// A left click on a directory zooms into it, a left click on a "small items" group
+/// expands it, and a left click on the current layout root zooms back out. Clicks with
+/// Ctrl or Shift held select instead.
+fn zoom_on_click(
+    mut clicked_events: EventReader<HoverableClicked>,
+    keys: Res<Input<KeyCode>>,
+    fs_entity_query: Query<&FsEntityComponent, Without<FsRootComponent>>,
+    small_items_group_query: Query<(), With<SmallItemsGroup>>,
+    zoom: Res<TreeViewZoom>,
+    mut zoom_commands: EventWriter<ZoomCommand>,
+) {
+    let is_selecting = keys.any_pressed([
+        KeyCode::LControl,
+        KeyCode::RControl,
+        KeyCode::LShift,
+        KeyCode::RShift,
+    ]);
+    for HoverableClicked { entity, button } in clicked_events.iter() {
+        if *button != MouseButton::Left || is_selecting {
+            continue;
+        }
+        if small_items_group_query.contains(*entity) {
+            zoom_commands.send(ZoomCommand::ExpandSmallItems(*entity));
+            continue;
+        }
+
+        match fs_entity_query.get(*entity) {
+            Ok(_) if zoom.layout_root() == Some(*entity) => {
+                zoom_commands.send(ZoomCommand::Out)
+            }
+            Ok(fs_entity) if fs_entity.is_dir() => {
+                zoom_commands.send(ZoomCommand::To(*entity))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Alt+← and Alt+→ move through the history
+fn zoom_on_keys(keys: Res<Input<KeyCode>>, mut zoom_commands: EventWriter<ZoomCommand>) {
+    let is_alt_down = keys.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if is_alt_down && keys.just_pressed(KeyCode::Left) {
+        zoom_commands.send(ZoomCommand::Back);
+    }
+    if is_alt_down && keys.just_pressed(KeyCode::Right) {
+        zoom_commands.send(ZoomCommand::Forward);
+    }
+}
+
+fn apply_zoom_commands(
+    mut zoom_commands: EventReader<ZoomCommand>,
+    mut zoom: ResMut<TreeViewZoom>,
+    fs_tree: Res<FsTree>,
+    fs_entity_map: Res<FsEntityMap>,
+    fs_entity_query: Query<&FsEntityComponent>,
+    small_items_group_query: Query<&SmallItemsGroup>,
+) {
+    // The fs root is the default layout root, so it's never stored
+    let non_root_node = |entity: Entity| {
+        fs_entity_query
+            .get(entity)
+            .ok()
+            .map(|fs_entity| fs_entity.node_id)
+            .filter(|node_id| *node_id != FsNodeId::ROOT)
+    };
+
+    for command in zoom_commands.iter() {
+        debug!(?command, "applying zoom command");
+        match *command {
+            ZoomCommand::To(entity) => {
+                if fs_entity_query.contains(entity) {
+                    zoom.zoom_to(non_root_node(entity));
+                }
+            }
+            ZoomCommand::ToRoot => zoom.zoom_to(None),
+            ZoomCommand::ExpandSmallItems(group) => {
+                let group = match small_items_group_query.get(group) {
+                    Ok(group) => group,
+                    Err(_) => continue,
+                };
+                let parent = match fs_entity_query.get(group.parent) {
+                    Ok(parent) => parent,
+                    Err(_) => continue,
+                };
+                // Children that are too small to be materialized are in the group too
+                let items: HashSet<Entity> = group.items.iter().copied().collect();
+                let small_items = fs_tree
+                    .children(parent.node_id)
+                    .filter(|child| match fs_entity_map.get(child) {
+                        Some(entity) => items.contains(entity),
+                        None => true,
+                    })
+                    .collect();
+                zoom.show(ZoomView {
+                    layout_root: non_root_node(group.parent),
+                    small_items,
+                });
+            }
+            ZoomCommand::Out if !zoom.view.small_items.is_empty() => {
+                let layout_root = zoom.view.layout_root;
+                zoom.zoom_to(layout_root);
+            }
+            ZoomCommand::Out => {
+                // Removed nodes still know their parent
+                if let Some(layout_root) = zoom.view.layout_root {
+                    let parent = fs_tree
+                        .parent(layout_root)
+                        .filter(|parent| *parent != FsNodeId::ROOT);
+                    zoom.zoom_to(parent);
+                }
+            }
+            ZoomCommand::Back => zoom.go_back(),
+            ZoomCommand::Forward => zoom.go_forward(),
+        }
+    }
+}
+
+/// Looks up the entities of the current view whenever it, or the materialized entities,
+/// change
+fn resolve_zoom_view(mut zoom: ResMut<TreeViewZoom>, fs_entity_map: Res<FsEntityMap>) {
+    if !zoom.is_changed() && !fs_entity_map.is_changed() {
+        return;
+    }
+    let resolved = zoom.view.resolve(&fs_entity_map);
+    if resolved != zoom.resolved {
+        debug!(?resolved, "zoom view resolved");
+        zoom.resolved = resolved;
+    }
+}
+
+/// The bar sits in the bottom left corner, out of the way of the diagnostics text
+fn create_breadcrumb_bar(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(16.0),
+                    left: Val::Px(16.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Row,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(BreadcrumbBar);
+}
+
+/// Replaces the bar's buttons whenever the zoom or the theme changes. The crumbs are the
+/// layout root and its ancestors, named from the [`FsTree`].
+#[allow(clippy::too_many_arguments)]
+fn rebuild_breadcrumbs(
+    mut commands: Commands,
+    zoom: Res<TreeViewZoom>,
+    fs_tree: Res<FsTree>,
+    breadcrumb_bar_query: Query<(Entity, Option<&Children>), With<BreadcrumbBar>>,
+    fs_entity_query: Query<(&FsEntityComponent, Option<&Parent>)>,
+    theme: Res<Theme>,
+    font: Res<PanelFont>,
+    mut is_initialized: Local<bool>,
+) {
+    if (*is_initialized && !zoom.is_changed() && !theme.is_changed())
+        || breadcrumb_bar_query.is_empty()
+        || fs_tree.is_empty()
+    {
+        return;
+    }
+    *is_initialized = true;
+
+    let (bar, maybe_crumbs) = breadcrumb_bar_query.single();
+    if let Some(crumbs) = maybe_crumbs {
+        for crumb in crumbs.iter() {
+            commands.entity(*crumb).despawn_recursive();
+        }
+    }
+
+    // Walk up from the layout root to find the crumbs
+    let mut path: Vec<(Entity, String)> = vec![];
+    let mut next = zoom.layout_root();
+    while let Some(entity) = next {
+        let (fs_entity, maybe_parent) = match fs_entity_query.get(entity) {
+            Ok(res) => res,
+            Err(_) => break,
+        };
+        if fs_entity.depth == 0 {
+            break;
+        }
+        path.push((
+            entity,
+            fs_tree.name(fs_entity.node_id).to_string_lossy().into(),
+        ));
+        next = maybe_parent.map(|p| p.0);
+    }
+
+    let mut spawn_button = |label: String, command: ZoomCommand, is_enabled: bool| {
+        let button = commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    padding: Rect::all(Val::Px(6.0)),
+                    margin: Rect {
+                        right: Val::Px(4.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                color: theme.panel.into(),
+                ..default()
+            })
+            .insert(BreadcrumbButton {
+                command,
+                is_enabled,
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        label,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: BREADCRUMB_FONT_SIZE,
+                            color: if is_enabled {
+                                theme.panel_text
+                            } else {
+                                theme.panel_text_dim
+                            },
+                        },
+                        default(),
+                    ),
+                    ..default()
+                });
+            })
+            .id();
+        commands.entity(bar).add_child(button);
+    };
+
+    spawn_button("‹".into(), ZoomCommand::Back, zoom.can_go_back());
+    spawn_button("›".into(), ZoomCommand::Forward, zoom.can_go_forward());
+    // The layout root's crumb stays enabled while small items are expanded, to show all
+    // of its children again
+    let small_items = zoom.small_items();
+    spawn_button(
+        fs_tree.root_path().display().to_string(),
+        ZoomCommand::ToRoot,
+        zoom.layout_root().is_some() || small_items.is_some(),
+    );
+    let crumb_count = path.len();
+    for (i, (entity, name)) in path.into_iter().rev().enumerate() {
+        let is_layout_root = i == crumb_count - 1;
+        spawn_button(
+            name,
+            ZoomCommand::To(entity),
+            !is_layout_root || small_items.is_some(),
+        );
+    }
+    if small_items.is_some() {
+        // Counted like the group that was expanded, including the items too small to
+        // have been materialized
+        let small_item_count = zoom
+            .small_item_nodes()
+            .iter()
+            .filter(|node_id| fs_tree.contains(**node_id))
+            .count();
+        spawn_button(
+            format!("{small_item_count} small items"),
+            ZoomCommand::Out,
+            false,
+        );
+    }
+}
+
+fn handle_breadcrumb_interactions(
+    mut button_query: Query<
+        (&Interaction, &BreadcrumbButton, &mut UiColor),
+        Changed<Interaction>,
+    >,
+    mut zoom_commands: EventWriter<ZoomCommand>,
+    theme: Res<Theme>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        if !button.is_enabled {
+            continue;
+        }
+
+        if *interaction == Interaction::Clicked {
+            zoom_commands.send(button.command);
+        }
+        // Crumbs are drawn over the tree, so they keep a background when not hovered
+        *color = match *interaction {
+            Interaction::None => theme.panel.into(),
+            _ => menu_item_color(&theme, *interaction),
+        };
+    }
+}